@@ -0,0 +1,482 @@
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::format::{Item, StrftimeItems};
+use datafusion::arrow::array::timezone::Tz;
+use datafusion::arrow::util::display::{DurationFormat, FormatOptions};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use std::{env, fs};
+use toml_edit::DocumentMut;
+
+/// Runs kept per cell unless configured otherwise
+pub const DEFAULT_RUN_HISTORY: usize = 10;
+
+/// Results of runs kept per cell unless configured otherwise: the shown one and the one before it
+pub const DEFAULT_RUN_RESULTS: usize = 2;
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Default query timeout, can be overridden per cell with `-- @timeout`
+    pub timeout: Option<Duration>,
+    /// Maximum number of rows kept from a query result, unlimited if not set
+    pub max_rows: Option<usize>,
+    /// Number of runs kept per cell
+    pub run_history: usize,
+    /// Number of the latest runs which keep their results, older ones keep only the row count
+    pub run_results: usize,
+    pub memory: MemoryConfig,
+    /// DataFusion options applied to the session on start, e.g. `datafusion.execution.batch_size`
+    pub session: BTreeMap<String, String>,
+    pub format: FormatConfig,
+    pub formatter: FormatterConfig,
+    /// Sections of the terminal UI (`theme`, `keymap`, `vim`, `sidebar`), parsed by `tui::config`
+    pub ui: toml::Table,
+    /// SQL scripts executed when the session starts
+    pub startup: Vec<PathBuf>,
+    /// File the config was loaded from and where changes are persisted
+    pub path: Option<PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            timeout: None,
+            max_rows: None,
+            run_history: DEFAULT_RUN_HISTORY,
+            run_results: DEFAULT_RUN_RESULTS,
+            memory: MemoryConfig::default(),
+            session: BTreeMap::new(),
+            format: FormatConfig::default(),
+            formatter: FormatterConfig::default(),
+            ui: toml::Table::new(),
+            startup: Vec::new(),
+            path: None,
+        }
+    }
+}
+
+/// How values are rendered in results, date and time formats use chrono `strftime` syntax
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FormatConfig {
+    pub null: String,
+    pub date: Option<String>,
+    pub datetime: Option<String>,
+    pub timestamp: Option<String>,
+    pub timestamp_tz: Option<String>,
+    pub time: Option<String>,
+    pub duration: DurationStyle,
+    /// Digits after the decimal point of floats, as computed if not set
+    pub float_precision: Option<usize>,
+    /// Groups digits of numbers by thousands, e.g. `","`
+    pub thousands_separator: Option<char>,
+    /// Timestamps with a timezone are shown in this one, e.g. `Europe/Kyiv` or `+02:00`
+    pub timezone: Option<String>,
+}
+
+impl Default for FormatConfig {
+    fn default() -> Self {
+        Self {
+            null: "NULL".to_string(),
+            date: None,
+            datetime: None,
+            timestamp: None,
+            timestamp_tz: None,
+            time: None,
+            duration: DurationStyle::default(),
+            float_precision: None,
+            thousands_separator: None,
+            timezone: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DurationStyle {
+    /// `P198DT72932.972880S`
+    Iso8601,
+    /// `198 days 16 hours 34 mins 15.407810000 secs`
+    #[default]
+    Pretty,
+}
+
+impl FormatConfig {
+    pub fn options(&self) -> FormatOptions<'_> {
+        FormatOptions::new()
+            .with_display_error(true)
+            .with_null(&self.null)
+            .with_date_format(self.date.as_deref())
+            .with_datetime_format(self.datetime.as_deref())
+            .with_timestamp_format(self.timestamp.as_deref())
+            .with_timestamp_tz_format(self.timestamp_tz.as_deref())
+            .with_time_format(self.time.as_deref())
+            .with_duration_format(match self.duration {
+                DurationStyle::Iso8601 => DurationFormat::ISO8601,
+                DurationStyle::Pretty => DurationFormat::Pretty,
+            })
+    }
+
+    fn validate(&self) -> Result<()> {
+        let formats = [
+            ("date", &self.date),
+            ("datetime", &self.datetime),
+            ("timestamp", &self.timestamp),
+            ("timestamp_tz", &self.timestamp_tz),
+            ("time", &self.time),
+        ];
+        for (name, format) in formats {
+            if let Some(format) = format {
+                if StrftimeItems::new(format).any(|item| item == Item::Error) {
+                    bail!("format.{}: invalid strftime format '{}'", name, format);
+                }
+            }
+        }
+        if let Some(timezone) = &self.timezone {
+            if timezone.parse::<Tz>().is_err() {
+                bail!("format.timezone: unknown timezone '{}'", timezone);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// How SQL of cells is pretty-printed
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FormatterConfig {
+    pub keyword_case: KeywordCase,
+    /// Spaces per indentation level
+    pub indent: usize,
+}
+
+impl Default for FormatterConfig {
+    fn default() -> Self {
+        Self {
+            keyword_case: KeywordCase::default(),
+            indent: 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeywordCase {
+    #[default]
+    Upper,
+    Lower,
+    /// Keywords are kept as written
+    Preserve,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MemoryConfig {
+    /// Memory pool size in bytes, unbounded if not set
+    pub limit: Option<usize>,
+    pub pool: MemoryPoolKind,
+    /// Directory for spill files, OS temp directory if not set
+    pub spill_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum MemoryPoolKind {
+    /// Splits the memory fairly between spillable operators
+    #[default]
+    Fair,
+    /// First come first served
+    Greedy,
+}
+
+/// Raw representation of the config file, values are validated in `Config::load`
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    timeout: Option<String>,
+    /// `0` disables the limit
+    max_rows: Option<usize>,
+    /// `0` disables the history
+    run_history: Option<usize>,
+    run_results: Option<usize>,
+    #[serde(default)]
+    memory: MemoryConfigFile,
+    #[serde(default)]
+    session: BTreeMap<String, toml::Value>,
+    #[serde(default)]
+    format: FormatConfig,
+    #[serde(default)]
+    formatter: FormatterConfig,
+    /// Sections of the terminal UI, kept as they are for `tui::config`
+    theme: Option<toml::Value>,
+    keymap: Option<toml::Value>,
+    vim: Option<toml::Value>,
+    sidebar: Option<toml::Value>,
+    #[serde(default)]
+    startup: Vec<PathBuf>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MemoryConfigFile {
+    limit: Option<String>,
+    pool: Option<MemoryPoolKind>,
+    spill_dir: Option<PathBuf>,
+}
+
+impl Config {
+    /// `$XDG_CONFIG_HOME/exo/config.toml`, falling back to `~/.config/exo/config.toml`
+    pub fn default_path() -> Option<PathBuf> {
+        env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+            .map(|dir| dir.join("exo").join("config.toml"))
+    }
+
+    /// Loads the config from the default location, a missing file means default config
+    pub fn load_default() -> Result<Self> {
+        match Self::default_path() {
+            Some(path) if path.exists() => Self::load(&path),
+            path => Ok(Self {
+                path,
+                ..Self::default()
+            }),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Cannot read config file {}", path.display()))?;
+        let mut config = Self::parse(&content)
+            .with_context(|| format!("Invalid config file {}", path.display()))?;
+
+        // startup scripts are relative to the config file
+        let dir = path.parent().unwrap_or(Path::new("."));
+        for script in config.startup.iter_mut() {
+            if script.is_relative() {
+                *script = dir.join(&script);
+            }
+            if !script.is_file() {
+                bail!(
+                    "Invalid config file {}: startup script {} does not exist",
+                    path.display(),
+                    script.display()
+                );
+            }
+        }
+        config.path = Some(path.to_path_buf());
+        Ok(config)
+    }
+
+    /// Replaces the `[session]` table of the config file, keeping the rest of the file as it is,
+    /// including comments and unchanged options
+    pub fn save_session_options(&self, options: &BTreeMap<String, String>) -> Result<PathBuf> {
+        let path = self
+            .path
+            .clone()
+            .ok_or_else(|| anyhow!("Config file location is unknown"))?;
+        let mut document = match fs::read_to_string(&path) {
+            Ok(content) => content
+                .parse::<DocumentMut>()
+                .with_context(|| format!("Invalid config file {}", path.display()))?,
+            Err(_) => DocumentMut::new(),
+        };
+        let session = document
+            .entry("session")
+            .or_insert(toml_edit::table())
+            .as_table_like_mut()
+            .ok_or_else(|| {
+                anyhow!(
+                    "Invalid config file {}: session is not a table",
+                    path.display()
+                )
+            })?;
+
+        // unchanged options keep their formatting and comments
+        let reset = session
+            .iter()
+            .map(|(key, _)| key.to_string())
+            .filter(|key| !options.contains_key(key))
+            .collect::<Vec<_>>();
+        for key in reset {
+            session.remove(&key);
+        }
+        for (key, value) in options {
+            let current = session
+                .get(key)
+                .and_then(|item| item.as_value())
+                .map(|current| match current.as_str() {
+                    Some(current) => current.to_string(),
+                    None => current.clone().decorated("", "").to_string(),
+                });
+            if current.as_ref() != Some(value) {
+                session.insert(key, toml_edit::value(value.as_str()));
+            }
+        }
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&path, document.to_string())
+            .with_context(|| format!("Cannot write config file {}", path.display()))?;
+        Ok(path)
+    }
+
+    pub fn parse(content: &str) -> Result<Self> {
+        let file: ConfigFile = toml::from_str(content)?;
+
+        let timeout = file
+            .timeout
+            .map(|value| {
+                humantime::parse_duration(&value)
+                    .map_err(|err| anyhow!("timeout: invalid duration '{}': {}", value, err))
+            })
+            .transpose()?;
+
+        let limit = file
+            .memory
+            .limit
+            .map(|value| parse_bytes(&value).context("memory.limit"))
+            .transpose()?;
+
+        let session = file
+            .session
+            .into_iter()
+            .map(|(key, value)| match value {
+                toml::Value::String(s) => Ok((key, s)),
+                toml::Value::Integer(_) | toml::Value::Float(_) | toml::Value::Boolean(_) => {
+                    Ok((key, value.to_string()))
+                }
+                _ => bail!("session.\"{}\": expected a string, number or boolean", key),
+            })
+            .collect::<Result<_>>()?;
+
+        file.format.validate()?;
+
+        let ui = [
+            ("theme", file.theme),
+            ("keymap", file.keymap),
+            ("vim", file.vim),
+            ("sidebar", file.sidebar),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| Some((key.to_string(), value?)))
+        .collect();
+
+        Ok(Self {
+            timeout,
+            max_rows: file.max_rows.filter(|&rows| rows > 0),
+            run_history: file.run_history.unwrap_or(DEFAULT_RUN_HISTORY),
+            run_results: file.run_results.unwrap_or(DEFAULT_RUN_RESULTS),
+            memory: MemoryConfig {
+                limit,
+                pool: file.memory.pool.unwrap_or_default(),
+                spill_dir: file.memory.spill_dir,
+            },
+            session,
+            format: file.format,
+            formatter: file.formatter,
+            ui,
+            startup: file.startup,
+            path: None,
+        })
+    }
+}
+
+/// Parses sizes like `512MB`, `1.5GiB` or `1048576`. Units are powers of 1024.
+pub fn parse_bytes(value: &str) -> Result<usize> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| anyhow!("invalid size '{}'", value))?;
+    let multiplier: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        "T" | "TB" | "TIB" => 1 << 40,
+        _ => bail!(
+            "invalid size '{}', expected a number with an optional unit (KB, MB, GB, TB)",
+            value
+        ),
+    };
+    Ok((number * multiplier as f64) as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn sizes() {
+        assert_eq!(parse_bytes("1048576").unwrap(), 1 << 20);
+        assert_eq!(parse_bytes("512MB").unwrap(), 512 << 20);
+        assert_eq!(parse_bytes(" 4 gib ").unwrap(), 4 << 30);
+        assert_eq!(parse_bytes("1.5K").unwrap(), 1536);
+        assert_eq!(parse_bytes("2b").unwrap(), 2);
+        assert_eq!(parse_bytes("1TB").unwrap(), 1 << 40);
+        assert!(parse_bytes("").is_err());
+        assert!(parse_bytes("MB").is_err());
+        assert!(parse_bytes("1.2.3GB").is_err());
+        assert!(parse_bytes("-1GB").is_err());
+        assert!(parse_bytes("10 PB")
+            .unwrap_err()
+            .to_string()
+            .contains("expected a number with an optional unit"));
+    }
+
+    #[test]
+    fn session_options_keep_the_rest_of_the_file() {
+        let path = env::temp_dir().join(format!("exo-config-{}.toml", std::process::id()));
+        fs::write(
+            &path,
+            indoc! {r#"
+                # query timeout
+                timeout = "5m" # long enough
+
+                [session]
+                # bigger batches
+                "datafusion.execution.batch_size" = 8192 # rows
+                "datafusion.execution.target_partitions" = "4"
+
+                [format]
+                null = "-"
+            "#},
+        )
+        .unwrap();
+
+        let config = Config::load(&path).unwrap();
+        let mut options = config.session.clone();
+        options.remove("datafusion.execution.target_partitions");
+        options.insert(
+            "datafusion.sql_parser.enable_ident_normalization".to_string(),
+            "false".to_string(),
+        );
+        config.save_session_options(&options).unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            content,
+            indoc! {r#"
+                # query timeout
+                timeout = "5m" # long enough
+
+                [session]
+                # bigger batches
+                "datafusion.execution.batch_size" = 8192 # rows
+                "datafusion.sql_parser.enable_ident_normalization" = "false"
+
+                [format]
+                null = "-"
+            "#}
+        );
+        assert_eq!(Config::parse(&content).unwrap().session, options);
+    }
+}
@@ -0,0 +1,486 @@
+use crate::config::FormatConfig;
+use crate::core::display::ValueFormatter;
+use anyhow::{anyhow, bail, Result};
+use datafusion::arrow::array::{Array, AsArray, RecordBatch, TimestampMillisecondArray};
+use datafusion::arrow::compute::cast;
+use datafusion::arrow::datatypes::{DataType, Float64Type, Schema, TimeUnit};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChartKind {
+    Bar,
+    Line,
+    Scatter,
+}
+
+impl ChartKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChartKind::Bar => "bar",
+            ChartKind::Line => "line",
+            ChartKind::Scatter => "scatter",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            ChartKind::Bar => ChartKind::Line,
+            ChartKind::Line => ChartKind::Scatter,
+            ChartKind::Scatter => ChartKind::Bar,
+        }
+    }
+}
+
+impl FromStr for ChartKind {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value {
+            "bar" => Ok(ChartKind::Bar),
+            "line" => Ok(ChartKind::Line),
+            "scatter" => Ok(ChartKind::Scatter),
+            _ => bail!("unknown chart '{}', expected bar, line or scatter", value),
+        }
+    }
+}
+
+/// Chart requested with `-- @chart line x=day y=total series=region`, everything is optional
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ChartSpec {
+    pub kind: Option<ChartKind>,
+    pub x: Option<String>,
+    pub y: Option<String>,
+    pub series: Option<String>,
+}
+
+impl FromStr for ChartSpec {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let mut spec = ChartSpec::default();
+        for part in value.split_whitespace() {
+            match part.split_once('=') {
+                Some(("x", column)) => spec.x = Some(column.to_string()),
+                Some(("y", column)) => spec.y = Some(column.to_string()),
+                Some(("series", column)) => spec.series = Some(column.to_string()),
+                Some((key, _)) => bail!("unknown chart option '{}'", key),
+                None => spec.kind = Some(part.parse()?),
+            }
+        }
+        Ok(spec)
+    }
+}
+
+/// Columns of the result shown in a chart
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChartColumns {
+    pub kind: ChartKind,
+    pub x: usize,
+    pub y: usize,
+    pub series: Option<usize>,
+}
+
+impl ChartColumns {
+    /// Columns named in the spec, otherwise the first temporal or first column as x
+    /// and the first other numeric column as y
+    pub fn resolve(schema: &Schema, spec: &ChartSpec) -> Result<Self> {
+        let index = |name: &str| {
+            schema
+                .index_of(name)
+                .map_err(|_| anyhow!("chart column '{}' not found", name))
+        };
+        let x = match &spec.x {
+            Some(name) => index(name)?,
+            None => schema
+                .fields()
+                .iter()
+                .position(|f| is_temporal(f.data_type()))
+                .unwrap_or(0),
+        };
+        let y = match &spec.y {
+            Some(name) => index(name)?,
+            None => schema
+                .fields()
+                .iter()
+                .enumerate()
+                .position(|(i, f)| i != x && f.data_type().is_numeric())
+                .ok_or_else(|| anyhow!("no numeric column to chart"))?,
+        };
+        if !schema.field(y).data_type().is_numeric() {
+            bail!("chart column '{}' is not numeric", schema.field(y).name());
+        }
+        let series = spec.series.as_deref().map(index).transpose()?;
+        let x_type = schema.field(x).data_type();
+        let kind = spec
+            .kind
+            .unwrap_or(if x_type.is_numeric() || is_temporal(x_type) {
+                ChartKind::Line
+            } else {
+                ChartKind::Bar
+            });
+        Ok(Self { kind, x, y, series })
+    }
+}
+
+fn timezone(data_type: &DataType) -> Option<Arc<str>> {
+    match data_type {
+        DataType::Timestamp(_, timezone) => timezone.clone(),
+        _ => None,
+    }
+}
+
+fn is_temporal(data_type: &DataType) -> bool {
+    matches!(
+        data_type,
+        DataType::Date32 | DataType::Date64 | DataType::Timestamp(_, _)
+    )
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum XAxis {
+    Numeric,
+    /// Milliseconds since the epoch, labelled like values of the column type
+    Temporal(DataType),
+    /// Values are positions of the labels
+    Category(Vec<String>),
+}
+
+impl XAxis {
+    pub fn label(&self, value: f64, format: &FormatConfig) -> String {
+        match self {
+            XAxis::Numeric => format_number(value),
+            XAxis::Temporal(data_type) => {
+                temporal_label(value, data_type, format).unwrap_or_default()
+            }
+            XAxis::Category(labels) => labels
+                .get(value.round() as usize)
+                .cloned()
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Milliseconds since the epoch as a value of the column type, formatted as in the table
+fn temporal_label(millis: f64, data_type: &DataType, format: &FormatConfig) -> Option<String> {
+    let array = TimestampMillisecondArray::from(vec![millis.floor() as i64])
+        .with_timezone_opt(timezone(data_type));
+    let array = cast(&array, data_type).ok()?;
+    let formatter = ValueFormatter::try_new(array.as_ref(), format).ok()?;
+    Some(formatter.value(0))
+}
+
+pub fn format_number(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < 1e15 {
+        format!("{}", value as i64)
+    } else {
+        format!("{:.2}", value)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Series {
+    pub name: String,
+    pub points: Vec<(f64, f64)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChartData {
+    pub x_axis: XAxis,
+    pub series: Vec<Series>,
+}
+
+/// Points of the chart ordered by x, rows with NULL in x or y are skipped.
+/// For bars the values with the same x are summed up, like in a GROUP BY.
+pub fn chart_data(
+    batches: &[RecordBatch],
+    columns: &ChartColumns,
+    format: &FormatConfig,
+) -> Result<ChartData> {
+    let Some(first) = batches.first() else {
+        bail!("no rows to chart");
+    };
+    let x_type = first.schema().field(columns.x).data_type().clone();
+    let mut x_axis = if x_type.is_numeric() {
+        XAxis::Numeric
+    } else if is_temporal(&x_type) {
+        XAxis::Temporal(x_type.clone())
+    } else {
+        XAxis::Category(Vec::new())
+    };
+
+    let mut series: Vec<Series> = Vec::new();
+    // positions of categories and series by name, results can have many of both
+    let mut categories = HashMap::new();
+    let mut series_index = HashMap::new();
+    for batch in batches {
+        let x = batch.column(columns.x);
+        let y = cast(batch.column(columns.y), &DataType::Float64)?;
+        let y = y.as_primitive::<Float64Type>();
+        let xs = match &x_axis {
+            XAxis::Numeric => Some(
+                cast(x, &DataType::Float64)?
+                    .as_primitive::<Float64Type>()
+                    .clone(),
+            ),
+            XAxis::Temporal(data_type) => {
+                // the timezone is kept, so values stay points in time
+                let millis = cast(
+                    x,
+                    &DataType::Timestamp(TimeUnit::Millisecond, timezone(data_type)),
+                )?;
+                let millis = cast(&millis, &DataType::Int64)?;
+                Some(
+                    cast(&millis, &DataType::Float64)?
+                        .as_primitive::<Float64Type>()
+                        .clone(),
+                )
+            }
+            XAxis::Category(_) => None,
+        };
+        let x_labels = ValueFormatter::try_new(x.as_ref(), format)?;
+        let names = columns
+            .series
+            .map(|i| ValueFormatter::try_new(batch.column(i).as_ref(), format))
+            .transpose()?;
+
+        for row in 0..batch.num_rows() {
+            if x.is_null(row) || y.is_null(row) {
+                continue;
+            }
+            let x_value = match (&xs, &mut x_axis) {
+                (Some(xs), _) => xs.value(row),
+                (None, XAxis::Category(labels)) => {
+                    let label = x_labels.value(row);
+                    *categories.entry(label).or_insert_with_key(|label| {
+                        labels.push(label.clone());
+                        (labels.len() - 1) as f64
+                    })
+                }
+                (None, _) => unreachable!("only categories have no numeric x"),
+            };
+            let name = names
+                .as_ref()
+                .map(|names| names.value(row))
+                .unwrap_or_else(|| first.schema().field(columns.y).name().clone());
+            let point = (x_value, y.value(row));
+            let index = *series_index.entry(name).or_insert_with_key(|name| {
+                series.push(Series {
+                    name: name.clone(),
+                    points: Vec::new(),
+                });
+                series.len() - 1
+            });
+            series[index].points.push(point);
+        }
+    }
+    for series in series.iter_mut() {
+        // lines are drawn from point to point, so they go along x
+        series.points.sort_by(|a, b| a.0.total_cmp(&b.0));
+        if columns.kind == ChartKind::Bar {
+            series.points.dedup_by(|point, previous| {
+                let same = point.0 == previous.0;
+                if same {
+                    previous.1 += point.1;
+                }
+                same
+            });
+        }
+    }
+    Ok(ChartData { x_axis, series })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::{
+        ArrayRef, Date32Array, Float64Array, StringArray, TimestampSecondArray,
+    };
+    use datafusion::arrow::datatypes::Field;
+
+    fn result(columns: Vec<(&str, ArrayRef)>) -> RecordBatch {
+        let fields = columns
+            .iter()
+            .map(|(name, array)| Field::new(*name, array.data_type().clone(), true))
+            .collect::<Vec<_>>();
+        let arrays = columns.into_iter().map(|(_, array)| array).collect();
+        RecordBatch::try_new(Arc::new(Schema::new(fields)), arrays).unwrap()
+    }
+
+    fn columns(batch: &RecordBatch, spec: &str) -> ChartColumns {
+        ChartColumns::resolve(&batch.schema(), &spec.parse().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn spec() {
+        let spec = "line x=day series=region".parse::<ChartSpec>().unwrap();
+        assert_eq!(spec.kind, Some(ChartKind::Line));
+        assert_eq!(spec.x.as_deref(), Some("day"));
+        assert_eq!(spec.y, None);
+        assert_eq!(spec.series.as_deref(), Some("region"));
+        assert!("pie".parse::<ChartSpec>().is_err());
+        assert!("bar colour=red".parse::<ChartSpec>().is_err());
+    }
+
+    #[test]
+    fn resolve_defaults() {
+        let batch = result(vec![
+            ("name", Arc::new(StringArray::from(vec!["a"])) as ArrayRef),
+            ("day", Arc::new(Date32Array::from(vec![0]))),
+            ("total", Arc::new(Float64Array::from(vec![1.0]))),
+        ]);
+        let resolved = columns(&batch, "");
+        assert_eq!(
+            (resolved.kind, resolved.x, resolved.y),
+            (ChartKind::Line, 1, 2)
+        );
+        let resolved = columns(&batch, "x=name");
+        assert_eq!(
+            (resolved.kind, resolved.x, resolved.y),
+            (ChartKind::Bar, 0, 2)
+        );
+        let schema = batch.schema();
+        assert!(ChartColumns::resolve(&schema, &"y=name".parse().unwrap()).is_err());
+        assert!(ChartColumns::resolve(&schema, &"x=nope".parse().unwrap()).is_err());
+    }
+
+    #[test]
+    fn categories_and_series() {
+        let batch = result(vec![
+            (
+                "region",
+                Arc::new(StringArray::from(vec!["eu", "us", "eu", "us"])) as ArrayRef,
+            ),
+            (
+                "year",
+                Arc::new(StringArray::from(vec!["2023", "2023", "2024", "2024"])),
+            ),
+            (
+                "total",
+                Arc::new(Float64Array::from(vec![
+                    Some(1.0),
+                    Some(2.0),
+                    None,
+                    Some(4.0),
+                ])),
+            ),
+        ]);
+        let data = chart_data(
+            std::slice::from_ref(&batch),
+            &columns(&batch, "x=year series=region"),
+            &FormatConfig::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            data.x_axis,
+            XAxis::Category(vec!["2023".into(), "2024".into()])
+        );
+        assert_eq!(
+            data.series,
+            vec![
+                Series {
+                    name: "eu".into(),
+                    points: vec![(0.0, 1.0)],
+                },
+                Series {
+                    name: "us".into(),
+                    points: vec![(0.0, 2.0), (1.0, 4.0)],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn bars_sum_up_and_lines_go_along_x() {
+        let result = result(vec![
+            (
+                "x",
+                Arc::new(Float64Array::from(vec![3.0, 1.0, 3.0, 2.0])) as ArrayRef,
+            ),
+            ("y", Arc::new(Float64Array::from(vec![1.0, 2.0, 3.0, 4.0]))),
+        ]);
+        let batches = std::slice::from_ref(&result);
+        let format = FormatConfig::default();
+        let line = chart_data(batches, &columns(&result, "line"), &format).unwrap();
+        assert_eq!(
+            line.series[0].points,
+            vec![(1.0, 2.0), (2.0, 4.0), (3.0, 1.0), (3.0, 3.0)]
+        );
+        let bar = chart_data(batches, &columns(&result, "bar"), &format).unwrap();
+        assert_eq!(
+            bar.series[0].points,
+            vec![(1.0, 2.0), (2.0, 4.0), (3.0, 4.0)]
+        );
+    }
+
+    #[test]
+    fn many_categories() {
+        let labels = (0..10_000)
+            .map(|i| format!("c{}", i % 5_000))
+            .collect::<Vec<_>>();
+        let result = result(vec![
+            ("label", Arc::new(StringArray::from(labels)) as ArrayRef),
+            ("y", Arc::new(Float64Array::from(vec![1.0; 10_000]))),
+        ]);
+        let data = chart_data(
+            std::slice::from_ref(&result),
+            &columns(&result, ""),
+            &FormatConfig::default(),
+        )
+        .unwrap();
+        let XAxis::Category(labels) = &data.x_axis else {
+            panic!("expected categories");
+        };
+        assert_eq!(labels.len(), 5_000);
+        assert_eq!(data.series[0].points.len(), 5_000);
+        assert!(data.series[0].points.iter().all(|(_, y)| *y == 2.0));
+    }
+
+    #[test]
+    fn temporal_labels_follow_the_format() {
+        let day = 19_723; // 2024-01-01
+        let format = FormatConfig {
+            date: Some("%d.%m.%Y".into()),
+            timestamp: Some("%Y-%m-%d %H:%M".into()),
+            timestamp_tz: Some("%H:%M %z".into()),
+            ..FormatConfig::default()
+        };
+        let millis = day as f64 * 86_400_000.0;
+        assert_eq!(
+            XAxis::Temporal(DataType::Date32).label(millis, &format),
+            "01.01.2024"
+        );
+
+        let batch = result(vec![
+            (
+                "at",
+                Arc::new(TimestampSecondArray::from(vec![day * 86_400 + 3_600])) as ArrayRef,
+            ),
+            ("total", Arc::new(Float64Array::from(vec![1.0]))),
+        ]);
+        let data = chart_data(std::slice::from_ref(&batch), &columns(&batch, ""), &format).unwrap();
+        let (x, _) = data.series[0].points[0];
+        assert_eq!(data.x_axis.label(x, &format), "2024-01-01 01:00");
+
+        // timestamps with a timezone are labelled in it, like in the table
+        let utc = TimestampSecondArray::from(vec![day * 86_400 + 3_600]).with_timezone("UTC");
+        let local =
+            crate::core::display::localize_array(&(Arc::new(utc) as ArrayRef), "+02:00").unwrap();
+        let batch = result(vec![
+            ("at", local),
+            ("total", Arc::new(Float64Array::from(vec![1.0]))),
+        ]);
+        let data = chart_data(std::slice::from_ref(&batch), &columns(&batch, ""), &format).unwrap();
+        let (x, _) = data.series[0].points[0];
+        assert_eq!(data.x_axis.label(x, &format), "03:00 +0200");
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(format_number(3.0), "3");
+        assert_eq!(format_number(-2.5), "-2.50");
+        assert_eq!(format_number(1e16), "10000000000000000.00");
+        assert_eq!(XAxis::Numeric.label(4.0, &FormatConfig::default()), "4");
+    }
+}
@@ -0,0 +1,510 @@
+//! Comparison of two results: rows matched by key columns, and how the schemas differ.
+
+use crate::core::QueryResult;
+use anyhow::{anyhow, bail, Result};
+use datafusion::arrow::array::{Array, RecordBatch};
+use datafusion::arrow::compute::{can_cast_types, cast_with_options, concat_batches, CastOptions};
+use datafusion::arrow::datatypes::{DataType, Schema, SchemaRef};
+use datafusion::arrow::util::display::{ArrayFormatter, FormatOptions};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SchemaChange {
+    Added(String, DataType),
+    Removed(String, DataType),
+    TypeChanged {
+        name: String,
+        before: DataType,
+        after: DataType,
+        /// Values before can be converted to the new type, otherwise the column is not compared
+        comparable: bool,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RowDiff {
+    /// Row of the result after
+    Added(usize),
+    /// Row of the result before
+    Removed(usize),
+    Changed {
+        before: usize,
+        after: usize,
+        /// Positions among the compared columns
+        columns: Vec<usize>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct ResultDiff {
+    pub schema: Vec<SchemaChange>,
+    /// Names of the columns present in both results, key columns first
+    pub columns: Vec<String>,
+    /// Number of key columns at the start of `columns`, rows are compared as a whole without them
+    pub key: usize,
+    /// Both results with only the compared columns, rows of the diff point into them
+    pub before: RecordBatch,
+    pub after: RecordBatch,
+    /// Changed and added rows in the order of the result after, then the removed ones
+    pub rows: Vec<RowDiff>,
+    pub unchanged: usize,
+}
+
+/// Key columns in a list like `id, day`
+pub fn parse_key(text: &str) -> Vec<String> {
+    text.split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Rows of the results matched by the key columns. Without a key whole rows are matched,
+/// so a changed row shows up as removed and added. A result without rows has all rows
+/// of the other one added or removed.
+pub fn diff(before: &QueryResult, after: &QueryResult, key: &[String]) -> Result<ResultDiff> {
+    let schema_before = &before.schema;
+    let schema_after = &after.schema;
+
+    let mut schema = Vec::new();
+    let mut common = Vec::new();
+    for field in schema_after.fields() {
+        match schema_before.field_with_name(field.name()) {
+            Ok(old) if old.data_type() != field.data_type() => {
+                schema.push(SchemaChange::TypeChanged {
+                    name: field.name().clone(),
+                    before: old.data_type().clone(),
+                    after: field.data_type().clone(),
+                    comparable: true,
+                });
+                common.push(field.name().clone());
+            }
+            Ok(_) => common.push(field.name().clone()),
+            Err(_) => schema.push(SchemaChange::Added(
+                field.name().clone(),
+                field.data_type().clone(),
+            )),
+        }
+    }
+    for field in schema_before.fields() {
+        if schema_after.field_with_name(field.name()).is_err() {
+            schema.push(SchemaChange::Removed(
+                field.name().clone(),
+                field.data_type().clone(),
+            ));
+        }
+    }
+
+    // key columns come first, as they identify the rows
+    let mut columns = Vec::new();
+    for name in key {
+        let found = common
+            .iter()
+            .find(|c| *c == name)
+            .or_else(|| common.iter().find(|c| c.eq_ignore_ascii_case(name)))
+            .ok_or_else(|| anyhow!("key column '{}' is not in both results", name))?;
+        if !columns.contains(found) {
+            columns.push(found.clone());
+        }
+    }
+    let key = columns.len();
+    let rest = common
+        .into_iter()
+        .filter(|c| !columns.contains(c))
+        .collect::<Vec<_>>();
+    columns.extend(rest);
+    // rows are told apart by the common columns, without them all would look the same
+    if columns.is_empty() {
+        bail!("the results have no columns in common");
+    }
+
+    let after = project(schema_after, &after.batches, &columns)?;
+    let mut before = project(schema_before, &before.batches, &columns)?;
+    // values of a column with a changed type are compared as the new type, so `10` equals `10.0`
+    let mut incomparable = Vec::new();
+    if before.schema() != after.schema() {
+        let options = CastOptions {
+            safe: false,
+            ..CastOptions::default()
+        };
+        let mut columns_before = Vec::new();
+        for (i, (column, field)) in before
+            .columns()
+            .iter()
+            .zip(after.schema().fields())
+            .enumerate()
+        {
+            let converted = match column.data_type() == field.data_type() {
+                true => Some(column.clone()),
+                false => can_cast_types(column.data_type(), field.data_type())
+                    .then(|| cast_with_options(column, field.data_type(), &options).ok())
+                    .flatten(),
+            };
+            match converted {
+                Some(converted) => columns_before.push(converted),
+                // a failed cast would make NULLs of the values and show them as changed
+                None if i < key => bail!(
+                    "key column '{}' changed its type from {} to {}, the values cannot be compared",
+                    columns[i],
+                    column.data_type(),
+                    field.data_type()
+                ),
+                None => {
+                    incomparable.push(i);
+                    columns_before.push(column.clone());
+                }
+            }
+        }
+        let fields = columns_before
+            .iter()
+            .zip(before.schema().fields())
+            .map(|(column, field)| {
+                field
+                    .as_ref()
+                    .clone()
+                    .with_data_type(column.data_type().clone())
+            })
+            .collect::<Vec<_>>();
+        before = RecordBatch::try_new(Arc::new(Schema::new(fields)), columns_before)?;
+    }
+    if incomparable.len() == columns.len() {
+        bail!("none of the columns in both results can be compared");
+    }
+    for change in &mut schema {
+        if let SchemaChange::TypeChanged {
+            name, comparable, ..
+        } = change
+        {
+            *comparable = !incomparable.iter().any(|i| columns[*i] == *name);
+        }
+    }
+    let mut values_before = values(&before)?;
+    let mut values_after = values(&after)?;
+    // columns which are not compared are left out, so they never make rows differ
+    for row in values_before.iter_mut().chain(values_after.iter_mut()) {
+        for column in &incomparable {
+            row[*column] = None;
+        }
+    }
+
+    let mut rows = Vec::new();
+    let mut unchanged = 0;
+    // rows with the same key are paired in order, extra ones are added or removed
+    let key_len = if key == 0 { columns.len() } else { key };
+    let mut remaining: HashMap<&[Option<String>], VecDeque<usize>> = HashMap::new();
+    for (i, row) in values_before.iter().enumerate() {
+        remaining.entry(&row[..key_len]).or_default().push_back(i);
+    }
+    for (i, row) in values_after.iter().enumerate() {
+        let Some(j) = remaining
+            .get_mut(&row[..key_len])
+            .and_then(|rows| rows.pop_front())
+        else {
+            rows.push(RowDiff::Added(i));
+            continue;
+        };
+        let changed = (key_len..columns.len())
+            .filter(|c| values_before[j][*c] != row[*c])
+            .collect::<Vec<_>>();
+        if changed.is_empty() {
+            unchanged += 1;
+        } else {
+            rows.push(RowDiff::Changed {
+                before: j,
+                after: i,
+                columns: changed,
+            });
+        }
+    }
+    let mut removed = remaining.into_values().flatten().collect::<Vec<_>>();
+    removed.sort_unstable();
+    rows.extend(removed.into_iter().map(RowDiff::Removed));
+
+    Ok(ResultDiff {
+        schema,
+        columns,
+        key,
+        before,
+        after,
+        rows,
+        unchanged,
+    })
+}
+
+/// All rows of the result with only the given columns, in their order
+fn project(schema: &SchemaRef, batches: &[RecordBatch], columns: &[String]) -> Result<RecordBatch> {
+    let batch = concat_batches(schema, batches)?;
+    let indices = columns
+        .iter()
+        .map(|name| schema.index_of(name))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(batch.project(&indices)?)
+}
+
+/// Values as text for comparison, NULL is told apart from an empty string
+fn values(batch: &RecordBatch) -> Result<Vec<Vec<Option<String>>>> {
+    let options = FormatOptions::default();
+    let formatters = batch
+        .columns()
+        .iter()
+        .map(|c| ArrayFormatter::try_new(c.as_ref(), &options))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((0..batch.num_rows())
+        .map(|row| {
+            batch
+                .columns()
+                .iter()
+                .zip(&formatters)
+                .map(|(column, formatter)| {
+                    (!column.is_null(row)).then(|| formatter.value(row).to_string())
+                })
+                .collect()
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::{ArrayRef, Float64Array, Int64Array, StringArray};
+
+    fn ints(values: &[i64]) -> ArrayRef {
+        Arc::new(Int64Array::from(values.to_vec()))
+    }
+
+    fn texts(values: &[&str]) -> ArrayRef {
+        Arc::new(StringArray::from(values.to_vec()))
+    }
+
+    fn result(columns: Vec<(&str, ArrayRef)>) -> QueryResult {
+        let batch = RecordBatch::try_from_iter(columns).unwrap();
+        QueryResult {
+            schema: batch.schema(),
+            batches: vec![batch],
+            truncated: false,
+        }
+    }
+
+    /// The result without rows, DataFusion returns no batches for it
+    fn empty(result: &QueryResult) -> QueryResult {
+        QueryResult {
+            batches: Vec::new(),
+            ..result.clone()
+        }
+    }
+
+    fn key(text: &str) -> Vec<String> {
+        parse_key(text)
+    }
+
+    #[test]
+    fn keyed_rows() {
+        let before = result(vec![
+            ("id", ints(&[1, 2, 3])),
+            ("v", texts(&["a", "b", "c"])),
+        ]);
+        let after = result(vec![
+            ("id", ints(&[3, 1, 4])),
+            ("v", texts(&["c", "x", "d"])),
+        ]);
+        let diff = diff(&before, &after, &key("id")).unwrap();
+        assert_eq!(diff.columns, ["id", "v"]);
+        assert_eq!(diff.key, 1);
+        assert_eq!(
+            diff.rows,
+            [
+                RowDiff::Changed {
+                    before: 0,
+                    after: 1,
+                    columns: vec![1],
+                },
+                RowDiff::Added(2),
+                RowDiff::Removed(1),
+            ]
+        );
+        assert_eq!(diff.unchanged, 1);
+        assert!(diff.schema.is_empty());
+    }
+
+    #[test]
+    fn whole_rows() {
+        let before = result(vec![
+            ("id", ints(&[1, 2, 3])),
+            ("v", texts(&["a", "b", "c"])),
+        ]);
+        let after = result(vec![
+            ("id", ints(&[3, 1, 4])),
+            ("v", texts(&["c", "x", "d"])),
+        ]);
+        let diff = diff(&before, &after, &[]).unwrap();
+        assert_eq!(diff.key, 0);
+        assert_eq!(
+            diff.rows,
+            [
+                RowDiff::Added(1),
+                RowDiff::Added(2),
+                RowDiff::Removed(0),
+                RowDiff::Removed(1),
+            ]
+        );
+        assert_eq!(diff.unchanged, 1);
+    }
+
+    #[test]
+    fn duplicate_keys_are_paired_in_order() {
+        let before = result(vec![
+            ("id", ints(&[1, 1, 2])),
+            ("v", texts(&["a", "b", "c"])),
+        ]);
+        let after = result(vec![
+            ("id", ints(&[1, 1, 1])),
+            ("v", texts(&["a", "z", "y"])),
+        ]);
+        let diff = diff(&before, &after, &key("id")).unwrap();
+        assert_eq!(
+            diff.rows,
+            [
+                RowDiff::Changed {
+                    before: 1,
+                    after: 1,
+                    columns: vec![1],
+                },
+                RowDiff::Added(2),
+                RowDiff::Removed(2),
+            ]
+        );
+        assert_eq!(diff.unchanged, 1);
+
+        // duplicate rows without a key
+        let before = result(vec![("v", texts(&["a", "a", "b"]))]);
+        let after = result(vec![("v", texts(&["a", "b", "b"]))]);
+        let diff = super::diff(&before, &after, &[]).unwrap();
+        assert_eq!(diff.rows, [RowDiff::Added(2), RowDiff::Removed(1)]);
+    }
+
+    #[test]
+    fn key_columns() {
+        let before = result(vec![("v", texts(&["a"])), ("Id", ints(&[1]))]);
+        let after = result(vec![
+            ("Id", ints(&[1])),
+            ("v", texts(&["a"])),
+            ("w", ints(&[2])),
+        ]);
+        let diff = diff(&before, &after, &key("v, id, V")).unwrap();
+        assert_eq!(diff.columns, ["v", "Id"]);
+        assert_eq!(diff.key, 2);
+        assert_eq!(diff.unchanged, 1);
+        assert_eq!(
+            diff.schema,
+            [SchemaChange::Added("w".to_string(), DataType::Int64)]
+        );
+        let err = super::diff(&before, &after, &key("w")).unwrap_err();
+        assert_eq!(err.to_string(), "key column 'w' is not in both results");
+    }
+
+    #[test]
+    fn empty_results() {
+        let rows = result(vec![("id", ints(&[1, 2])), ("v", texts(&["a", "b"]))]);
+        let none = empty(&rows);
+        for key in [key("id"), Vec::new()] {
+            let diff = super::diff(&none, &rows, &key).unwrap();
+            assert_eq!(diff.rows, [RowDiff::Added(0), RowDiff::Added(1)]);
+            assert_eq!(diff.unchanged, 0);
+            let diff = super::diff(&rows, &none, &key).unwrap();
+            assert_eq!(diff.rows, [RowDiff::Removed(0), RowDiff::Removed(1)]);
+            let diff = super::diff(&none, &none, &key).unwrap();
+            assert_eq!(diff.rows, []);
+            assert_eq!(diff.unchanged, 0);
+        }
+        // the columns are known without rows as well
+        let other = empty(&result(vec![("id", ints(&[1])), ("w", ints(&[1]))]));
+        let diff = super::diff(&other, &rows, &key("id")).unwrap();
+        assert_eq!(
+            diff.schema,
+            [
+                SchemaChange::Added("v".to_string(), DataType::Utf8),
+                SchemaChange::Removed("w".to_string(), DataType::Int64),
+            ]
+        );
+        assert_eq!(diff.rows.len(), 2);
+    }
+
+    #[test]
+    fn results_without_comparable_columns() {
+        let before = result(vec![("a", ints(&[1, 2]))]);
+        let after = result(vec![("b", ints(&[3, 4]))]);
+        let err = diff(&before, &after, &[]).unwrap_err();
+        assert_eq!(err.to_string(), "the results have no columns in common");
+
+        let before = result(vec![("s", texts(&["one", "two"]))]);
+        let after = result(vec![("s", ints(&[1, 2]))]);
+        let err = diff(&before, &after, &[]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "none of the columns in both results can be compared"
+        );
+    }
+
+    #[test]
+    fn changed_types_are_compared_as_the_new_type() {
+        let before = result(vec![
+            ("id", ints(&[1, 2])),
+            ("n", ints(&[10, 20])),
+            ("old", ints(&[0, 0])),
+        ]);
+        let after = result(vec![
+            ("id", ints(&[1, 2])),
+            ("n", Arc::new(Float64Array::from(vec![10.0, 25.0]))),
+        ]);
+        let diff = diff(&before, &after, &key("id")).unwrap();
+        assert_eq!(
+            diff.schema,
+            [
+                SchemaChange::TypeChanged {
+                    name: "n".to_string(),
+                    before: DataType::Int64,
+                    after: DataType::Float64,
+                    comparable: true,
+                },
+                SchemaChange::Removed("old".to_string(), DataType::Int64),
+            ]
+        );
+        assert_eq!(
+            diff.rows,
+            [RowDiff::Changed {
+                before: 1,
+                after: 1,
+                columns: vec![1],
+            }]
+        );
+        assert_eq!(diff.unchanged, 1);
+    }
+
+    #[test]
+    fn values_which_cannot_be_converted_are_not_compared() {
+        let before = result(vec![("id", ints(&[1, 2])), ("s", texts(&["1", "one"]))]);
+        let after = result(vec![("id", ints(&[1, 2])), ("s", ints(&[1, 2]))]);
+        for key in [key("id"), Vec::new()] {
+            let diff = diff(&before, &after, &key).unwrap();
+            assert_eq!(
+                diff.schema,
+                [SchemaChange::TypeChanged {
+                    name: "s".to_string(),
+                    before: DataType::Utf8,
+                    after: DataType::Int64,
+                    comparable: false,
+                }]
+            );
+            assert_eq!(diff.rows, []);
+            assert_eq!(diff.unchanged, 2);
+            // the values before are shown as they were
+            assert_eq!(diff.before.column(1).data_type(), &DataType::Utf8);
+        }
+        let err = diff(&before, &after, &key("s")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "key column 's' changed its type from Utf8 to Int64, the values cannot be compared"
+        );
+    }
+}
@@ -0,0 +1,80 @@
+use crate::core::chart::ChartSpec;
+use anyhow::{anyhow, Result};
+use std::time::Duration;
+
+/// Cell directives are SQL comments of the form `-- @name value`, so they are ignored by DataFusion.
+/// Returns the value of the first directive with the given name.
+pub fn find<'a>(code: &'a str, name: &str) -> Option<&'a str> {
+    code.lines().find_map(|line| {
+        let rest = line
+            .trim()
+            .strip_prefix("--")?
+            .trim_start()
+            .strip_prefix('@')?;
+        let (directive, value) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        (directive == name).then_some(value.trim())
+    })
+}
+
+/// Name of the cell shown in the cell list, e.g. `-- @name daily totals`
+pub fn name(code: &str) -> Option<&str> {
+    find(code, "name").filter(|name| !name.is_empty())
+}
+
+/// Per-cell timeout, e.g. `-- @timeout 30s`
+pub fn timeout(code: &str) -> Result<Option<Duration>> {
+    find(code, "timeout")
+        .map(|value| {
+            humantime::parse_duration(value)
+                .map_err(|err| anyhow!("Invalid @timeout directive '{}': {}", value, err))
+        })
+        .transpose()
+}
+
+/// Chart of the result, e.g. `-- @chart bar x=region y=total`
+pub fn chart(code: &str) -> Result<Option<ChartSpec>> {
+    find(code, "chart")
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|err| anyhow!("Invalid @chart directive '{}': {}", value, err))
+        })
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::chart::ChartKind;
+
+    #[test]
+    fn finds_the_first_directive() {
+        let code =
+            "-- @name daily totals \n  --@timeout 30s\n-- @name other\nselect 1 -- @chart bar";
+        assert_eq!(find(code, "name"), Some("daily totals"));
+        assert_eq!(find(code, "timeout"), Some("30s"));
+        assert_eq!(find(code, "chart"), None);
+        assert_eq!(find("-- @names x", "name"), None);
+        assert_eq!(find("-- @name", "name"), Some(""));
+        assert_eq!(name("-- @name"), None);
+        assert_eq!(name("select 1"), None);
+    }
+
+    #[test]
+    fn values() {
+        assert_eq!(
+            timeout("-- @timeout 1m 30s").unwrap(),
+            Some(Duration::from_secs(90))
+        );
+        assert_eq!(timeout("select 1").unwrap(), None);
+        assert!(timeout("-- @timeout soon")
+            .unwrap_err()
+            .to_string()
+            .starts_with("Invalid @timeout directive 'soon'"));
+
+        let spec = chart("-- @chart line x=day").unwrap().unwrap();
+        assert_eq!(spec.kind, Some(ChartKind::Line));
+        assert_eq!(spec.x.as_deref(), Some("day"));
+        assert!(chart("-- @chart pie").is_err());
+    }
+}
@@ -0,0 +1,251 @@
+//! Values rendered as text the way `[format]` of the config asks for, on top of Arrow's formatting.
+
+use crate::config::FormatConfig;
+use crate::core::QueryResult;
+use anyhow::Result;
+use datafusion::arrow::array::{Array, ArrayRef, RecordBatch};
+use datafusion::arrow::compute::cast;
+use datafusion::arrow::datatypes::{DataType, Schema};
+use datafusion::arrow::error::ArrowError;
+use datafusion::arrow::util::display::ArrayFormatter;
+use std::sync::Arc;
+
+/// Formats values of an array, like `ArrayFormatter` with the float precision
+/// and the thousands separator applied to numbers
+pub struct ValueFormatter<'a> {
+    array: &'a dyn Array,
+    formatter: ArrayFormatter<'a>,
+    /// Only set for float columns
+    precision: Option<usize>,
+    /// Only set for numeric columns
+    separator: Option<char>,
+}
+
+impl<'a> ValueFormatter<'a> {
+    pub fn try_new(array: &'a dyn Array, format: &'a FormatConfig) -> Result<Self, ArrowError> {
+        let data_type = array.data_type();
+        let is_float = matches!(
+            data_type,
+            DataType::Float16 | DataType::Float32 | DataType::Float64
+        );
+        Ok(Self {
+            array,
+            formatter: ArrayFormatter::try_new(array, &format.options())?,
+            precision: format.float_precision.filter(|_| is_float),
+            separator: format
+                .thousands_separator
+                .filter(|_| data_type.is_numeric()),
+        })
+    }
+
+    pub fn value(&self, row: usize) -> String {
+        let text = self.formatter.value(row).to_string();
+        if self.array.is_null(row) {
+            return text;
+        }
+        let text = match self.precision {
+            Some(precision) => match text.parse::<f64>() {
+                Ok(value) if value.is_finite() => format!("{:.*}", precision, value),
+                _ => text,
+            },
+            None => text,
+        };
+        match self.separator {
+            Some(separator) => group_thousands(&text, separator),
+            None => text,
+        }
+    }
+}
+
+/// `1234567.5` becomes `1,234,567.5`, text which does not start with digits is kept
+fn group_thousands(text: &str, separator: char) -> String {
+    let (sign, rest) = match text.strip_prefix('-') {
+        Some(rest) => ("-", rest),
+        None => ("", text),
+    };
+    let digits = rest
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(rest.len());
+    let (integer, fraction) = rest.split_at(digits);
+    if integer.len() <= 3 {
+        return text.to_string();
+    }
+    let mut grouped = String::new();
+    for (i, c) in integer.chars().enumerate() {
+        if i > 0 && (integer.len() - i) % 3 == 0 {
+            grouped.push(separator);
+        }
+        grouped.push(c);
+    }
+    format!("{}{}{}", sign, grouped, fraction)
+}
+
+/// Timestamps with a timezone are moved to the display timezone. Only the timezone of the
+/// column changes, values are the same points in time, timestamps without a timezone are kept.
+pub fn localize(result: QueryResult, format: &FormatConfig) -> Result<QueryResult> {
+    let Some(timezone) = format.timezone.as_deref() else {
+        return Ok(result);
+    };
+    if !result
+        .schema
+        .fields()
+        .iter()
+        .any(|f| has_timezone(f.data_type()))
+    {
+        return Ok(result);
+    }
+    let fields = result
+        .schema
+        .fields()
+        .iter()
+        .map(|field| match field.data_type() {
+            DataType::Timestamp(unit, Some(_)) => field
+                .as_ref()
+                .clone()
+                .with_data_type(DataType::Timestamp(*unit, Some(timezone.into()))),
+            _ => field.as_ref().clone(),
+        })
+        .collect::<Vec<_>>();
+    let schema = Arc::new(Schema::new_with_metadata(
+        fields,
+        result.schema.metadata().clone(),
+    ));
+    let batches = result
+        .batches
+        .iter()
+        .map(|batch| {
+            let columns = batch
+                .columns()
+                .iter()
+                .map(|column| localize_array(column, timezone))
+                .collect::<Result<Vec<_>>>()?;
+            Ok(RecordBatch::try_new(schema.clone(), columns)?)
+        })
+        .collect::<Result<_>>()?;
+    Ok(QueryResult {
+        schema,
+        batches,
+        ..result
+    })
+}
+
+/// The array in the display timezone, when it holds timestamps with a timezone
+pub fn localize_array(array: &ArrayRef, timezone: &str) -> Result<ArrayRef> {
+    match array.data_type() {
+        DataType::Timestamp(unit, Some(_)) => Ok(cast(
+            array,
+            &DataType::Timestamp(*unit, Some(timezone.into())),
+        )?),
+        _ => Ok(array.clone()),
+    }
+}
+
+fn has_timezone(data_type: &DataType) -> bool {
+    matches!(data_type, DataType::Timestamp(_, Some(_)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::{Float64Array, Int64Array, StringArray, TimestampSecondArray};
+    use datafusion::arrow::datatypes::Field;
+
+    fn values(array: &dyn Array, format: &FormatConfig) -> Vec<String> {
+        let formatter = ValueFormatter::try_new(array, format).unwrap();
+        (0..array.len()).map(|row| formatter.value(row)).collect()
+    }
+
+    #[test]
+    fn thousands() {
+        assert_eq!(group_thousands("1234567.5", ','), "1,234,567.5");
+        assert_eq!(group_thousands("-1234", ' '), "-1 234");
+        assert_eq!(group_thousands("123456", '\''), "123'456");
+        assert_eq!(group_thousands("999", ','), "999");
+        assert_eq!(group_thousands("-999.99", ','), "-999.99");
+        assert_eq!(group_thousands("1000000e10", ','), "1,000,000e10");
+        assert_eq!(group_thousands("NaN", ','), "NaN");
+        assert_eq!(group_thousands("", ','), "");
+    }
+
+    #[test]
+    fn float_precision() {
+        let format = FormatConfig {
+            float_precision: Some(2),
+            thousands_separator: Some(','),
+            ..FormatConfig::default()
+        };
+        let floats = Float64Array::from(vec![
+            Some(1234.5678),
+            Some(-0.001),
+            None,
+            Some(f64::INFINITY),
+            Some(f64::NAN),
+        ]);
+        assert_eq!(
+            values(&floats, &format),
+            vec!["1,234.57", "-0.00", "NULL", "inf", "NaN"]
+        );
+        // integers get the separator, but no decimals
+        let integers = Int64Array::from(vec![1234567, -12]);
+        assert_eq!(values(&integers, &format), vec!["1,234,567", "-12"]);
+        // text which looks like a number is kept
+        let text = StringArray::from(vec!["1234.5678"]);
+        assert_eq!(values(&text, &format), vec!["1234.5678"]);
+    }
+
+    #[test]
+    fn unformatted() {
+        let floats = Float64Array::from(vec![1234.5678]);
+        assert_eq!(values(&floats, &FormatConfig::default()), vec!["1234.5678"]);
+        let nulls = Float64Array::from(vec![None]);
+        let format = FormatConfig {
+            null: "-".into(),
+            float_precision: Some(1),
+            ..FormatConfig::default()
+        };
+        assert_eq!(values(&nulls, &format), vec!["-"]);
+    }
+
+    #[test]
+    fn localize_timestamps() {
+        let format = FormatConfig {
+            timezone: Some("Europe/Kyiv".into()),
+            ..FormatConfig::default()
+        };
+        let with_tz = TimestampSecondArray::from(vec![0]).with_timezone("UTC");
+        let without_tz = TimestampSecondArray::from(vec![0]);
+        let schema = Schema::new(vec![
+            Field::new("a", with_tz.data_type().clone(), true),
+            Field::new("b", without_tz.data_type().clone(), true),
+        ]);
+        let batch = RecordBatch::try_new(
+            Arc::new(schema),
+            vec![Arc::new(with_tz), Arc::new(without_tz)],
+        )
+        .unwrap();
+        let result = QueryResult {
+            schema: batch.schema(),
+            batches: vec![batch],
+            truncated: false,
+        };
+        let result = localize(result, &format).unwrap();
+        let batches = result.batches;
+        let schema = batches[0].schema();
+        assert_eq!(schema, result.schema);
+        assert_eq!(
+            schema.field(0).data_type(),
+            &DataType::Timestamp(
+                datafusion::arrow::datatypes::TimeUnit::Second,
+                Some("Europe/Kyiv".into())
+            )
+        );
+        assert_eq!(
+            values(batches[0].column(0).as_ref(), &format),
+            vec!["1970-01-01T03:00:00+03:00"]
+        );
+        assert_eq!(
+            values(batches[0].column(1).as_ref(), &format),
+            vec!["1970-01-01T00:00:00"]
+        );
+    }
+}
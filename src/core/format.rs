@@ -0,0 +1,750 @@
+use crate::config::{FormatterConfig, KeywordCase};
+use anyhow::{anyhow, bail, Result};
+use datafusion::sql::sqlparser::dialect::GenericDialect;
+use datafusion::sql::sqlparser::tokenizer::{Token, Tokenizer, Whitespace};
+
+/// Words written in the configured keyword case. Function names and data types are kept as written.
+const KEYWORDS: &[&str] = &[
+    "ALL",
+    "ALTER",
+    "ANALYZE",
+    "AND",
+    "ANTI",
+    "ANY",
+    "AS",
+    "ASC",
+    "BETWEEN",
+    "BY",
+    "CASE",
+    "CAST",
+    "COPY",
+    "CREATE",
+    "CROSS",
+    "CURRENT",
+    "DEFAULT",
+    "DELETE",
+    "DESC",
+    "DESCRIBE",
+    "DISTINCT",
+    "DROP",
+    "ELSE",
+    "END",
+    "EXCEPT",
+    "EXISTS",
+    "EXPLAIN",
+    "EXTERNAL",
+    "EXTRACT",
+    "FALSE",
+    "FILTER",
+    "FIRST",
+    "FOLLOWING",
+    "FROM",
+    "FULL",
+    "GROUP",
+    "HAVING",
+    "IF",
+    "ILIKE",
+    "IN",
+    "INNER",
+    "INSERT",
+    "INTERSECT",
+    "INTERVAL",
+    "INTO",
+    "IS",
+    "JOIN",
+    "LAST",
+    "LATERAL",
+    "LEFT",
+    "LIKE",
+    "LIMIT",
+    "LOCATION",
+    "NATURAL",
+    "NOT",
+    "NULL",
+    "NULLS",
+    "OFFSET",
+    "ON",
+    "OPTIONS",
+    "OR",
+    "ORDER",
+    "OUTER",
+    "OVER",
+    "OVERWRITE",
+    "PARTITION",
+    "PARTITIONED",
+    "PRECEDING",
+    "QUALIFY",
+    "RANGE",
+    "REPLACE",
+    "RIGHT",
+    "ROW",
+    "ROWS",
+    "SELECT",
+    "SEMI",
+    "SET",
+    "SHOW",
+    "SIMILAR",
+    "STORED",
+    "TABLE",
+    "TABLES",
+    "THEN",
+    "TO",
+    "TRUE",
+    "TRY_CAST",
+    "UNBOUNDED",
+    "UNION",
+    "UNNEST",
+    "UPDATE",
+    "USING",
+    "VALUES",
+    "VERBOSE",
+    "VIEW",
+    "WHEN",
+    "WHERE",
+    "WINDOW",
+    "WITH",
+    "WITHIN",
+];
+
+/// Keywords used like functions, without a space before `(`
+const FUNCTION_KEYWORDS: &[&str] = &[
+    "CAST", "TRY_CAST", "EXTRACT", "LEFT", "RIGHT", "REPLACE", "UNNEST",
+];
+
+/// Keywords after which an identifier followed by `(` is a table, not a function call
+const TABLE_KEYWORDS: &[&str] = &["TABLE", "INTO", "VIEW", "EXISTS"];
+
+const JOIN_PREFIXES: &[&str] = &["INNER", "LEFT", "RIGHT", "FULL", "CROSS", "NATURAL"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Keyword,
+    Identifier,
+    Literal,
+    Operator,
+    /// `-` or `+` of a negative or positive number
+    Sign,
+    Comma,
+    Dot,
+    DoubleColon,
+    Open,
+    Close,
+    OpenBracket,
+    CloseBracket,
+    Semicolon,
+    Comment,
+}
+
+struct Item<'a> {
+    token: Token,
+    /// Source text of the token, comments without the trailing line break
+    text: &'a str,
+    /// Line breaks in the source between the previous item and this one
+    newlines: usize,
+}
+
+impl Item<'_> {
+    fn is_comment(&self) -> bool {
+        matches!(
+            self.token,
+            Token::Whitespace(Whitespace::SingleLineComment { .. })
+                | Token::Whitespace(Whitespace::MultiLineComment(_))
+        )
+    }
+
+    /// Uppercase word if it is a keyword
+    fn keyword(&self) -> Option<String> {
+        match &self.token {
+            Token::Word(word) if word.quote_style.is_none() => {
+                let upper = word.value.to_uppercase();
+                KEYWORDS.contains(&upper.as_str()).then_some(upper)
+            }
+            _ => None,
+        }
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        self.keyword().is_some_and(|k| k == keyword)
+    }
+
+    fn kind(&self) -> Kind {
+        match &self.token {
+            Token::Word(_) if self.keyword().is_some() => Kind::Keyword,
+            Token::Word(_) => Kind::Identifier,
+            Token::Comma => Kind::Comma,
+            Token::Period => Kind::Dot,
+            Token::DoubleColon => Kind::DoubleColon,
+            Token::LParen => Kind::Open,
+            Token::RParen => Kind::Close,
+            Token::LBracket => Kind::OpenBracket,
+            Token::RBracket => Kind::CloseBracket,
+            Token::SemiColon => Kind::Semicolon,
+            _ if self.is_comment() => Kind::Comment,
+            _ if self.text.chars().all(|c| "+-*/%=<>!|&^~#@?:".contains(c)) => Kind::Operator,
+            _ => Kind::Literal,
+        }
+    }
+}
+
+/// Splits the SQL into tokens keeping their source text, whitespace is dropped
+fn tokenize(sql: &str) -> Result<Vec<Item<'_>>> {
+    let dialect = GenericDialect {};
+    let tokens = Tokenizer::new(&dialect, sql)
+        .with_unescape(false)
+        .tokenize_with_location()
+        .map_err(|err| anyhow!("Cannot format SQL: {}", err))?;
+
+    // tokens are contiguous, so the text of a token ends where its span ends
+    let mut items = Vec::new();
+    let mut chars = sql.char_indices().peekable();
+    let (mut line, mut col) = (1, 1);
+    let mut newlines = 0;
+    for token in tokens {
+        let start = chars.peek().map_or(sql.len(), |(i, _)| *i);
+        while (line, col) < (token.span.end.line, token.span.end.column) {
+            match chars.next() {
+                Some((_, '\n')) => (line, col) = (line + 1, 1),
+                Some(_) => col += 1,
+                None => break,
+            }
+        }
+        let end = chars.peek().map_or(sql.len(), |(i, _)| *i);
+        let text = &sql[start..end];
+        match token.token {
+            Token::Whitespace(Whitespace::Space | Whitespace::Tab | Whitespace::Newline) => {
+                newlines += text.matches('\n').count();
+            }
+            token => {
+                items.push(Item {
+                    token,
+                    text: text.trim_end(),
+                    newlines,
+                });
+                // single line comments include the line break
+                newlines = text.matches('\n').count();
+            }
+        }
+    }
+    Ok(items)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Clause {
+    None,
+    Select,
+    Values,
+    With,
+    Where,
+    Other,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FrameKind {
+    /// The statement or a subquery
+    Query,
+    Parens,
+    Case,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Frame {
+    kind: FrameKind,
+    /// Indentation of clauses of a query or of `CASE`
+    indent: usize,
+    /// Indentation of the line with the opening parenthesis
+    open_indent: usize,
+    clause: Clause,
+}
+
+impl Frame {
+    fn query(indent: usize, open_indent: usize) -> Self {
+        Self {
+            kind: FrameKind::Query,
+            indent,
+            open_indent,
+            clause: Clause::None,
+        }
+    }
+}
+
+struct Printer<'a> {
+    config: &'a FormatterConfig,
+    out: String,
+    line_indent: usize,
+    line_empty: bool,
+    /// The next token starts a new line with this indentation
+    pending: Option<usize>,
+    blank_line: bool,
+}
+
+impl Printer<'_> {
+    fn write(&mut self, text: &str, space: bool) {
+        if let Some(indent) = self.pending.take() {
+            if !self.out.is_empty() {
+                self.out.truncate(self.out.trim_end_matches(' ').len());
+                self.out.push('\n');
+                if self.blank_line {
+                    self.out.push('\n');
+                }
+            }
+            self.out.push_str(&" ".repeat(indent * self.config.indent));
+            self.line_indent = indent;
+            self.line_empty = true;
+        } else if space && !self.line_empty {
+            self.out.push(' ');
+        }
+        self.blank_line = false;
+        self.out.push_str(text);
+        self.line_empty = false;
+    }
+
+    fn break_line(&mut self, indent: usize) {
+        self.pending = Some(indent);
+    }
+}
+
+/// Whether the word at `i` starts a clause of a query, which goes on its own line
+fn starts_clause(items: &[Item], i: usize) -> bool {
+    let Some(keyword) = items[i].keyword() else {
+        return false;
+    };
+    let prev = significant(items, i, false);
+    let next = significant(items, i, true);
+    match keyword.as_str() {
+        "SELECT" | "WHERE" | "HAVING" | "LIMIT" | "OFFSET" | "WINDOW" | "QUALIFY" | "VALUES"
+        | "UNION" | "EXCEPT" | "INTERSECT" => true,
+        // not `IS DISTINCT FROM` or `DELETE FROM`
+        "FROM" => prev.is_none_or(|p| !p.is_keyword("DISTINCT") && !p.is_keyword("DELETE")),
+        "GROUP" | "ORDER" => next.is_some_and(|n| n.is_keyword("BY")),
+        "JOIN" => prev.is_none_or(|p| {
+            !JOIN_PREFIXES.contains(&p.keyword().unwrap_or_default().as_str())
+                && !p.is_keyword("OUTER")
+                && !p.is_keyword("SEMI")
+                && !p.is_keyword("ANTI")
+        }),
+        k if JOIN_PREFIXES.contains(&k) => next.is_some_and(|n| n.kind() == Kind::Keyword),
+        _ => false,
+    }
+}
+
+/// The closest item before or after `i` which is not a comment
+fn significant<'a, 'b>(items: &'b [Item<'a>], i: usize, forward: bool) -> Option<&'b Item<'a>> {
+    if forward {
+        items[i + 1..].iter().find(|item| !item.is_comment())
+    } else {
+        items[..i].iter().rev().find(|item| !item.is_comment())
+    }
+}
+
+fn needs_space(prev: Option<Kind>, kind: Kind) -> bool {
+    !matches!(
+        kind,
+        Kind::Comma
+            | Kind::Close
+            | Kind::Semicolon
+            | Kind::Dot
+            | Kind::DoubleColon
+            | Kind::OpenBracket
+            | Kind::CloseBracket
+    ) && !matches!(
+        prev,
+        None | Some(Kind::Open | Kind::Dot | Kind::DoubleColon | Kind::OpenBracket | Kind::Sign)
+    )
+}
+
+fn keyword_text(keyword: &str, text: &str, case: KeywordCase) -> String {
+    match case {
+        KeywordCase::Upper => keyword.to_string(),
+        KeywordCase::Lower => keyword.to_lowercase(),
+        KeywordCase::Preserve => text.to_string(),
+    }
+}
+
+/// Pretty-prints SQL: keyword casing, one clause per line, indented subqueries and select lists.
+/// Comments are kept, an error is returned if the SQL cannot be tokenized.
+pub fn format_sql(sql: &str, config: &FormatterConfig) -> Result<String> {
+    let items = tokenize(sql)?;
+    let mut printer = Printer {
+        config,
+        out: String::new(),
+        line_indent: 0,
+        line_empty: true,
+        pending: None,
+        blank_line: false,
+    };
+    let mut frames = vec![Frame::query(0, 0)];
+    let mut prev: Option<Kind> = None;
+    let mut between = false;
+
+    for (i, item) in items.iter().enumerate() {
+        let frame = *frames.last().expect("the statement frame is never removed");
+        let in_query = frame.kind == FrameKind::Query;
+        let mut kind = item.kind();
+        match kind {
+            Kind::Comment => {
+                let own_line = item.newlines > 0 || printer.out.is_empty();
+                if own_line {
+                    printer.blank_line = item.newlines > 1;
+                    if printer.pending.is_none() && !printer.out.is_empty() {
+                        let next_clause = (i + 1..items.len())
+                            .find(|j| !items[*j].is_comment())
+                            .is_some_and(|j| in_query && starts_clause(&items, j));
+                        let indent = if next_clause {
+                            frame.indent
+                        } else {
+                            printer.line_indent
+                        };
+                        printer.break_line(indent);
+                    }
+                }
+                printer.write(item.text, true);
+                let single_line = matches!(
+                    item.token,
+                    Token::Whitespace(Whitespace::SingleLineComment { .. })
+                );
+                // a block comment leading a token on its line stays in front of it, so the
+                // line break written before it does not split them on the next format
+                let ends_line = items.get(i + 1).is_none_or(|next| next.newlines > 0);
+                if (single_line || (own_line && ends_line)) && printer.pending.is_none() {
+                    printer.break_line(printer.line_indent);
+                }
+                // a blank line after leading comments like directives is kept
+                if let Some(next) = items.get(i + 1) {
+                    if own_line && next.newlines > 1 && !next.is_comment() {
+                        printer.blank_line = true;
+                    }
+                }
+                continue;
+            }
+            Kind::Semicolon => {
+                printer.write(item.text, false);
+                frames = vec![Frame::query(0, 0)];
+                if i + 1 < items.len() {
+                    printer.break_line(0);
+                    printer.blank_line = true;
+                }
+                prev = Some(kind);
+                continue;
+            }
+            _ => {}
+        }
+
+        let keyword = item.keyword();
+        let text = match &keyword {
+            Some(keyword) => keyword_text(keyword, item.text, config.keyword_case),
+            None => item.text.to_string(),
+        };
+        let mut space = needs_space(prev, kind);
+
+        if in_query && starts_clause(&items, i) {
+            let keyword = keyword.as_deref().unwrap_or_default();
+            // the first SELECT of a subquery follows the line break after `(`
+            if printer.pending.is_none() && !(keyword == "SELECT" && prev == Some(Kind::Open)) {
+                printer.blank_line = false;
+            }
+            if !(keyword == "SELECT" && prev == Some(Kind::Open)) {
+                printer.break_line(frame.indent);
+            }
+            printer.write(&text, space);
+            let frame = frames.last_mut().expect("checked above");
+            frame.clause = match keyword {
+                "SELECT" => Clause::Select,
+                "VALUES" => Clause::Values,
+                "WHERE" | "HAVING" => Clause::Where,
+                _ => Clause::Other,
+            };
+            between = false;
+            if matches!(frame.clause, Clause::Select | Clause::Values) {
+                printer.break_line(frame.indent + 1);
+            }
+            prev = Some(kind);
+            continue;
+        }
+
+        match keyword.as_deref() {
+            Some("WITH") if in_query && frame.clause == Clause::None => {
+                frames.last_mut().expect("checked above").clause = Clause::With;
+            }
+            // `SELECT DISTINCT` stays on one line, the list goes below
+            Some("DISTINCT" | "ALL")
+                if in_query
+                    && frame.clause == Clause::Select
+                    && significant(&items, i, false).is_some_and(|p| p.is_keyword("SELECT")) =>
+            {
+                let indent = printer.pending.take();
+                printer.write(&text, space);
+                printer.pending = indent;
+                prev = Some(kind);
+                continue;
+            }
+            Some("BETWEEN") => between = true,
+            Some("AND") if between => between = false,
+            Some("AND" | "OR") if in_query && frame.clause == Clause::Where => {
+                printer.break_line(frame.indent + 1);
+            }
+            Some("CASE") => {
+                printer.write(&text, space);
+                frames.push(Frame {
+                    kind: FrameKind::Case,
+                    indent: printer.line_indent,
+                    open_indent: printer.line_indent,
+                    clause: Clause::None,
+                });
+                prev = Some(kind);
+                continue;
+            }
+            Some("WHEN" | "ELSE") if frame.kind == FrameKind::Case => {
+                printer.break_line(frame.indent + 1);
+            }
+            Some("END") if frame.kind == FrameKind::Case => {
+                printer.break_line(frame.indent);
+                frames.pop();
+            }
+            _ => {}
+        }
+
+        match kind {
+            Kind::Open => {
+                // `count(`, `CAST(`, but `IN (`, `AS (` and `INSERT INTO t (`
+                let function = match (significant(&items, i, false), prev) {
+                    (Some(p), Some(Kind::Identifier)) => {
+                        let before = (0..i.saturating_sub(1))
+                            .rev()
+                            .find(|j| !items[*j].is_comment() && !std::ptr::eq(&items[*j], p));
+                        !before.is_some_and(|j| {
+                            TABLE_KEYWORDS
+                                .contains(&items[j].keyword().unwrap_or_default().as_str())
+                        })
+                    }
+                    (Some(p), Some(Kind::Keyword)) => {
+                        FUNCTION_KEYWORDS.contains(&p.keyword().unwrap_or_default().as_str())
+                    }
+                    _ => false,
+                };
+                if function {
+                    space = false;
+                }
+                printer.write(&text, space);
+                let subquery = significant(&items, i, true)
+                    .is_some_and(|n| n.is_keyword("SELECT") || n.is_keyword("WITH"));
+                let open_indent = printer.line_indent;
+                if subquery {
+                    frames.push(Frame::query(open_indent + 1, open_indent));
+                    printer.break_line(open_indent + 1);
+                } else {
+                    frames.push(Frame {
+                        kind: FrameKind::Parens,
+                        indent: open_indent,
+                        open_indent,
+                        clause: Clause::None,
+                    });
+                }
+            }
+            Kind::Close => {
+                // unclosed CASE frames are dropped with the parentheses
+                while frames.len() > 1 {
+                    let frame = frames.pop().expect("checked by the loop");
+                    if frame.kind == FrameKind::Case {
+                        continue;
+                    }
+                    if frame.kind == FrameKind::Query {
+                        printer.break_line(frame.open_indent);
+                    }
+                    break;
+                }
+                printer.write(&text, space);
+            }
+            Kind::Comma => {
+                printer.write(&text, space);
+                match frame.clause {
+                    Clause::Select | Clause::Values if in_query => {
+                        printer.break_line(frame.indent + 1)
+                    }
+                    Clause::With if in_query => printer.break_line(frame.indent),
+                    _ => {}
+                }
+            }
+            Kind::Operator
+                if (item.text == "-" || item.text == "+")
+                    && matches!(
+                        prev,
+                        None | Some(
+                            Kind::Open
+                                | Kind::Comma
+                                | Kind::Operator
+                                | Kind::Keyword
+                                | Kind::Sign
+                                | Kind::OpenBracket
+                        )
+                    ) =>
+            {
+                kind = Kind::Sign;
+                printer.write(&text, space);
+            }
+            _ => printer.write(&text, space),
+        }
+        prev = Some(kind);
+    }
+
+    let formatted = printer.out.trim_end().to_string();
+    check_tokens(sql, &formatted)?;
+    Ok(formatted)
+}
+
+/// Makes sure formatting changed nothing but whitespace and keyword case
+fn check_tokens(sql: &str, formatted: &str) -> Result<()> {
+    let normalize = |sql| -> Result<Vec<String>> {
+        Ok(tokenize(sql)?
+            .iter()
+            .map(|item| match item.keyword() {
+                Some(keyword) => keyword,
+                None => item.text.to_string(),
+            })
+            .collect())
+    };
+    if normalize(sql)? != normalize(formatted)? {
+        bail!("Cannot format SQL: the formatted query would differ from the original");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::directive;
+    use indoc::indoc;
+
+    fn format(sql: &str) -> String {
+        format_sql(sql, &FormatterConfig::default()).unwrap()
+    }
+
+    const CORPUS: &[&str] = &[
+        "select a, /* inline */ b -- trailing\nfrom t",
+        "select a, b from t where x = 1 and y between 1 and 2 or z is not null",
+        "-- directive\n\nselect 1",
+        "-- first\n-- second\nselect a -- a\n, b /* b */ from t -- t\n;",
+        "/* leading */ select * from t",
+        "/* block\n   on lines */\nselect a,\n/* own line */\nb from t",
+        "select a /* after a */, b from t /* end */",
+        "select a,\n  -- about b\n  b\nfrom t\n-- before where\nwhere a > 1 /* why */ and b < 2",
+        "with x as (select 1 as a /* one */), y as (select 2) select * from x join y on true",
+        "select case /* c */ when a then 1 -- one\n else 2 end from t",
+        "select count(*) from (select /* inner */ a from t -- t\n) as s group by 1 order by 1 desc",
+        "select a from t; -- done\nselect /* next */ b from u",
+        "insert into t (a, b) values (1, /* two */ 2), (3, 4)",
+        "select -1, +2, a - b, cast(x as int) /* cast */ from t limit 10 offset 5",
+    ];
+
+    #[test]
+    fn idempotent() {
+        for sql in CORPUS {
+            let once = format(sql);
+            assert_eq!(format(&once), once, "formatting twice changed\n{}", once);
+        }
+    }
+
+    #[test]
+    fn inline_comment_stays_with_its_token() {
+        assert_eq!(
+            format("select a, /* inline */ b -- trailing\nfrom t"),
+            indoc! {"
+                SELECT
+                    a,
+                    /* inline */ b -- trailing
+                FROM t"}
+        );
+    }
+
+    #[test]
+    fn clauses_and_subqueries() {
+        assert_eq!(
+            format(
+                "select distinct a, count(*) from (select a from t where x in (1, 2)) s group by a"
+            ),
+            indoc! {"
+                SELECT DISTINCT
+                    a,
+                    count(*)
+                FROM (
+                    SELECT
+                        a
+                    FROM t
+                    WHERE x IN (1, 2)
+                ) s
+                GROUP BY a"}
+        );
+    }
+
+    #[test]
+    fn conditions_and_case() {
+        assert_eq!(
+            format("select case when a > 0 then 'p' else 'n' end from t where a between 1 and 2 and b or c"),
+            indoc! {"
+                SELECT
+                    CASE
+                        WHEN a > 0 THEN 'p'
+                        ELSE 'n'
+                    END
+                FROM t
+                WHERE a BETWEEN 1 AND 2
+                    AND b
+                    OR c"}
+        );
+    }
+
+    #[test]
+    fn keyword_case() {
+        let config = |keyword_case| FormatterConfig {
+            keyword_case,
+            indent: 2,
+        };
+        assert_eq!(
+            format_sql("SELECT a FROM t", &config(KeywordCase::Lower)).unwrap(),
+            "select\n  a\nfrom t"
+        );
+        assert_eq!(
+            format_sql("Select a From t", &config(KeywordCase::Preserve)).unwrap(),
+            "Select\n  a\nFrom t"
+        );
+        // quoted identifiers are not keywords
+        assert_eq!(
+            format("select \"select\" from t"),
+            "SELECT\n    \"select\"\nFROM t"
+        );
+    }
+
+    #[test]
+    fn statements_and_directives() {
+        let sql = "-- @name daily totals\n-- @timeout 5s\n-- @chart bar x=day y=total\n\nselect day, sum(x) as total from t group by day; select 2";
+        let formatted = format(sql);
+        assert_eq!(
+            formatted,
+            indoc! {"
+                -- @name daily totals
+                -- @timeout 5s
+                -- @chart bar x=day y=total
+
+                SELECT
+                    day,
+                    sum(x) AS total
+                FROM t
+                GROUP BY day;
+
+                SELECT
+                    2"}
+        );
+        // the directives are still found after formatting
+        assert_eq!(directive::name(&formatted), Some("daily totals"));
+        assert_eq!(
+            directive::timeout(&formatted).unwrap(),
+            Some(std::time::Duration::from_secs(5))
+        );
+        let chart = directive::chart(&formatted).unwrap().unwrap();
+        assert_eq!(chart.y.as_deref(), Some("total"));
+    }
+
+    #[test]
+    fn invalid_sql() {
+        assert!(format_sql("select 'unterminated", &FormatterConfig::default()).is_err());
+    }
+}
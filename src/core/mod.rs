@@ -1,3 +1,12 @@
+pub mod chart;
+pub mod diff;
+pub mod directive;
+pub mod display;
+pub mod format;
+pub mod notebook;
+pub mod plan;
+pub mod profile;
 pub mod session;
+pub mod table;
 
 pub use session::*;
@@ -0,0 +1,187 @@
+//! Notebook files: SQL of cells separated by `-- next-cell` lines.
+//! Markdown cells follow `-- next-cell markdown` with every line commented out,
+//! so the file stays a valid SQL script.
+
+pub const CELL_SEPARATOR: &str = "-- next-cell";
+
+const MARKDOWN: &str = "markdown";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum CellKind {
+    #[default]
+    Sql,
+    /// Documentation, rendered instead of executed
+    Markdown,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NotebookCell {
+    pub kind: CellKind,
+    pub code: String,
+}
+
+/// Cells of a notebook file
+pub fn parse(content: &str) -> Vec<NotebookCell> {
+    let mut cells = Vec::new();
+    let mut kind = CellKind::Sql;
+    let mut lines: Vec<&str> = Vec::new();
+    for line in content.lines() {
+        let Some(rest) = separator(line) else {
+            lines.push(line);
+            continue;
+        };
+        // a file starting with a separator, e.g. of a markdown cell, has no SQL cell before it
+        let leading = cells.is_empty() && lines.iter().all(|l| l.trim().is_empty());
+        if !leading {
+            cells.push(cell(kind, &lines));
+        }
+        kind = if rest.trim() == MARKDOWN {
+            CellKind::Markdown
+        } else {
+            CellKind::Sql
+        };
+        lines.clear();
+    }
+    cells.push(cell(kind, &lines));
+    cells
+}
+
+/// What follows the separator if the line is one, `-- next-cellar` is not
+fn separator(line: &str) -> Option<&str> {
+    let rest = line.trim().strip_prefix(CELL_SEPARATOR)?;
+    (rest.is_empty() || rest.starts_with(char::is_whitespace)).then_some(rest)
+}
+
+fn cell(kind: CellKind, lines: &[&str]) -> NotebookCell {
+    let code = match kind {
+        CellKind::Sql => lines.join("\n"),
+        CellKind::Markdown => lines
+            .iter()
+            .map(|line| {
+                let line = line.strip_prefix("--").unwrap_or(line);
+                line.strip_prefix(' ').unwrap_or(line)
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+    };
+    NotebookCell {
+        kind,
+        code: code.trim().to_string(),
+    }
+}
+
+/// Notebook file with the given cells
+pub fn serialize(cells: &[NotebookCell]) -> String {
+    let mut content = String::new();
+    for (i, cell) in cells.iter().enumerate() {
+        match cell.kind {
+            CellKind::Sql if i > 0 => content.push_str(&format!("\n\n{}\n\n", CELL_SEPARATOR)),
+            CellKind::Sql => {}
+            CellKind::Markdown => {
+                if i > 0 {
+                    content.push_str("\n\n");
+                }
+                content.push_str(&format!("{} {}\n\n", CELL_SEPARATOR, MARKDOWN));
+            }
+        }
+        match cell.kind {
+            CellKind::Sql => content.push_str(&cell.code),
+            CellKind::Markdown => {
+                let lines = cell
+                    .code
+                    .lines()
+                    .map(|line| format!("-- {}", line).trim_end().to_string())
+                    .collect::<Vec<_>>();
+                content.push_str(&lines.join("\n"));
+            }
+        }
+    }
+    content.push('\n');
+    content
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    fn sql(code: &str) -> NotebookCell {
+        NotebookCell {
+            kind: CellKind::Sql,
+            code: code.to_string(),
+        }
+    }
+
+    fn markdown(code: &str) -> NotebookCell {
+        NotebookCell {
+            kind: CellKind::Markdown,
+            code: code.to_string(),
+        }
+    }
+
+    #[test]
+    fn separators() {
+        let content = indoc! {"
+            select 1;
+            -- next-cellar is a comment
+              -- next-cell
+            select 2;
+            -- next-cell markdown
+            -- # Title
+        "};
+        assert_eq!(
+            parse(content),
+            [
+                sql("select 1;\n-- next-cellar is a comment"),
+                sql("select 2;"),
+                markdown("# Title"),
+            ]
+        );
+        assert_eq!(parse(""), [sql("")]);
+        assert_eq!(parse("-- next-cell markdown\n-- text"), [markdown("text")]);
+    }
+
+    #[test]
+    fn round_trip() {
+        let cells = [
+            markdown("# Trips\n\nDaily totals, see `trips.csv`\n  - indented"),
+            sql("-- @name totals\nselect day, count(*)\nfrom trips\ngroup by day"),
+            sql("select 1"),
+            markdown("-- looks like a comment"),
+            sql("select 2"),
+        ];
+        let content = serialize(&cells);
+        assert_eq!(
+            content,
+            indoc! {"
+                -- next-cell markdown
+
+                -- # Trips
+                --
+                -- Daily totals, see `trips.csv`
+                --   - indented
+
+                -- next-cell
+
+                -- @name totals
+                select day, count(*)
+                from trips
+                group by day
+
+                -- next-cell
+
+                select 1
+
+                -- next-cell markdown
+
+                -- -- looks like a comment
+
+                -- next-cell
+
+                select 2
+            "}
+        );
+        assert_eq!(parse(&content), cells);
+        assert_eq!(serialize(&parse(&content)), content);
+    }
+}
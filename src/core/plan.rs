@@ -0,0 +1,124 @@
+use datafusion::logical_expr::LogicalPlan;
+use datafusion::physical_plan::{displayable, ExecutionPlan};
+use std::sync::Arc;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryPlan {
+    pub logical: PlanNode,
+    pub physical: PlanNode,
+    pub analyzed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlanNode {
+    pub name: String,
+    pub details: String,
+    pub partitioning: Option<String>,
+    pub metrics: Option<PlanMetrics>,
+    pub children: Vec<PlanNode>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PlanMetrics {
+    pub output_rows: Option<usize>,
+    /// CPU time spent by the operator itself, in nanoseconds
+    pub elapsed_compute: Option<usize>,
+    pub spill_count: Option<usize>,
+    pub all: Vec<(String, String)>,
+}
+
+/// Splits "Projection: a, b" into ("Projection", "a, b")
+fn split_line(line: &str) -> (String, String) {
+    match line.split_once(':') {
+        Some((name, details)) => (name.trim().to_string(), details.trim().to_string()),
+        None => (line.trim().to_string(), String::new()),
+    }
+}
+
+impl PlanNode {
+    pub fn from_logical(plan: &LogicalPlan) -> Self {
+        let (name, details) = split_line(&plan.display().to_string());
+        Self {
+            name,
+            details,
+            partitioning: None,
+            metrics: None,
+            children: plan.inputs().into_iter().map(Self::from_logical).collect(),
+        }
+    }
+
+    pub fn from_physical(plan: &Arc<dyn ExecutionPlan>) -> Self {
+        let (name, details) = split_line(&displayable(plan.as_ref()).one_line().to_string());
+        let metrics = plan.metrics().map(|set| {
+            let set = set
+                .aggregate_by_name()
+                .sorted_for_display()
+                .timestamps_removed();
+            PlanMetrics {
+                output_rows: set.output_rows(),
+                elapsed_compute: set.elapsed_compute(),
+                spill_count: set.spill_count(),
+                all: set
+                    .iter()
+                    .map(|m| (m.value().name().to_string(), m.value().to_string()))
+                    .collect(),
+            }
+        });
+        Self {
+            name,
+            details,
+            partitioning: Some(plan.properties().output_partitioning().to_string()),
+            metrics,
+            children: plan
+                .children()
+                .into_iter()
+                .map(Self::from_physical)
+                .collect(),
+        }
+    }
+
+    pub fn elapsed_compute(&self) -> usize {
+        self.metrics
+            .as_ref()
+            .and_then(|m| m.elapsed_compute)
+            .unwrap_or_default()
+    }
+
+    /// Sum of `elapsed_compute` over the whole subtree
+    pub fn total_elapsed_compute(&self) -> usize {
+        self.elapsed_compute()
+            + self
+                .children
+                .iter()
+                .map(|c| c.total_elapsed_compute())
+                .sum::<usize>()
+    }
+}
+
+/// Detects `EXPLAIN [ANALYZE] [VERBOSE] <query>` and returns the inner query together
+/// with the `ANALYZE` flag, so such cells can be shown in the plan view.
+pub fn strip_explain(sql: &str) -> Option<(&str, bool)> {
+    fn keyword<'a>(s: &'a str, kw: &str) -> Option<&'a str> {
+        let s = s.trim_start();
+        let head = s.get(..kw.len())?;
+        let rest = &s[kw.len()..];
+        (head.eq_ignore_ascii_case(kw) && rest.starts_with(char::is_whitespace)).then_some(rest)
+    }
+
+    // leading comments are common in cells, skip them
+    let mut offset = 0;
+    for line in sql.split_inclusive('\n') {
+        let line_trimmed = line.trim();
+        if !line_trimmed.is_empty() && !line_trimmed.starts_with("--") {
+            break;
+        }
+        offset += line.len();
+    }
+    let rest = keyword(&sql[offset..], "EXPLAIN")?;
+    let (rest, analyze) = match keyword(rest, "ANALYZE") {
+        Some(rest) => (rest, true),
+        None => (rest, false),
+    };
+    let rest = keyword(rest, "VERBOSE").unwrap_or(rest);
+    Some((rest.trim(), analyze))
+}
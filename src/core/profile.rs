@@ -0,0 +1,391 @@
+//! Column statistics of a result or a table, computed with SQL over the source query.
+
+use crate::core::QueryResult;
+use anyhow::{bail, Result};
+use datafusion::arrow::array::{Array, AsArray, RecordBatch};
+use datafusion::arrow::compute::{can_cast_types, cast};
+use datafusion::arrow::datatypes::{DataType, Float64Type, Int64Type};
+use datafusion::common::ScalarValue;
+use datafusion::datasource::MemTable;
+use datafusion::prelude::SessionContext;
+use std::sync::Arc;
+
+/// Most frequent values listed per column
+const TOP_VALUES: usize = 5;
+
+const HISTOGRAM_BINS: usize = 10;
+
+/// What to profile: a query, or rows which are already collected
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProfileSource {
+    Query(String),
+    /// A finished result, profiled against its schema, so a result without rows keeps its columns
+    Collected(QueryResult),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Profile {
+    pub rows: usize,
+    pub columns: Vec<ColumnProfile>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnProfile {
+    pub name: String,
+    pub data_type: DataType,
+    pub nulls: usize,
+    /// Estimated number of distinct values, `None` for types which cannot be compared as text
+    pub distinct: Option<usize>,
+    pub min: Option<ScalarValue>,
+    pub max: Option<ScalarValue>,
+    /// Only for numeric columns
+    pub mean: Option<f64>,
+    pub stddev: Option<f64>,
+    /// Most frequent values with their counts, NULLs are not listed
+    pub top: Vec<(ScalarValue, usize)>,
+    pub histogram: Option<Histogram>,
+}
+
+/// Counts of numeric values in bins of equal width between min and max
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    pub min: f64,
+    pub max: f64,
+    pub counts: Vec<usize>,
+}
+
+/// Detects `SUMMARIZE <table>` and `SUMMARIZE <query>`, returning the query to profile
+pub fn strip_summarize(sql: &str) -> Option<String> {
+    // directives and comments often come first in cells
+    let code = sql
+        .lines()
+        .skip_while(|line| line.trim().is_empty() || line.trim().starts_with("--"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let head = code.get(..9)?;
+    let rest = &code[9..];
+    if !head.eq_ignore_ascii_case("SUMMARIZE") || !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let target = rest.trim().trim_end_matches(';').trim();
+    if target.is_empty() {
+        return None;
+    }
+    let is_table = !target.contains(char::is_whitespace) && !target.contains('(');
+    Some(if is_table {
+        format!("SELECT * FROM {}", target)
+    } else {
+        target.to_string()
+    })
+}
+
+fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Min and max are computed for types with a natural order
+fn is_ordered(data_type: &DataType) -> bool {
+    data_type.is_numeric()
+        || data_type.is_temporal()
+        || matches!(
+            data_type,
+            DataType::Boolean | DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View
+        )
+}
+
+/// Profile of the source, the session runs the statistics queries
+pub async fn profile(ctx: &SessionContext, source: ProfileSource) -> Result<Profile> {
+    match source {
+        ProfileSource::Query(query) => profile_query(ctx, &query).await,
+        ProfileSource::Collected(result) => {
+            // a separate context, so the result does not show up in the catalog
+            let ctx = SessionContext::new();
+            let table = MemTable::try_new(result.schema, vec![result.batches])?;
+            ctx.register_table("result", Arc::new(table))?;
+            profile_query(&ctx, "SELECT * FROM result").await
+        }
+    }
+}
+
+async fn collect(ctx: &SessionContext, sql: &str) -> Result<Vec<RecordBatch>> {
+    let batches = ctx.sql(sql).await?.collect().await?;
+    Ok(batches)
+}
+
+async fn profile_query(ctx: &SessionContext, query: &str) -> Result<Profile> {
+    let query = query.trim().trim_end_matches(';');
+    let schema = ctx.sql(query).await?.schema().as_arrow().clone();
+    let source = format!("FROM ({}) AS source", query);
+
+    // one pass for the counts, min and max, mean and stddev of all columns
+    let mut expressions = vec!["count(*)".to_string()];
+    for field in schema.fields() {
+        let column = quote(field.name());
+        let data_type = field.data_type();
+        expressions.push(format!("count({})", column));
+        if can_cast_types(data_type, &DataType::Utf8) {
+            expressions.push(format!("approx_distinct(CAST({} AS VARCHAR))", column));
+        }
+        if is_ordered(data_type) {
+            expressions.push(format!("min({})", column));
+            expressions.push(format!("max({})", column));
+        }
+        if data_type.is_numeric() {
+            expressions.push(format!("avg(CAST({} AS DOUBLE))", column));
+            expressions.push(format!("stddev(CAST({} AS DOUBLE))", column));
+        }
+    }
+    let sql = format!("SELECT {} {}", expressions.join(", "), source);
+    let batches = collect(ctx, &sql).await?;
+    let Some(stats) = batches.first().filter(|b| b.num_rows() > 0) else {
+        bail!("Statistics query returned no rows");
+    };
+
+    // statistics of every column follow in the order of the expressions
+    let mut index = 0;
+    let mut next = || {
+        index += 1;
+        index - 1
+    };
+    let rows = count(stats, next())?;
+    let mut columns = Vec::new();
+    for field in schema.fields() {
+        let data_type = field.data_type();
+        let mut column = ColumnProfile {
+            name: field.name().clone(),
+            data_type: data_type.clone(),
+            nulls: rows - count(stats, next())?,
+            distinct: None,
+            min: None,
+            max: None,
+            mean: None,
+            stddev: None,
+            top: Vec::new(),
+            histogram: None,
+        };
+        if can_cast_types(data_type, &DataType::Utf8) {
+            column.distinct = Some(count(stats, next())?);
+        }
+        if is_ordered(data_type) {
+            column.min = scalar(stats, next())?;
+            column.max = scalar(stats, next())?;
+        }
+        if data_type.is_numeric() {
+            column.mean = float(stats, next())?;
+            column.stddev = float(stats, next())?;
+        }
+        columns.push(column);
+    }
+
+    for column in columns.iter_mut() {
+        // a column which cannot be grouped or binned still has its other statistics
+        if column.distinct.is_some() {
+            column.top = top_values(ctx, &source, column).await.unwrap_or_default();
+        }
+        column.histogram = histogram(ctx, &source, column).await.unwrap_or_default();
+    }
+
+    Ok(Profile { rows, columns })
+}
+
+/// Counts are referred to by position, so they cannot collide with a column of the source
+async fn top_values(
+    ctx: &SessionContext,
+    source: &str,
+    column: &ColumnProfile,
+) -> Result<Vec<(ScalarValue, usize)>> {
+    let name = quote(&column.name);
+    // ties are listed in the order of values when there is one
+    let order = if is_ordered(&column.data_type) {
+        "2 DESC, 1"
+    } else {
+        "2 DESC"
+    };
+    let sql = format!(
+        "SELECT {name}, count(*) {source} WHERE {name} IS NOT NULL \
+         GROUP BY {name} ORDER BY {order} LIMIT {TOP_VALUES}"
+    );
+    let mut top = Vec::new();
+    for batch in collect(ctx, &sql).await? {
+        let counts = cast(batch.column(1), &DataType::Int64)?;
+        let counts = counts.as_primitive::<Int64Type>();
+        for row in 0..batch.num_rows() {
+            let value = ScalarValue::try_from_array(batch.column(0), row)?;
+            top.push((value, counts.value(row) as usize));
+        }
+    }
+    Ok(top)
+}
+
+fn count(batch: &RecordBatch, index: usize) -> Result<usize> {
+    let array = cast(batch.column(index), &DataType::Int64)?;
+    Ok(array.as_primitive::<Int64Type>().value(0) as usize)
+}
+
+fn scalar(batch: &RecordBatch, index: usize) -> Result<Option<ScalarValue>> {
+    let value = ScalarValue::try_from_array(batch.column(index), 0)?;
+    Ok((!value.is_null()).then_some(value))
+}
+
+fn float(batch: &RecordBatch, index: usize) -> Result<Option<f64>> {
+    let array = cast(batch.column(index), &DataType::Float64)?;
+    let array = array.as_primitive::<Float64Type>();
+    Ok((!array.is_null(0)).then(|| array.value(0)))
+}
+
+async fn histogram(
+    ctx: &SessionContext,
+    source: &str,
+    column: &ColumnProfile,
+) -> Result<Option<Histogram>> {
+    if !column.data_type.is_numeric() {
+        return Ok(None);
+    }
+    let bound = |value: &Option<ScalarValue>| -> Result<Option<f64>> {
+        let Some(value) = value else {
+            return Ok(None);
+        };
+        let array = cast(&value.to_array()?, &DataType::Float64)?;
+        Ok(Some(array.as_primitive::<Float64Type>().value(0)))
+    };
+    let (Some(min), Some(max)) = (bound(&column.min)?, bound(&column.max)?) else {
+        return Ok(None);
+    };
+    if !min.is_finite() || !max.is_finite() {
+        return Ok(None);
+    }
+    if min == max {
+        return Ok(None);
+    }
+
+    let name = quote(&column.name);
+    let width = (max - min) / HISTOGRAM_BINS as f64;
+    let sql = format!(
+        "SELECT CAST(floor((CAST({name} AS DOUBLE) - {min:?}) / {width:?}) AS BIGINT), \
+         count(*) {source} WHERE {name} IS NOT NULL GROUP BY 1"
+    );
+    let mut counts = vec![0; HISTOGRAM_BINS];
+    for batch in collect(ctx, &sql).await? {
+        let bins = cast(batch.column(0), &DataType::Int64)?;
+        let bins = bins.as_primitive::<Int64Type>();
+        let bin_counts = cast(batch.column(1), &DataType::Int64)?;
+        let bin_counts = bin_counts.as_primitive::<Int64Type>();
+        for row in 0..batch.num_rows() {
+            // the maximum falls on the upper edge of the last bin
+            let bin = (bins.value(row).max(0) as usize).min(HISTOGRAM_BINS - 1);
+            counts[bin] += bin_counts.value(row) as usize;
+        }
+    }
+    Ok(Some(Histogram { min, max, counts }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::datatypes::{Field, Schema};
+
+    async fn profile_sql(sql: &str) -> Profile {
+        let ctx = SessionContext::new();
+        profile(&ctx, ProfileSource::Query(sql.to_string()))
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn columns_named_like_the_counts() {
+        let profile = profile_sql(
+            "SELECT * FROM (VALUES ('a', 3, 1), ('b', 1, 2), ('a', 3, 3)) AS t(region, count, bin)",
+        )
+        .await;
+        assert_eq!(profile.rows, 3);
+        let region = &profile.columns[0];
+        assert_eq!(
+            region.top,
+            vec![(ScalarValue::from("a"), 2), (ScalarValue::from("b"), 1)]
+        );
+        let count = &profile.columns[1];
+        assert_eq!(count.name, "count");
+        assert_eq!(
+            count.top,
+            vec![
+                (ScalarValue::Int64(Some(3)), 2),
+                (ScalarValue::Int64(Some(1)), 1)
+            ]
+        );
+        assert!(count.histogram.is_some());
+        assert!(profile.columns[2].histogram.is_some());
+    }
+
+    #[tokio::test]
+    async fn ties_in_order_of_values() {
+        let profile =
+            profile_sql("SELECT * FROM (VALUES ('c'), ('a'), ('b'), ('a')) AS t(x)").await;
+        let values = profile.columns[0]
+            .top
+            .iter()
+            .map(|(value, count)| (value.to_string(), *count))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            values,
+            vec![("a".into(), 2), ("b".into(), 1), ("c".into(), 1)]
+        );
+    }
+
+    #[tokio::test]
+    async fn result_without_rows() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, true),
+            Field::new("name", DataType::Utf8, true),
+        ]));
+        // DataFusion returns no batches for a result without rows
+        let result = QueryResult {
+            schema,
+            batches: Vec::new(),
+            truncated: false,
+        };
+        let ctx = SessionContext::new();
+        let profile = profile(&ctx, ProfileSource::Collected(result))
+            .await
+            .unwrap();
+        assert_eq!(profile.rows, 0);
+        let names = profile.columns.iter().map(|c| c.name.as_str());
+        assert_eq!(names.collect::<Vec<_>>(), ["id", "name"]);
+        for column in &profile.columns {
+            assert_eq!(column.nulls, 0);
+            assert_eq!(column.min, None);
+            assert_eq!(column.max, None);
+            assert_eq!(column.mean, None);
+            assert!(column.top.is_empty());
+            assert_eq!(column.histogram, None);
+        }
+    }
+
+    #[tokio::test]
+    async fn nulls_and_histogram() {
+        let profile =
+            profile_sql("SELECT * FROM (VALUES (0.0), (NULL), (5.0), (10.0)) AS t(x)").await;
+        let x = &profile.columns[0];
+        assert_eq!(x.nulls, 1);
+        assert_eq!(x.mean, Some(5.0));
+        let histogram = x.histogram.as_ref().unwrap();
+        assert_eq!(histogram.counts.iter().sum::<usize>(), 3);
+        assert_eq!(histogram.counts[0], 1);
+        assert_eq!(histogram.counts[5], 1);
+        assert_eq!(histogram.counts[HISTOGRAM_BINS - 1], 1);
+    }
+
+    #[test]
+    fn summarize() {
+        assert_eq!(
+            strip_summarize("-- profile\nsummarize trips;").as_deref(),
+            Some("SELECT * FROM trips")
+        );
+        assert_eq!(
+            strip_summarize("SUMMARIZE SELECT 1 AS a").as_deref(),
+            Some("SELECT 1 AS a")
+        );
+        assert_eq!(strip_summarize("SUMMARIZE"), None);
+        assert_eq!(strip_summarize("SUMMARIZED trips"), None);
+        assert_eq!(strip_summarize("SELECT * FROM summarize"), None);
+    }
+}
@@ -1,14 +1,84 @@
-use anyhow::Result;
+use crate::config::{Config, MemoryPoolKind};
+use crate::core::plan::{PlanNode, QueryPlan};
+use crate::core::profile::{self, Profile, ProfileSource};
+use anyhow::{Context, Result};
+use datafusion::arrow::datatypes::SchemaRef;
 use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::execution::disk_manager::DiskManagerConfig;
+use datafusion::execution::memory_pool::{
+    FairSpillPool, GreedyMemoryPool, MemoryPool, TrackConsumersPool,
+};
+use datafusion::execution::runtime_env::RuntimeEnvBuilder;
+use datafusion::physical_plan::collect;
 use datafusion::prelude::{SessionConfig, SessionContext};
+use datafusion::sql::parser::DFParser;
+use futures::StreamExt;
 use std::future::Future;
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+use std::time::Duration;
 
+/// How often progress is reported while a query is waiting for the next batch
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Clone)]
 pub struct LocalDataFusionSession {
     ctx: SessionContext,
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryResult {
+    /// Columns of the result, known even when there are no rows and so no batches
+    pub schema: SchemaRef,
+    pub batches: Vec<RecordBatch>,
+    /// Set when the result was cut at the row limit
+    pub truncated: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Progress {
+    pub rows: usize,
+    pub batches: usize,
+    /// Bytes currently reserved in the session memory pool
+    pub memory_reserved: usize,
+}
+
+/// Session option as listed by `SHOW ALL`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Setting {
+    pub key: String,
+    pub value: Option<String>,
+    pub description: String,
+}
+
 pub trait DataFusionSession {
     fn sql(&self, expr: &str) -> impl Future<Output = Result<Vec<RecordBatch>>> + Send;
+
+    /// Same as `sql`, but streams the result, stopping after `max_rows`,
+    /// and reports progress along the way
+    fn sql_with_progress<F>(
+        &self,
+        expr: &str,
+        max_rows: Option<usize>,
+        on_progress: F,
+    ) -> impl Future<Output = Result<QueryResult>> + Send
+    where
+        F: FnMut(&Progress) + Send;
+
+    /// Executes all statements of the script, discarding results
+    fn run_script(&self, sql: &str) -> impl Future<Output = Result<()>> + Send;
+
+    /// Builds logical and physical plans for the query. With `analyze` the query is executed,
+    /// so physical plan nodes carry runtime metrics.
+    fn explain(&self, expr: &str, analyze: bool) -> impl Future<Output = Result<QueryPlan>> + Send;
+
+    /// Column statistics of a query or of already collected rows
+    fn profile(&self, source: ProfileSource) -> impl Future<Output = Result<Profile>> + Send;
+
+    fn settings(&self) -> Vec<Setting>;
+
+    /// Changes an option of the live session, same as `SET key = value`
+    fn set_setting(&self, key: &str, value: &str) -> Result<()>;
 }
 
 impl Default for LocalDataFusionSession {
@@ -17,12 +87,72 @@ impl Default for LocalDataFusionSession {
     }
 }
 
+fn default_session_config() -> SessionConfig {
+    SessionConfig::new().with_information_schema(true)
+}
+
+fn to_settings(conf: &SessionConfig) -> Vec<Setting> {
+    conf.options()
+        .entries()
+        .into_iter()
+        .map(|entry| Setting {
+            key: entry.key,
+            value: entry.value,
+            description: entry.description.to_string(),
+        })
+        .collect()
+}
+
+/// Settings which differ from the defaults of a fresh session
+pub fn changed_settings(settings: &[Setting]) -> Vec<Setting> {
+    let defaults = to_settings(&default_session_config());
+    settings
+        .iter()
+        .filter(|s| !defaults.contains(s))
+        .cloned()
+        .collect()
+}
+
 impl LocalDataFusionSession {
     pub fn new() -> Self {
-        let conf = SessionConfig::new().with_information_schema(true);
-        let ctx = SessionContext::new_with_config(conf);
+        let ctx = SessionContext::new_with_config(default_session_config());
         Self { ctx }
     }
+
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let mut conf = default_session_config();
+        for (key, value) in &config.session {
+            conf.options_mut()
+                .set(key, value)
+                .with_context(|| format!("Invalid session option '{}'", key))?;
+        }
+
+        let mut runtime = RuntimeEnvBuilder::new();
+        if let Some(limit) = config.memory.limit {
+            // Report top memory consumers when a query runs out of memory
+            let top = NonZeroUsize::new(5).unwrap();
+            let pool: Arc<dyn MemoryPool> = match config.memory.pool {
+                MemoryPoolKind::Fair => {
+                    Arc::new(TrackConsumersPool::new(FairSpillPool::new(limit), top))
+                }
+                MemoryPoolKind::Greedy => {
+                    Arc::new(TrackConsumersPool::new(GreedyMemoryPool::new(limit), top))
+                }
+            };
+            runtime = runtime.with_memory_pool(pool);
+        }
+        if let Some(dir) = &config.memory.spill_dir {
+            runtime =
+                runtime.with_disk_manager(DiskManagerConfig::new_specified(vec![dir.clone()]));
+        }
+
+        let ctx = SessionContext::new_with_config_rt(conf, runtime.build_arc()?);
+        Ok(Self { ctx })
+    }
+
+    pub fn memory_pool(&self) -> Arc<dyn MemoryPool> {
+        self.ctx.runtime_env().memory_pool.clone()
+    }
 }
 
 impl DataFusionSession for LocalDataFusionSession {
@@ -30,4 +160,94 @@ impl DataFusionSession for LocalDataFusionSession {
         let batches = self.ctx.sql(expr).await?.collect().await?;
         Ok(batches)
     }
+
+    async fn sql_with_progress<F>(
+        &self,
+        expr: &str,
+        max_rows: Option<usize>,
+        mut on_progress: F,
+    ) -> Result<QueryResult>
+    where
+        F: FnMut(&Progress) + Send,
+    {
+        let mut stream = self.ctx.sql(expr).await?.execute_stream().await?;
+        let pool = self.ctx.runtime_env().memory_pool.clone();
+        let mut interval = tokio::time::interval(PROGRESS_INTERVAL);
+        let mut progress = Progress::default();
+        let mut result = QueryResult {
+            schema: stream.schema(),
+            batches: Vec::new(),
+            truncated: false,
+        };
+
+        loop {
+            tokio::select! {
+                batch = stream.next() => {
+                    let Some(batch) = batch else {
+                        break;
+                    };
+                    let batch = batch?;
+                    if let Some(max_rows) = max_rows {
+                        let remaining = max_rows - progress.rows;
+                        if batch.num_rows() > remaining {
+                            // dropping the stream cancels the rest of the query
+                            result.truncated = true;
+                            if remaining > 0 {
+                                result.batches.push(batch.slice(0, remaining));
+                            }
+                            break;
+                        }
+                    }
+                    progress.rows += batch.num_rows();
+                    progress.batches += 1;
+                    result.batches.push(batch);
+                }
+                _ = interval.tick() => {
+                    progress.memory_reserved = pool.reserved();
+                    on_progress(&progress);
+                }
+            }
+        }
+
+        Ok(result)
+    }
+
+    async fn run_script(&self, sql: &str) -> Result<()> {
+        for statement in DFParser::parse_sql(sql)? {
+            let plan = self.ctx.state().statement_to_plan(statement).await?;
+            self.ctx.execute_logical_plan(plan).await?.collect().await?;
+        }
+        Ok(())
+    }
+
+    async fn explain(&self, expr: &str, analyze: bool) -> Result<QueryPlan> {
+        let df = self.ctx.sql(expr).await?;
+        let task_ctx = df.task_ctx();
+        let (state, logical_plan) = df.into_parts();
+        let logical_plan = state.optimize(&logical_plan)?;
+        let physical_plan = state.create_physical_plan(&logical_plan).await?;
+        if analyze {
+            collect(physical_plan.clone(), task_ctx.into()).await?;
+        }
+        Ok(QueryPlan {
+            logical: PlanNode::from_logical(&logical_plan),
+            physical: PlanNode::from_physical(&physical_plan),
+            analyzed: analyze,
+        })
+    }
+
+    async fn profile(&self, source: ProfileSource) -> Result<Profile> {
+        profile::profile(&self.ctx, source).await
+    }
+
+    fn settings(&self) -> Vec<Setting> {
+        to_settings(&self.ctx.copied_config())
+    }
+
+    fn set_setting(&self, key: &str, value: &str) -> Result<()> {
+        let state = self.ctx.state_ref();
+        let mut state = state.write();
+        state.config_mut().options_mut().set(key, value)?;
+        Ok(())
+    }
 }
@@ -0,0 +1,395 @@
+//! Sorting and filtering of collected results, done with Arrow kernels without the session.
+
+use anyhow::{anyhow, bail, Result};
+use datafusion::arrow::array::{
+    Array, ArrayRef, BooleanArray, Datum, RecordBatch, Scalar, StringArray,
+};
+use datafusion::arrow::compute::kernels::cmp::{eq, gt, gt_eq, lt, lt_eq, neq};
+use datafusion::arrow::compute::kernels::comparison::ilike;
+use datafusion::arrow::compute::{
+    and_kleene, can_cast_types, cast, concat_batches, filter_record_batch, is_not_null, is_null,
+    lexsort_to_indices, or_kleene, take_record_batch, SortColumn, SortOptions,
+};
+use datafusion::arrow::datatypes::{DataType, Schema};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SortKey {
+    pub column: usize,
+    pub descending: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operator {
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+    /// Text of the value contains the text, ignoring case
+    Contains,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Condition {
+    Compare {
+        column: String,
+        operator: Operator,
+        value: String,
+    },
+    /// Any column contains the text, ignoring case
+    Text(String),
+}
+
+/// Quick filter like `region=eu total>100 name~smith`, all conditions must hold.
+/// A word without an operator matches rows where any column contains it, values with
+/// spaces are quoted, and `col=null` or `col!=null` checks for NULL.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Filter {
+    conditions: Vec<Condition>,
+}
+
+/// Operators in the order they are looked for, so `>=` is not taken for `>`
+const OPERATORS: [(&str, Operator); 7] = [
+    ("!=", Operator::NotEq),
+    (">=", Operator::GtEq),
+    ("<=", Operator::LtEq),
+    ("=", Operator::Eq),
+    ("<", Operator::Lt),
+    (">", Operator::Gt),
+    ("~", Operator::Contains),
+];
+
+/// Words of the filter, double quotes keep spaces and are removed
+fn split_words(text: &str) -> Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut quoted = false;
+    let mut started = false;
+    for c in text.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                started = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if started {
+                    words.push(std::mem::take(&mut word));
+                    started = false;
+                }
+            }
+            c => {
+                word.push(c);
+                started = true;
+            }
+        }
+    }
+    if quoted {
+        bail!("unclosed quote");
+    }
+    if started {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+impl FromStr for Filter {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        let conditions = split_words(text)?
+            .into_iter()
+            .map(|word| {
+                let found = OPERATORS
+                    .iter()
+                    .filter_map(|(symbol, operator)| {
+                        word.find(symbol).map(|i| (i, *symbol, *operator))
+                    })
+                    .min_by_key(|(i, _, _)| *i);
+                match found {
+                    Some((i, symbol, operator)) if i > 0 => Condition::Compare {
+                        column: word[..i].to_string(),
+                        operator,
+                        value: word[i + symbol.len()..].to_string(),
+                    },
+                    _ => Condition::Text(word),
+                }
+            })
+            .collect();
+        Ok(Self { conditions })
+    }
+}
+
+impl Filter {
+    pub fn is_empty(&self) -> bool {
+        self.conditions.is_empty()
+    }
+
+    /// Rows of the batch which pass all conditions
+    fn evaluate(&self, batch: &RecordBatch) -> Result<BooleanArray> {
+        let mut mask = BooleanArray::from(vec![true; batch.num_rows()]);
+        for condition in &self.conditions {
+            let matches = match condition {
+                Condition::Compare {
+                    column,
+                    operator,
+                    value,
+                } => {
+                    let index = column_index(&batch.schema(), column)?;
+                    compare(batch.column(index), *operator, value)?
+                }
+                Condition::Text(text) => {
+                    let mut any = BooleanArray::from(vec![false; batch.num_rows()]);
+                    for column in batch.columns() {
+                        if can_cast_types(column.data_type(), &DataType::Utf8) {
+                            any = or_kleene(&any, &contains(column, text)?)?;
+                        }
+                    }
+                    any
+                }
+            };
+            mask = and_kleene(&mask, &matches)?;
+        }
+        Ok(mask)
+    }
+}
+
+/// Exact name first, then ignoring case
+fn column_index(schema: &Schema, name: &str) -> Result<usize> {
+    schema
+        .index_of(name)
+        .ok()
+        .or_else(|| {
+            schema
+                .fields()
+                .iter()
+                .position(|f| f.name().eq_ignore_ascii_case(name))
+        })
+        .ok_or_else(|| anyhow!("column '{}' not found", name))
+}
+
+fn contains(column: &ArrayRef, text: &str) -> Result<BooleanArray> {
+    let column = cast(column, &DataType::Utf8)?;
+    let escaped = text
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    let pattern = StringArray::new_scalar(format!("%{}%", escaped));
+    Ok(ilike(&column, &pattern)?)
+}
+
+fn compare(column: &ArrayRef, operator: Operator, value: &str) -> Result<BooleanArray> {
+    if value.eq_ignore_ascii_case("null") {
+        return match operator {
+            Operator::Eq => Ok(is_null(column)?),
+            Operator::NotEq => Ok(is_not_null(column)?),
+            _ => bail!("NULL can only be compared with = and !="),
+        };
+    }
+    if operator == Operator::Contains {
+        return contains(column, value);
+    }
+    let value = cast(&StringArray::from(vec![value]), column.data_type())
+        .ok()
+        .filter(|v| v.is_valid(0))
+        .ok_or_else(|| anyhow!("'{}' is not a valid {} value", value, column.data_type()))?;
+    let value = Scalar::new(value);
+    let column: &dyn Datum = column;
+    let result = match operator {
+        Operator::Eq => eq(column, &value),
+        Operator::NotEq => neq(column, &value),
+        Operator::Lt => lt(column, &value),
+        Operator::LtEq => lt_eq(column, &value),
+        Operator::Gt => gt(column, &value),
+        Operator::GtEq => gt_eq(column, &value),
+        Operator::Contains => unreachable!("handled above"),
+    };
+    Ok(result?)
+}
+
+/// Rows of the result filtered and sorted by the keys in order, NULLs last
+pub fn arrange(
+    batches: &[RecordBatch],
+    sort: &[SortKey],
+    filter: Option<&Filter>,
+) -> Result<RecordBatch> {
+    let Some(first) = batches.first() else {
+        bail!("no rows");
+    };
+    let mut batch = concat_batches(&first.schema(), batches)?;
+    if let Some(filter) = filter {
+        batch = filter_record_batch(&batch, &filter.evaluate(&batch)?)?;
+    }
+    if !sort.is_empty() {
+        let columns = sort
+            .iter()
+            .map(|key| SortColumn {
+                values: batch.column(key.column).clone(),
+                options: Some(SortOptions {
+                    descending: key.descending,
+                    nulls_first: false,
+                }),
+            })
+            .collect::<Vec<_>>();
+        let indices = lexsort_to_indices(&columns, None)?;
+        batch = take_record_batch(&batch, &indices)?;
+    }
+    Ok(batch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::{Float64Array, Int64Array};
+    use std::sync::Arc;
+
+    fn batches() -> Vec<RecordBatch> {
+        let batch = RecordBatch::try_from_iter([
+            (
+                "id",
+                Arc::new(Int64Array::from(vec![1, 2, 3, 4])) as ArrayRef,
+            ),
+            (
+                "region",
+                Arc::new(StringArray::from(vec![
+                    Some("eu"),
+                    Some("us"),
+                    Some("eu"),
+                    None,
+                ])),
+            ),
+            (
+                "total",
+                Arc::new(Float64Array::from(vec![
+                    Some(100.5),
+                    Some(50.0),
+                    Some(200.0),
+                    None,
+                ])),
+            ),
+            (
+                "name",
+                Arc::new(StringArray::from(vec![
+                    "Ann Smith",
+                    "bob",
+                    "Smithers",
+                    "x_y",
+                ])),
+            ),
+        ])
+        .unwrap();
+        // results usually come in several batches
+        vec![batch.slice(0, 3), batch.slice(3, 1)]
+    }
+
+    fn ids(sort: &[SortKey], filter: &str) -> Result<Vec<i64>> {
+        let filter = filter.parse::<Filter>()?;
+        let batch = arrange(&batches(), sort, Some(&filter))?;
+        let ids = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        Ok(ids.values().to_vec())
+    }
+
+    fn filtered(filter: &str) -> Vec<i64> {
+        ids(&[], filter).unwrap()
+    }
+
+    fn compare(column: &str, operator: Operator, value: &str) -> Condition {
+        Condition::Compare {
+            column: column.to_string(),
+            operator,
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn parse() {
+        let parse = |text: &str| text.parse::<Filter>().unwrap().conditions;
+        assert_eq!(parse("a<=1"), [compare("a", Operator::LtEq, "1")]);
+        assert_eq!(parse("a<1"), [compare("a", Operator::Lt, "1")]);
+        assert_eq!(parse("a>=1"), [compare("a", Operator::GtEq, "1")]);
+        assert_eq!(parse("a!=1"), [compare("a", Operator::NotEq, "1")]);
+        // the first operator in the word wins
+        assert_eq!(parse("a=b<c"), [compare("a", Operator::Eq, "b<c")]);
+        assert_eq!(parse("a<b=c"), [compare("a", Operator::Lt, "b=c")]);
+        assert_eq!(
+            parse(r#"name~"ann smith"  "two words" x"#),
+            [
+                compare("name", Operator::Contains, "ann smith"),
+                Condition::Text("two words".to_string()),
+                Condition::Text("x".to_string()),
+            ]
+        );
+        assert_eq!(parse(r#"note="""#), [compare("note", Operator::Eq, "")]);
+        assert_eq!(parse("=null"), [Condition::Text("=null".to_string())]);
+        assert!("  ".parse::<Filter>().unwrap().is_empty());
+        assert_eq!(
+            r#"name="ann"#.parse::<Filter>().unwrap_err().to_string(),
+            "unclosed quote"
+        );
+    }
+
+    #[test]
+    fn comparisons() {
+        assert_eq!(filtered("total>=100"), [1, 3]);
+        assert_eq!(filtered("total<=100"), [2]);
+        assert_eq!(filtered("total>100.5"), [3]);
+        assert_eq!(filtered("total<100.5"), [2]);
+        assert_eq!(filtered("region=eu total>150"), [3]);
+        assert_eq!(filtered("Region!=eu"), [2]);
+        assert_eq!(filtered("name~SMITH"), [1, 3]);
+        assert_eq!(filtered(r#"name="Ann Smith""#), [1]);
+        assert_eq!(filtered("id=3"), [3]);
+    }
+
+    #[test]
+    fn nulls() {
+        assert_eq!(filtered("region=null"), [4]);
+        assert_eq!(filtered("total=NULL"), [4]);
+        assert_eq!(filtered("region!=null"), [1, 2, 3]);
+        assert_eq!(
+            ids(&[], "total<null").unwrap_err().to_string(),
+            "NULL can only be compared with = and !="
+        );
+    }
+
+    #[test]
+    fn text_in_any_column() {
+        assert_eq!(filtered("smith"), [1, 3]);
+        assert_eq!(filtered("200"), [3]);
+        assert_eq!(filtered("_"), [4]);
+        assert_eq!(filtered("%"), Vec::<i64>::new());
+        assert_eq!(filtered("=null"), Vec::<i64>::new());
+    }
+
+    #[test]
+    fn invalid_values() {
+        let error = |filter: &str| ids(&[], filter).unwrap_err().to_string();
+        assert_eq!(error("id>abc"), "'abc' is not a valid Int64 value");
+        assert_eq!(error("id=1.5"), "'1.5' is not a valid Int64 value");
+        assert_eq!(error("total<"), "'' is not a valid Float64 value");
+        assert_eq!(error("nope=1"), "column 'nope' not found");
+    }
+
+    #[test]
+    fn sort() {
+        let key = |column, descending| SortKey { column, descending };
+        assert_eq!(ids(&[key(2, false)], "").unwrap(), [2, 1, 3, 4]);
+        assert_eq!(ids(&[key(2, true)], "").unwrap(), [3, 1, 2, 4]);
+        // NULLs are last in both directions, later keys break ties
+        assert_eq!(
+            ids(&[key(1, false), key(0, true)], "").unwrap(),
+            [3, 1, 2, 4]
+        );
+        assert_eq!(
+            ids(&[key(1, true), key(0, true)], "").unwrap(),
+            [2, 3, 1, 4]
+        );
+        assert_eq!(ids(&[key(3, true)], "total>60").unwrap(), [3, 1]);
+        assert_eq!(arrange(&[], &[], None).unwrap_err().to_string(), "no rows");
+    }
+}
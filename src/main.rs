@@ -1,14 +1,141 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use clap::{Parser, Subcommand};
+use exo::config::{parse_bytes, Config, FormatterConfig, MemoryPoolKind};
+use exo::core::format::format_sql;
+use exo::core::notebook::{self, CellKind, NotebookCell};
+use exo::tui::config::TuiConfig;
 use exo::tui::start;
-use std::env;
-use std::fs::read_to_string;
+use std::fs::{read_to_string, write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
+
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// SQL file to load, cells are separated by `-- next-cell`
+    file: Option<PathBuf>,
+
+    /// Config file, `$XDG_CONFIG_HOME/exo/config.toml` by default
+    #[arg(long, short, global = true)]
+    config: Option<PathBuf>,
+
+    /// Default query timeout, e.g. `30s` or `5m`
+    #[arg(long, value_parser = humantime::parse_duration)]
+    timeout: Option<Duration>,
+
+    /// Memory limit of the session, e.g. `4GB`
+    #[arg(long, value_parser = parse_bytes)]
+    memory_limit: Option<usize>,
+
+    /// Memory pool used when the limit is set
+    #[arg(long, value_enum)]
+    memory_pool: Option<MemoryPoolKind>,
+
+    /// Directory for temporary files of operators spilling to disk
+    #[arg(long)]
+    spill_dir: Option<PathBuf>,
+
+    /// Edit cells with Vim key bindings
+    #[arg(long)]
+    vim: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Format SQL of notebook files in place
+    Fmt {
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+
+        /// Only report files which are not formatted, exit with 1 if there are any
+        #[arg(long)]
+        check: bool,
+    },
+}
+
+impl Args {
+    /// Command line arguments take precedence over the config file
+    fn apply(&self, config: &mut Config) {
+        if let Some(timeout) = self.timeout {
+            config.timeout = Some(timeout);
+        }
+        if let Some(limit) = self.memory_limit {
+            config.memory.limit = Some(limit);
+        }
+        if let Some(pool) = self.memory_pool {
+            config.memory.pool = pool;
+        }
+        if let Some(dir) = &self.spill_dir {
+            config.memory.spill_dir = Some(dir.clone());
+        }
+    }
+}
+
+/// Formatted content of the notebook, `None` if it is already formatted
+fn format_notebook(path: &Path, config: &FormatterConfig) -> Result<Option<String>> {
+    let content =
+        read_to_string(path).with_context(|| format!("Cannot read {}", path.display()))?;
+    let cells = notebook::parse(&content)
+        .into_iter()
+        .enumerate()
+        .map(|(i, cell)| match cell.kind {
+            CellKind::Sql => Ok(NotebookCell {
+                code: format_sql(&cell.code, config)
+                    .with_context(|| format!("{}, cell {}", path.display(), i + 1))?,
+                ..cell
+            }),
+            CellKind::Markdown => Ok(cell),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let formatted = notebook::serialize(&cells);
+    Ok((formatted != content).then_some(formatted))
+}
+
+fn fmt(files: &[PathBuf], check: bool, config: &FormatterConfig) -> Result<ExitCode> {
+    let mut unformatted = false;
+    for path in files {
+        let Some(formatted) = format_notebook(path, config)? else {
+            continue;
+        };
+        if check {
+            println!("Would reformat: {}", path.display());
+            unformatted = true;
+        } else {
+            write(path, formatted).with_context(|| format!("Cannot write {}", path.display()))?;
+            println!("Formatted {}", path.display());
+        }
+    }
+    Ok(if unformatted {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
 
 #[tokio::main]
-pub async fn main() -> Result<()> {
-    let preload = env::args()
-        .nth(1)
-        .and_then(|file| read_to_string(file).ok());
+pub async fn main() -> Result<ExitCode> {
+    let args = Args::parse();
+    let mut config = match &args.config {
+        Some(path) => Config::load(path)?,
+        None => Config::load_default()?,
+    };
+    args.apply(&mut config);
+
+    if let Some(Command::Fmt { files, check }) = &args.command {
+        return fmt(files, *check, &config.formatter);
+    }
+
+    let mut ui = TuiConfig::new(&config)?;
+    if args.vim {
+        ui.vim = true;
+    }
+
+    let preload = args.file.and_then(|file| read_to_string(file).ok());
 
-    start(preload).await?;
-    Ok(())
+    start(preload, config, ui).await?;
+    Ok(ExitCode::SUCCESS)
 }
@@ -1,9 +1,12 @@
+use super::config::TuiConfig;
 use super::state::State;
 use super::view;
-use crate::core::{DataFusionSession, LocalDataFusionSession};
+use crate::config::Config;
+use crate::core::display::localize;
+use crate::core::{notebook, DataFusionSession, LocalDataFusionSession, Progress};
 use crate::tui::handler::Handler;
-use crate::tui::message::{CellsMessage, Message};
-use anyhow::Result;
+use crate::tui::message::{CellsMessage, Message, SessionRequest, SettingsMessage};
+use anyhow::{anyhow, Context, Result};
 use crossterm::event;
 use crossterm::event::Event;
 use ratatui::{
@@ -14,11 +17,20 @@ use ratatui::{
     },
     Terminal,
 };
+use std::fs::read_to_string;
+use std::future::Future;
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::RecvTimeoutError;
 use std::sync::{mpsc, Arc, Mutex};
 use std::time::Duration;
+use std::{env, fs};
 use std::{io::stdout, panic};
 use uuid::Uuid;
 
+/// How often the UI is redrawn while a cell is running
+const TICK_RATE: Duration = Duration::from_millis(100);
+
 fn init_terminal() -> Result<Terminal<impl Backend>> {
     enable_raw_mode()?;
     stdout().execute(EnterAlternateScreen)?;
@@ -32,6 +44,40 @@ fn restore_terminal() -> Result<()> {
     Ok(())
 }
 
+/// Opens the code in `$VISUAL` or `$EDITOR` (`vi` if neither is set) and returns the edited code
+fn run_external_editor(code: &str) -> Result<String> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .ok()
+        .filter(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_string());
+    let path = env::temp_dir().join(format!("exo-{}.sql", Uuid::new_v4()));
+    fs::write(&path, code)
+        .with_context(|| format!("Cannot write temporary file {}", path.display()))?;
+
+    // the editor may come with arguments, e.g. `code --wait`
+    let mut args = editor.split_whitespace();
+    let program = args.next().unwrap_or("vi");
+    let result = match Command::new(program).args(args).arg(&path).status() {
+        Ok(status) if status.success() => fs::read_to_string(&path)
+            .with_context(|| format!("Cannot read temporary file {}", path.display())),
+        Ok(status) => Err(anyhow!("Editor '{}' exited with {}", editor, status)),
+        Err(err) => Err(anyhow!("Cannot run editor '{}': {}", editor, err)),
+    };
+    let _ = fs::remove_file(&path);
+    result
+}
+
+/// Leaves the TUI while the external editor is running and restores it afterwards
+fn edit_externally(terminal: &mut Terminal<impl Backend>, code: &str) -> Result<String> {
+    restore_terminal()?;
+    let result = run_external_editor(code);
+    enable_raw_mode()?;
+    stdout().execute(EnterAlternateScreen)?;
+    terminal.clear()?;
+    result
+}
+
 fn install_panic_hook() {
     let original_hook = panic::take_hook();
     panic::set_hook(Box::new(move |panic_info| {
@@ -52,36 +98,139 @@ fn user_event() -> Result<Option<Message>> {
     Ok(None)
 }
 
-pub async fn start(preload: Option<String>) -> Result<()> {
+/// Awaits the query, failing with a readable error if it runs longer than `timeout`
+async fn with_timeout<T: Send + 'static>(
+    timeout: Option<Duration>,
+    query: impl Future<Output = Result<T>> + Send + 'static,
+) -> Result<T> {
+    // The query runs as a separate task, so the timer fires even if the query never yields
+    let mut handle = tokio::spawn(query);
+    match timeout {
+        Some(timeout) => match tokio::time::timeout(timeout, &mut handle).await {
+            Ok(result) => result?,
+            Err(_) => {
+                handle.abort();
+                Err(anyhow!(
+                    "Query timed out after {}",
+                    humantime::format_duration(timeout)
+                ))
+            }
+        },
+        None => handle.await?,
+    }
+}
+
+pub async fn start(preload: Option<String>, config: Config, ui: TuiConfig) -> Result<()> {
     install_panic_hook();
+    let df = LocalDataFusionSession::from_config(&config)?;
+    for script in &config.startup {
+        let sql = read_to_string(script)
+            .with_context(|| format!("Cannot read startup script {}", script.display()))?;
+        df.run_script(&sql)
+            .await
+            .with_context(|| format!("Startup script {} failed", script.display()))?;
+    }
+    let mut state = State {
+        memory_pool: Some(df.memory_pool()),
+        config: config.clone(),
+        show_sidebar: ui.sidebar,
+        ui,
+        ..State::default()
+    };
     let mut terminal = init_terminal()?;
-    let mut state = State::default();
 
     let (sender, receiver) = mpsc::channel::<Vec<Message>>();
     let sender_from_ue = sender.clone();
+    // Held while the external editor runs, so the UI does not read its input.
+    // The flag makes the UI loop step aside, otherwise it could keep re-acquiring the lock.
+    let input_lock = Arc::new(Mutex::new(()));
+    let input_lock_ue = input_lock.clone();
+    let input_paused = Arc::new(AtomicBool::new(false));
+    let input_paused_ue = input_paused.clone();
 
     if let Some(p) = preload {
-        for cell_body in p.split("-- next-cell").collect::<Vec<_>>() {
-            sender.send(vec![Message::Cells(CellsMessage::Create(Some(
-                cell_body.trim().to_string(),
-            )))])?;
+        for cell in notebook::parse(&p) {
+            sender.send(vec![Message::Cells(CellsMessage::Load(cell))])?;
         }
     };
 
-    let (df_sender, df_receiver) = mpsc::channel::<(Uuid, String)>();
+    let (df_sender, df_receiver) = mpsc::channel::<SessionRequest>();
     let handler = Handler::new(df_sender);
 
     // Processing all DataFusion operations async
     let df_loop = tokio::spawn(async move {
-        let df = LocalDataFusionSession::new();
-
-        while let Ok((uuid, expr)) = df_receiver.recv() {
-            let messages = match df.sql(&expr).await {
-                Ok(result) => vec![Message::Cells(CellsMessage::SetResult(uuid, result))],
-                Err(err) => vec![Message::Cells(CellsMessage::SetError(
-                    uuid,
-                    err.to_string(),
-                ))],
+        while let Ok(request) = df_receiver.recv() {
+            let messages = match request {
+                SessionRequest::Execute {
+                    cell_id,
+                    expr,
+                    timeout,
+                } => {
+                    let progress_sender = sender.clone();
+                    let on_progress = move |progress: &Progress| {
+                        let _ = progress_sender.send(vec![Message::Cells(
+                            CellsMessage::SetProgress(cell_id, progress.clone()),
+                        )]);
+                    };
+                    let df = df.clone();
+                    let max_rows = config.max_rows;
+                    let query =
+                        async move { df.sql_with_progress(&expr, max_rows, on_progress).await };
+                    // timestamps are shown in the configured timezone
+                    let result = with_timeout(timeout.or(config.timeout), query)
+                        .await
+                        .and_then(|result| localize(result, &config.format));
+                    match result {
+                        Ok(result) => {
+                            vec![Message::Cells(CellsMessage::SetResult(cell_id, result))]
+                        }
+                        Err(err) => vec![Message::Cells(CellsMessage::SetError(
+                            cell_id,
+                            err.to_string(),
+                        ))],
+                    }
+                }
+                SessionRequest::Explain {
+                    cell_id,
+                    expr,
+                    analyze,
+                    timeout,
+                } => {
+                    let df = df.clone();
+                    let query = async move { df.explain(&expr, analyze).await };
+                    match with_timeout(timeout.or(config.timeout), query).await {
+                        Ok(plan) => {
+                            vec![Message::Cells(CellsMessage::SetPlan(
+                                cell_id,
+                                Box::new(plan),
+                            ))]
+                        }
+                        Err(err) => vec![Message::Cells(CellsMessage::SetError(
+                            cell_id,
+                            err.to_string(),
+                        ))],
+                    }
+                }
+                SessionRequest::Profile {
+                    cell_id,
+                    source,
+                    timeout,
+                } => {
+                    let df = df.clone();
+                    let query = async move { df.profile(source).await };
+                    let profile = with_timeout(timeout.or(config.timeout), query)
+                        .await
+                        .map(Box::new)
+                        .map_err(|err| err.to_string());
+                    vec![Message::Cells(CellsMessage::SetProfile(cell_id, profile))]
+                }
+                SessionRequest::LoadSettings => {
+                    vec![Message::Settings(SettingsMessage::Loaded(df.settings()))]
+                }
+                SessionRequest::SetSetting { key, value } => match df.set_setting(&key, &value) {
+                    Ok(()) => vec![Message::Settings(SettingsMessage::Loaded(df.settings()))],
+                    Err(err) => vec![Message::Settings(SettingsMessage::Failed(err.to_string()))],
+                },
             };
 
             sender.send(messages).unwrap();
@@ -92,13 +241,42 @@ pub async fn start(preload: Option<String>) -> Result<()> {
     let event_loop = tokio::spawn(async move {
         // We need to draw UI on start
         terminal.draw(|f| view::render(&state, f)).unwrap();
-        while let Ok(msgs) = receiver.recv() {
+        loop {
+            // Ticks are only needed to animate running cells, otherwise we wait for a message
+            let msgs = if state.cells.any_running() {
+                match receiver.recv_timeout(TICK_RATE) {
+                    Ok(msgs) => msgs,
+                    Err(RecvTimeoutError::Timeout) => vec![Message::Tick],
+                    Err(RecvTimeoutError::Disconnected) => return,
+                }
+            } else {
+                match receiver.recv() {
+                    Ok(msgs) => msgs,
+                    Err(_) => return,
+                }
+            };
             for msg in msgs {
                 handler.handle(&mut state, msg).unwrap();
             }
             if state.quit {
                 return;
             }
+            if let Some(cell_id) = state.external_edit.take() {
+                let code = state
+                    .cells
+                    .all
+                    .get(&cell_id)
+                    .and_then(|cell| cell.code.clone())
+                    .unwrap_or_default();
+                input_paused.store(true, Ordering::SeqCst);
+                let edited = {
+                    let _input = input_lock.lock().unwrap();
+                    edit_externally(&mut terminal, &code).map_err(|err| format!("{:#}", err))
+                };
+                input_paused.store(false, Ordering::SeqCst);
+                let msg = Message::Cells(CellsMessage::SetEditedCode(cell_id, edited));
+                handler.handle(&mut state, msg).unwrap();
+            }
             // In order to reduce CPU usage, we do not re-draw UI until a message has been received
             terminal.draw(|f| view::render(&state, f)).unwrap();
         }
@@ -108,7 +286,15 @@ pub async fn start(preload: Option<String>) -> Result<()> {
     let exit_flag_clone = exit_flag.clone();
     let ui_event_loop = tokio::spawn(async move {
         loop {
-            if let Some(msg) = user_event().unwrap() {
+            if input_paused_ue.load(Ordering::SeqCst) {
+                std::thread::sleep(Duration::from_millis(10));
+                continue;
+            }
+            let event = {
+                let _input = input_lock_ue.lock().unwrap();
+                user_event().unwrap()
+            };
+            if let Some(msg) = event {
                 if sender_from_ue.send(vec![msg]).is_err() {
                     break;
                 }
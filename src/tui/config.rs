@@ -0,0 +1,51 @@
+use crate::config::Config;
+use crate::tui::keymap::{KeyBinding, Keymap};
+use crate::tui::theme::Theme;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// Settings of the terminal UI, read from the sections of the config file kept in `Config::ui`
+#[derive(Debug, Clone, Default)]
+pub struct TuiConfig {
+    pub theme: Theme,
+    pub keymap: Keymap,
+    /// Vim-style modal editing in cells
+    pub vim: bool,
+    /// Show the list of cells on start
+    pub sidebar: bool,
+}
+
+/// Raw representation of the UI sections of the config file
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TuiConfigFile {
+    #[serde(default)]
+    theme: Theme,
+    /// Action name to key bindings, overriding the defaults
+    #[serde(default)]
+    keymap: BTreeMap<String, Vec<KeyBinding>>,
+    #[serde(default)]
+    vim: bool,
+    #[serde(default)]
+    sidebar: bool,
+}
+
+impl TuiConfig {
+    pub fn new(config: &Config) -> Result<Self> {
+        Self::parse(config).with_context(|| match &config.path {
+            Some(path) => format!("Invalid config file {}", path.display()),
+            None => "Invalid config".to_string(),
+        })
+    }
+
+    fn parse(config: &Config) -> Result<Self> {
+        let file: TuiConfigFile = toml::Value::Table(config.ui.clone()).try_into()?;
+        Ok(Self {
+            theme: file.theme,
+            keymap: Keymap::new(&file.keymap)?,
+            vim: file.vim,
+            sidebar: file.sidebar,
+        })
+    }
+}
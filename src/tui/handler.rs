@@ -1,24 +1,44 @@
-use super::message::{CellsMessage, Message};
-use super::state::{Cell, CellStatus, ConfirmDialog, ConfirmDialogButton, Mode, State};
+use super::history::Change;
+use super::keymap::{Action, KeyContext};
+use super::message::{CellsMessage, Message, SessionRequest, SettingsMessage};
+use super::palette::{Palette, PaletteItem};
+use super::search::{self, Match, ResultSearch, Search};
+use super::state::{
+    Cell, CellStatus, ConfirmDialog, ConfirmDialogButton, DiffView, Mode, PlanView, ProfileView,
+    SettingsPanel, State,
+};
+use super::vim::VimRequest;
+use crate::config::FormatConfig;
+use crate::core::chart::ChartColumns;
+use crate::core::format::format_sql;
+use crate::core::notebook::CellKind;
+use crate::core::plan::strip_explain;
+use crate::core::profile::{strip_summarize, ProfileSource};
+use crate::core::table::{arrange, Filter, SortKey};
+use crate::core::{changed_settings, directive};
 use anyhow::Result;
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
-use ratatui::style::{Color, Style};
 use ratatui::widgets::{Block, Borders};
 use std::sync::mpsc::Sender;
-use tui_textarea::TextArea;
+use tui_textarea::{CursorMove, TextArea};
 use uuid::Uuid;
 
+/// Rows moved by page up and page down in the result table
+const RESULT_PAGE: usize = 20;
+
 pub struct Handler {
-    df_channel: Sender<(Uuid, String)>,
+    df_channel: Sender<SessionRequest>,
 }
 
 impl Handler {
-    pub fn new(df_channel: Sender<(Uuid, String)>) -> Self {
+    pub fn new(df_channel: Sender<SessionRequest>) -> Self {
         Self { df_channel }
     }
 
-    fn create_cell(&self, state: &mut State, code: Option<String>) -> Result<()> {
+    /// Inserts a cell after the current one and selects it
+    fn create_cell(&self, state: &mut State, kind: CellKind, code: Option<String>) -> Change {
         let mut cell = Cell::new();
+        cell.kind = kind;
         cell.code = code;
         let cell_id = cell.id;
         state.cells.all.insert(cell.id, cell);
@@ -30,9 +50,20 @@ impl Handler {
         state
             .cells
             .editor
-            .set_cursor_style(Style::from((Color::White, Color::Gray)));
+            .set_cursor_style(state.ui.theme.cursor_inactive());
 
-        Ok(())
+        Change::Insert { index, id: cell_id }
+    }
+
+    /// Stores the editor text in the current cell, returns the change if the code differs
+    fn save_current(&self, state: &mut State) -> Option<Change> {
+        let lines = state.cells.editor.lines().join("\n");
+        let cell = state.cells.current_mut()?;
+        if cell.code.as_deref().unwrap_or_default() == lines {
+            return None;
+        }
+        let code = cell.code.replace(lines);
+        Some(Change::Code { id: cell.id, code })
     }
 
     fn switch_cell(&self, state: &mut State, cell_id: Uuid) {
@@ -41,45 +72,260 @@ impl Handler {
         state.cells.editor = TextArea::from(code.unwrap_or_default().lines());
         let block = Block::default()
             .borders(Borders::RIGHT)
-            .border_style(Color::Gray);
+            .border_style(state.ui.theme.separator);
         state.cells.editor.set_block(block);
+        state.vim.reset();
+    }
+
+    /// Marks the cell as running and sends its code to the DataFusion loop, applying directives.
+    /// `explain` asks for the query plan instead of the result, `Some(true)` for EXPLAIN ANALYZE.
+    /// `SUMMARIZE` cells ask for column statistics.
+    fn run_cell(&self, cell: &mut Cell, explain: Option<bool>) -> Result<()> {
+        let Some(code) = cell.code.clone() else {
+            return Ok(());
+        };
+        if cell.kind == CellKind::Markdown {
+            return Ok(());
+        }
+        let timeout = match directive::timeout(&code) {
+            Ok(timeout) => timeout,
+            Err(err) => {
+                cell.error = Some(err.to_string());
+                cell.finish(CellStatus::Failed);
+                return Ok(());
+            }
+        };
+
+        let explain = match explain {
+            Some(analyze) => Some((
+                strip_explain(&code).map_or(code.as_str(), |(q, _)| q),
+                analyze,
+            )),
+            None => strip_explain(&code),
+        };
+        let request = match (explain, strip_summarize(&code)) {
+            (Some((query, analyze)), _) => SessionRequest::Explain {
+                cell_id: cell.id,
+                expr: query.to_string(),
+                analyze,
+                timeout,
+            },
+            (None, Some(query)) => SessionRequest::Profile {
+                cell_id: cell.id,
+                source: ProfileSource::Query(query),
+                timeout,
+            },
+            (None, None) => SessionRequest::Execute {
+                cell_id: cell.id,
+                expr: code.clone(),
+                timeout,
+            },
+        };
+        cell.start();
+        cell.executed_code = Some(code);
+        self.df_channel.send(request)?;
+        Ok(())
+    }
+
+    /// Charts the new result as the `@chart` directive asks, otherwise keeps the chosen
+    /// columns while they still fit the result
+    fn update_chart(cell: &mut Cell) -> Result<()> {
+        let Some(schema) = cell.result.as_ref().map(|r| r.schema.clone()) else {
+            cell.chart = None;
+            return Ok(());
+        };
+        let code = cell.code.as_deref().unwrap_or_default();
+        if let Some(spec) = directive::chart(code)? {
+            cell.chart = Some(ChartColumns::resolve(&schema, &spec)?);
+        } else if let Some(chart) = &cell.chart {
+            let fits = [Some(chart.x), Some(chart.y), chart.series]
+                .into_iter()
+                .flatten()
+                .all(|i| i < schema.fields().len())
+                && schema.field(chart.y).data_type().is_numeric();
+            if !fits {
+                cell.chart = None;
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies the sort keys and the filter of the table to the result, the search follows
+    fn arrange_result(cell: &mut Cell, format: &FormatConfig) -> Result<()> {
+        let table = &mut cell.table;
+        table.arranged = None;
+        if let Some(result) = cell.result.as_ref().filter(|r| !r.batches.is_empty()) {
+            if !table.sort.is_empty() || table.filter.is_some() {
+                table.arranged = Some(arrange(
+                    &result.batches,
+                    &table.sort,
+                    table.filter.as_ref(),
+                )?);
+            }
+        }
+        let last_row = cell.visible_rows().saturating_sub(1);
+        cell.table.row = cell.table.row.min(last_row);
+        Self::refresh_search(cell, format);
+        Ok(())
+    }
+
+    /// Finds the matches of the result search again, e.g. after columns are hidden or moved
+    fn refresh_search(cell: &mut Cell, format: &FormatConfig) {
+        if let Some(mut search) = cell.table.search.take() {
+            search.update(cell.table_batches(), &cell.shown_columns(), format);
+            cell.table.search = Some(search);
+        }
     }
 
     fn handle_cell_messages(&self, state: &mut State, msg: CellsMessage) -> Result<()> {
         match msg {
             CellsMessage::ExecuteCurrent => {
                 if let Some(cell) = state.cells.current_mut() {
-                    if let Some(expr) = &cell.code {
-                        cell.status = CellStatus::Running;
-                        self.df_channel.send((cell.id, expr.clone()))?;
-                    }
+                    self.run_cell(cell, None)?;
+                }
+            }
+            CellsMessage::ExplainCurrent(analyze) => {
+                if let Some(cell) = state.cells.current_mut() {
+                    self.run_cell(cell, Some(analyze))?;
                 }
             }
             CellsMessage::SetResult(cell_id, result) => {
                 if let Some(cell) = state.cells.all.get_mut(&cell_id) {
-                    cell.result = Some(result);
-                    cell.status = CellStatus::Finished
+                    cell.result = Some(result.clone());
+                    cell.plan = None;
+                    cell.profile = None;
+                    cell.diff = None;
+                    cell.finish(CellStatus::Finished);
+                    cell.record_run(Ok(result), &state.config);
+                    if let Err(err) = Self::update_chart(cell) {
+                        state.message = Some(err.to_string());
+                    }
+                    cell.refresh_chart(&state.config.format);
+                    // sorting and the filter stay while they fit the new result
+                    let columns = cell.result.as_ref().map_or(0, |r| r.schema.fields().len());
+                    cell.table.sort.retain(|key| key.column < columns);
+                    cell.table.hidden.retain(|c| *c < columns);
+                    if cell.table.hidden.len() == columns {
+                        cell.table.hidden.clear();
+                    }
+                    cell.table.column = cell.table.column.min(columns.saturating_sub(1));
+                    if cell.table.hidden.contains(&cell.table.column) {
+                        cell.table.column = cell.shown_columns().first().copied().unwrap_or(0);
+                    }
+                    if let Err(err) = Self::arrange_result(cell, &state.config.format) {
+                        cell.table.sort.clear();
+                        cell.table.filter = None;
+                        cell.table.filter_text.clear();
+                        cell.table.arranged = None;
+                        state.message = Some(format!("Filter cleared: {}", err));
+                    }
+                }
+            }
+            CellsMessage::SetPlan(cell_id, plan) => {
+                if let Some(cell) = state.cells.all.get_mut(&cell_id) {
+                    cell.plan = Some(PlanView::new(*plan));
+                    cell.result = None;
+                    cell.profile = None;
+                    cell.finish(CellStatus::Finished);
+                }
+                if state.cells.current_cell_id == Some(cell_id) && state.mode == Mode::Navigate {
+                    state.mode = Mode::Plan;
+                }
+            }
+            CellsMessage::ProfileCurrent => {
+                let Some(cell) = state.cells.current() else {
+                    return Ok(());
+                };
+                let code = cell.code.as_deref().unwrap_or_default();
+                match &cell.result {
+                    _ if matches!(cell.status, CellStatus::Running) => {}
+                    Some(result) if matches!(cell.status, CellStatus::Finished) => {
+                        self.df_channel.send(SessionRequest::Profile {
+                            cell_id: cell.id,
+                            source: ProfileSource::Collected(result.clone()),
+                            timeout: directive::timeout(code).ok().flatten(),
+                        })?;
+                        state.message = Some("Profiling the result...".to_string());
+                    }
+                    _ if strip_summarize(code).is_some() => {
+                        self.handle(state, Message::Cells(CellsMessage::ExecuteCurrent))?;
+                    }
+                    _ => state.message = Some("No result to profile".to_string()),
+                }
+            }
+            CellsMessage::SetProfile(cell_id, profile) => {
+                let current = state.cells.current_cell_id == Some(cell_id);
+                let Some(cell) = state.cells.all.get_mut(&cell_id) else {
+                    return Ok(());
+                };
+                // a SUMMARIZE cell runs like a query, a result is profiled in the background
+                let running = matches!(cell.status, CellStatus::Running);
+                match profile {
+                    Ok(profile) => {
+                        cell.profile = Some(ProfileView::new(*profile));
+                        if running {
+                            cell.result = None;
+                            cell.plan = None;
+                            cell.finish(CellStatus::Finished);
+                        } else {
+                            state.message = None;
+                        }
+                        if current && state.mode == Mode::Navigate {
+                            state.mode = Mode::Profile;
+                        }
+                    }
+                    Err(error) if running => {
+                        cell.error = Some(error);
+                        cell.finish(CellStatus::Failed);
+                    }
+                    Err(error) => {
+                        state.message = Some(format!("Cannot profile the result: {}", error));
+                    }
                 }
             }
             CellsMessage::SetError(cell_id, error) => {
                 if let Some(cell) = state.cells.all.get_mut(&cell_id) {
-                    cell.error = Some(error);
-                    cell.status = CellStatus::Failed
+                    cell.error = Some(error.clone());
+                    cell.finish(CellStatus::Failed);
+                    cell.record_run(Err(error), &state.config);
+                }
+            }
+            CellsMessage::SetProgress(cell_id, progress) => {
+                if let Some(cell) = state.cells.all.get_mut(&cell_id) {
+                    if matches!(cell.status, CellStatus::Running) {
+                        cell.progress = Some(progress);
+                    }
                 }
             }
             CellsMessage::SaveCurrent => {
-                let lines = state.cells.editor.lines().join("\n").to_string();
-                if let Some(cell) = state.cells.current_mut() {
-                    cell.code = Some(lines)
+                if let Some(change) = self.save_current(state) {
+                    state.history.record(change);
                 }
             }
             CellsMessage::Create(code) => {
-                self.create_cell(state, code)?;
-                state
-                    .cells
-                    .editor
-                    .set_cursor_style(Style::from((Color::White, Color::Black)));
+                let change = self.create_cell(state, CellKind::Sql, code);
+                state.history.record(change);
+                state.cells.editor.set_cursor_style(state.ui.theme.cursor());
                 state.mode = Mode::EditCell;
+                state.vim.insert(&state.cells.editor);
+            }
+            CellsMessage::Load(cell) => {
+                // cells of the opened notebook are not a change which could be undone
+                self.create_cell(state, cell.kind, Some(cell.code));
+                state.cells.editor.set_cursor_style(state.ui.theme.cursor());
+                state.mode = Mode::EditCell;
+                state.vim.insert(&state.cells.editor);
+            }
+            CellsMessage::ToggleKind => {
+                if let Some(cell) = state.cells.current_mut() {
+                    let kind = match cell.kind {
+                        CellKind::Sql => CellKind::Markdown,
+                        CellKind::Markdown => CellKind::Sql,
+                    };
+                    let kind = std::mem::replace(&mut cell.kind, kind);
+                    let id = cell.id;
+                    state.history.record(Change::Kind { id, kind });
+                }
             }
             CellsMessage::DeleteCurrent => {
                 if let Some(index) = state.cells.current_cell_index() {
@@ -93,7 +339,12 @@ impl Handler {
                         }
                     };
                     let id = state.cells.order.remove(index);
-                    state.cells.all.remove(&id);
+                    if let Some(cell) = state.cells.all.remove(&id) {
+                        state.history.record(Change::Remove {
+                            index,
+                            cell: Box::new(cell),
+                        });
+                    }
 
                     if let Some(id) = new_current_index.map(|i| state.cells.order[i]) {
                         self.switch_cell(state, id);
@@ -102,6 +353,279 @@ impl Handler {
                     }
                 }
             }
+            CellsMessage::MoveCurrent(up) => {
+                if let Some(index) = state.cells.current_cell_index() {
+                    let target = if up {
+                        index.checked_sub(1)
+                    } else {
+                        Some(index + 1).filter(|i| *i < state.cells.order.len())
+                    };
+                    if let Some(target) = target {
+                        state.cells.order.swap(index, target);
+                        state.history.record(Change::Move {
+                            from: index,
+                            to: target,
+                        });
+                    }
+                }
+            }
+            CellsMessage::DuplicateCurrent => {
+                let saved = self.save_current(state);
+                if let Some(cell) = state.cells.current() {
+                    let insert = self.create_cell(state, cell.kind, cell.code.clone());
+                    let changes = saved.into_iter().chain([insert]).collect();
+                    state.history.record(Change::Group(changes));
+                }
+            }
+            CellsMessage::SplitCurrent => {
+                let Some(id) = state.cells.current_cell_id else {
+                    return Ok(());
+                };
+                let (row, col) = state.cells.editor.cursor();
+                let lines = state.cells.editor.lines();
+                let split = lines[row]
+                    .char_indices()
+                    .nth(col)
+                    .map_or(lines[row].len(), |(i, _)| i);
+                let (head, tail) = lines[row].split_at(split);
+                let before = [&lines[..row], &[head.to_string()]].concat().join("\n");
+                let after = [&[tail.to_string()], &lines[row + 1..]].concat().join("\n");
+
+                let Some(cell) = state.cells.current_mut() else {
+                    return Ok(());
+                };
+                let kind = cell.kind;
+                let code = cell.code.replace(before.trim_end().to_string());
+                let insert = self.create_cell(state, kind, Some(after.trim_start().to_string()));
+                state
+                    .history
+                    .record(Change::Group(vec![Change::Code { id, code }, insert]));
+                if state.mode == Mode::EditCell {
+                    state.cells.editor.set_cursor_style(state.ui.theme.cursor());
+                }
+            }
+            CellsMessage::MergeWithNext => {
+                let saved = self.save_current(state);
+                let Some(index) = state.cells.current_cell_index() else {
+                    return Ok(());
+                };
+                // merged prose would be executed as SQL and the other way around
+                let kinds = state.cells.current().map(|cell| cell.kind).zip(
+                    state
+                        .cells
+                        .order
+                        .get(index + 1)
+                        .and_then(|id| state.cells.all.get(id))
+                        .map(|cell| cell.kind),
+                );
+                if kinds.is_some_and(|(kind, next)| kind != next) {
+                    state
+                        .history
+                        .record(Change::Group(saved.into_iter().collect()));
+                    state.message = Some("Only cells of the same kind can be merged".to_string());
+                    return Ok(());
+                }
+                let Some(next) = state
+                    .cells
+                    .order
+                    .get(index + 1)
+                    .and_then(|id| state.cells.all.remove(id))
+                else {
+                    state
+                        .history
+                        .record(Change::Group(saved.into_iter().collect()));
+                    return Ok(());
+                };
+                state.cells.order.remove(index + 1);
+                let id = state.cells.order[index];
+                let mut changes = saved.into_iter().collect::<Vec<_>>();
+                if let Some(cell) = state.cells.current_mut() {
+                    let merged = [cell.code.clone(), next.code.clone()]
+                        .into_iter()
+                        .flatten()
+                        .filter(|code| !code.trim().is_empty())
+                        .collect::<Vec<_>>()
+                        .join("\n\n");
+                    let code = cell.code.replace(merged);
+                    changes.push(Change::Code { id, code });
+                }
+                changes.push(Change::Remove {
+                    index: index + 1,
+                    cell: Box::new(next),
+                });
+                state.history.record(Change::Group(changes));
+                self.switch_cell(state, id);
+            }
+            CellsMessage::Undo | CellsMessage::Redo => {
+                self.handle(state, Message::Cells(CellsMessage::SaveCurrent))?;
+                let focus = if msg == CellsMessage::Undo {
+                    state.history.undo(&mut state.cells)
+                } else {
+                    state.history.redo(&mut state.cells)
+                };
+                match focus {
+                    Some(Some(id)) => self.switch_cell(state, id),
+                    Some(None) => state.cells.current_cell_id = None,
+                    None if msg == CellsMessage::Undo => {
+                        state.message = Some("Nothing to undo".to_string())
+                    }
+                    None => state.message = Some("Nothing to redo".to_string()),
+                }
+            }
+            CellsMessage::EditExternally => {
+                self.handle(state, Message::Cells(CellsMessage::SaveCurrent))?;
+                state.external_edit = state.cells.current_cell_id;
+            }
+            CellsMessage::SetEditedCode(cell_id, Ok(code)) => {
+                if state.cells.current_cell_id != Some(cell_id) {
+                    self.switch_cell(state, cell_id);
+                }
+                // editors usually end the file with a newline
+                let code = code.strip_suffix('\n').unwrap_or(&code);
+                let editor = &mut state.cells.editor;
+                editor.select_all();
+                editor.insert_str(code);
+                editor.move_cursor(tui_textarea::CursorMove::Top);
+                self.handle(state, Message::Cells(CellsMessage::SaveCurrent))?;
+            }
+            CellsMessage::SetEditedCode(_, Err(error)) => {
+                state.message = Some(error);
+            }
+            CellsMessage::FormatCurrent => {
+                if state
+                    .cells
+                    .current()
+                    .is_some_and(|c| c.kind == CellKind::Markdown)
+                {
+                    return Ok(());
+                }
+                let code = state.cells.editor.lines().join("\n");
+                match format_sql(&code, &state.config.formatter) {
+                    Ok(formatted) if formatted != code => {
+                        let editor = &mut state.cells.editor;
+                        editor.select_all();
+                        editor.insert_str(&formatted);
+                        editor.move_cursor(tui_textarea::CursorMove::Top);
+                        self.handle(state, Message::Cells(CellsMessage::SaveCurrent))?;
+                    }
+                    Ok(_) => {}
+                    Err(error) => state.message = Some(error.to_string()),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_settings_messages(&self, state: &mut State, msg: SettingsMessage) -> Result<()> {
+        match msg {
+            SettingsMessage::Open => {
+                state.settings = Some(SettingsPanel::default());
+                self.df_channel.send(SessionRequest::LoadSettings)?;
+            }
+            SettingsMessage::Loaded(entries) => {
+                if let Some(panel) = state.settings.as_mut() {
+                    panel.entries = entries;
+                    panel.selected = panel.selected.min(panel.visible().len().saturating_sub(1));
+                }
+            }
+            SettingsMessage::Set(key, value) => {
+                if let Some(panel) = state.settings.as_mut() {
+                    panel.message = Some(format!("{} = {}", key, value));
+                }
+                self.df_channel
+                    .send(SessionRequest::SetSetting { key, value })?;
+            }
+            SettingsMessage::Failed(error) => {
+                if let Some(panel) = state.settings.as_mut() {
+                    panel.message = None;
+                    panel.error = Some(error.lines().collect::<Vec<_>>().join(" "));
+                }
+            }
+            SettingsMessage::Persist => {
+                if let Some(panel) = state.settings.as_mut() {
+                    let options = changed_settings(&panel.entries)
+                        .into_iter()
+                        .map(|s| (s.key, s.value.unwrap_or_default()))
+                        .collect();
+                    match state.config.save_session_options(&options) {
+                        Ok(path) => {
+                            panel.message = Some(format!(
+                                "Saved {} changed settings to {}",
+                                options.len(),
+                                path.display()
+                            ));
+                            state.config.session = options;
+                        }
+                        Err(err) => panel.error = Some(format!("{:#}", err)),
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_settings_keys(&self, state: &mut State, key: KeyEvent) -> Result<()> {
+        let Some(panel) = state.settings.as_mut() else {
+            return Ok(());
+        };
+        panel.message = None;
+        panel.error = None;
+
+        if let Some(editor) = panel.editor.as_mut() {
+            match key.code {
+                KeyCode::Enter => {
+                    let value = editor.lines().join("");
+                    let entry = panel.selected_entry().map(|e| e.key.clone());
+                    panel.editor = None;
+                    if let Some(entry) = entry {
+                        let msg = SettingsMessage::Set(entry, value.trim().to_string());
+                        self.handle(state, Message::Settings(msg))?;
+                    }
+                }
+                KeyCode::Esc => panel.editor = None,
+                _ => {
+                    editor.input(key);
+                }
+            }
+            return Ok(());
+        }
+
+        if panel.filtering {
+            match key.code {
+                KeyCode::Enter | KeyCode::Esc | KeyCode::Up | KeyCode::Down => {
+                    panel.filtering = false
+                }
+                _ => {
+                    panel.filter.input(key);
+                    panel.selected = 0;
+                }
+            }
+            return Ok(());
+        }
+
+        let visible = panel.visible().len();
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => state.settings = None,
+            KeyCode::Up | KeyCode::Char('k') => panel.selected = panel.selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => {
+                panel.selected = (panel.selected + 1).min(visible.saturating_sub(1))
+            }
+            KeyCode::PageUp => panel.selected = panel.selected.saturating_sub(10),
+            KeyCode::PageDown => {
+                panel.selected = (panel.selected + 10).min(visible.saturating_sub(1))
+            }
+            KeyCode::Char('/') => panel.filtering = true,
+            KeyCode::Enter | KeyCode::Char('e') => {
+                if let Some(entry) = panel.selected_entry() {
+                    let mut editor = TextArea::from([entry.value.clone().unwrap_or_default()]);
+                    editor.move_cursor(tui_textarea::CursorMove::End);
+                    panel.editor = Some(editor);
+                }
+            }
+            KeyCode::Char('w') => {
+                self.handle(state, Message::Settings(SettingsMessage::Persist))?;
+            }
+            _ => {}
         }
         Ok(())
     }
@@ -125,19 +649,15 @@ impl Handler {
                     self.handle(state, popup.message.clone())?;
                     state.popup = None;
                 }
-                KeyCode::Right => {
-                    if popup.active_button == ConfirmDialogButton::Yes {
-                        let mut p = (*popup).clone();
-                        p.active_button = ConfirmDialogButton::No;
-                        state.popup.replace(p);
-                    }
+                KeyCode::Right if popup.active_button == ConfirmDialogButton::Yes => {
+                    let mut p = (*popup).clone();
+                    p.active_button = ConfirmDialogButton::No;
+                    state.popup.replace(p);
                 }
-                KeyCode::Left => {
-                    if popup.active_button == ConfirmDialogButton::No {
-                        let mut p = (*popup).clone();
-                        p.active_button = ConfirmDialogButton::Yes;
-                        state.popup.replace(p);
-                    }
+                KeyCode::Left if popup.active_button == ConfirmDialogButton::No => {
+                    let mut p = (*popup).clone();
+                    p.active_button = ConfirmDialogButton::Yes;
+                    state.popup.replace(p);
                 }
                 _ => {}
             }
@@ -146,35 +666,91 @@ impl Handler {
         Ok(())
     }
 
-    fn handle_navigate_messages(&self, state: &mut State, key: KeyEvent) -> Result<()> {
-        match key.code {
-            KeyCode::Char('?') | KeyCode::F(1) => {
+    /// Runs an action triggered by a key binding
+    pub fn perform(&self, state: &mut State, action: Action) -> Result<()> {
+        match action {
+            Action::Help => {
                 state.show_help = true;
             }
-            KeyCode::Char('q') => {
+            Action::Quit => {
                 self.handle(state, Message::ConfirmQuit)?;
             }
-            KeyCode::Char('x') => {
+            Action::Execute => {
                 self.handle(state, Message::Cells(CellsMessage::ExecuteCurrent))?;
             }
-            KeyCode::Char('n') => {
+            Action::EditExternally => {
+                self.handle(state, Message::Cells(CellsMessage::EditExternally))?;
+            }
+            Action::Create => {
                 self.handle(state, Message::Cells(CellsMessage::Create(None)))?;
             }
-            KeyCode::Char('d') => {
+            Action::Settings => {
+                self.handle(state, Message::Settings(SettingsMessage::Open))?;
+            }
+            Action::Explain => {
+                self.handle(state, Message::Cells(CellsMessage::ExplainCurrent(false)))?;
+            }
+            Action::ExplainAnalyze => {
+                self.handle(state, Message::Cells(CellsMessage::ExplainCurrent(true)))?;
+            }
+            Action::FocusPlan => {
+                if state.cells.current().is_some_and(|c| c.plan.is_some()) {
+                    state.mode = Mode::Plan;
+                }
+            }
+            Action::MoveUp => {
+                self.handle(state, Message::Cells(CellsMessage::MoveCurrent(true)))?;
+            }
+            Action::MoveDown => {
+                self.handle(state, Message::Cells(CellsMessage::MoveCurrent(false)))?;
+            }
+            Action::Duplicate => {
+                self.handle(state, Message::Cells(CellsMessage::DuplicateCurrent))?;
+            }
+            Action::Merge => {
+                self.handle(state, Message::Cells(CellsMessage::MergeWithNext))?;
+            }
+            Action::Palette => {
+                state.palette = Some(Palette::default());
+            }
+            Action::Find | Action::Replace => {
+                self.handle(state, Message::Cells(CellsMessage::SaveCurrent))?;
+                let (row, col) = state.cells.editor.cursor();
+                let index = state.cells.current_cell_index().unwrap_or(0);
+                state.search = Some(Search {
+                    replacing: action == Action::Replace,
+                    origin: (index, row, col),
+                    ..Search::default()
+                });
+            }
+            Action::ToggleKind => {
+                self.handle(state, Message::Cells(CellsMessage::ToggleKind))?;
+            }
+            Action::ToggleSidebar => {
+                state.show_sidebar = !state.show_sidebar;
+            }
+            Action::Undo => {
+                self.handle(state, Message::Cells(CellsMessage::Undo))?;
+            }
+            Action::Redo => {
+                self.handle(state, Message::Cells(CellsMessage::Redo))?;
+            }
+            Action::Split => {
+                self.handle(state, Message::Cells(CellsMessage::SplitCurrent))?;
+            }
+            Action::Delete => {
                 state.popup = Some(ConfirmDialog {
                     message: Message::Cells(CellsMessage::DeleteCurrent),
                     body: "Delete current cell?".to_string(),
                     active_button: ConfirmDialogButton::Yes,
                 });
             }
-            KeyCode::Enter | KeyCode::Left | KeyCode::Char('h') => {
-                state
-                    .cells
-                    .editor
-                    .set_cursor_style(Style::from((Color::White, Color::Black)));
+            Action::Edit => {
+                state.cells.editor.set_cursor_style(state.ui.theme.cursor());
                 state.mode = Mode::EditCell;
+                state.vim.normal(&mut state.cells.editor);
             }
-            KeyCode::Up | KeyCode::Char('k') => {
+            Action::Previous => {
                 if let Some(index) = state.cells.current_cell_index() {
                     if index > 0 {
                         let new_index = index - 1;
@@ -182,7 +758,7 @@ impl Handler {
                     }
                 }
             }
-            KeyCode::Down | KeyCode::Char('j') => {
+            Action::Next => {
                 if let Some(index) = state.cells.current_cell_index() {
                     if index + 1 < state.cells.order.len() {
                         let new_index = index + 1;
@@ -190,31 +766,960 @@ impl Handler {
                     }
                 }
             }
+            Action::SaveAndExecute => {
+                self.handle(state, Message::Cells(CellsMessage::SaveCurrent))?;
+                self.handle(state, Message::Cells(CellsMessage::ExecuteCurrent))?;
+            }
+            Action::Format => {
+                self.handle(state, Message::Cells(CellsMessage::FormatCurrent))?;
+            }
+            Action::LeaveEditor => {
+                state
+                    .cells
+                    .editor
+                    .set_cursor_style(state.ui.theme.cursor_inactive());
+                state.mode = Mode::Navigate;
+                self.handle(state, Message::Cells(CellsMessage::SaveCurrent))?;
+            }
+            Action::PlanClose => {
+                state.mode = Mode::Navigate;
+            }
+            Action::Chart => {
+                let Some(cell) = state.cells.current_mut() else {
+                    return Ok(());
+                };
+                let Some(schema) = cell.result.as_ref().map(|r| r.schema.clone()) else {
+                    state.message = Some("No result to chart".to_string());
+                    return Ok(());
+                };
+                if cell.chart.is_none() {
+                    let code = cell.code.as_deref().unwrap_or_default();
+                    let chart = directive::chart(code)
+                        .and_then(|spec| ChartColumns::resolve(&schema, &spec.unwrap_or_default()));
+                    match chart {
+                        Ok(chart) => cell.chart = Some(chart),
+                        Err(err) => {
+                            state.message = Some(err.to_string());
+                            return Ok(());
+                        }
+                    }
+                    cell.refresh_chart(&state.config.format);
+                }
+                state.mode = Mode::Chart;
+            }
+            Action::ChartKind | Action::ChartX | Action::ChartY | Action::ChartSeries => {
+                if let Some(cell) = state.cells.current_mut() {
+                    Self::change_chart(cell, action);
+                    cell.refresh_chart(&state.config.format);
+                }
+            }
+            Action::ChartTable => {
+                if let Some(cell) = state.cells.current_mut() {
+                    cell.chart = None;
+                    cell.chart_data = None;
+                }
+                state.mode = Mode::Navigate;
+            }
+            Action::ChartClose => {
+                state.mode = Mode::Navigate;
+            }
+            Action::Profile => {
+                if state.cells.current().is_some_and(|c| c.profile.is_some()) {
+                    state.mode = Mode::Profile;
+                } else {
+                    self.handle(state, Message::Cells(CellsMessage::ProfileCurrent))?;
+                }
+            }
+            Action::CompareBase => {
+                let Some(id) = state.cells.current_cell_id else {
+                    return Ok(());
+                };
+                if state.compare_base == Some(id) {
+                    state.compare_base = None;
+                    state.message = Some("Comparison base cleared".to_string());
+                } else {
+                    state.compare_base = Some(id);
+                    state.message = Some(format!(
+                        "Results are compared with {}",
+                        Self::cell_label(state, id)
+                    ));
+                }
+            }
+            Action::Compare => {
+                let Some(cell) = state.cells.current() else {
+                    return Ok(());
+                };
+                let Some(after) = cell.result.clone() else {
+                    state.message = Some("No result to compare".to_string());
+                    return Ok(());
+                };
+                let label = Self::cell_label(state, cell.id);
+                let base = state
+                    .compare_base
+                    .filter(|id| *id != cell.id)
+                    .and_then(|id| state.cells.all.get(&id));
+                let (before_label, before) = match (base, cell.previous_result()) {
+                    (Some(base), _) => match &base.result {
+                        Some(result) => (Self::cell_label(state, base.id), result.clone()),
+                        None => {
+                            state.message = Some(format!(
+                                "{} has no result to compare with",
+                                Self::cell_label(state, base.id)
+                            ));
+                            return Ok(());
+                        }
+                    },
+                    (None, Some(previous)) => {
+                        (format!("previous run of {}", label), previous.clone())
+                    }
+                    (None, None) => {
+                        state.message = Some(
+                            "Nothing to compare with: run the cell again or mark another cell as the base"
+                                .to_string(),
+                        );
+                        return Ok(());
+                    }
+                };
+                let id = cell.id;
+                if let Some(cell) = state.cells.all.get_mut(&id) {
+                    cell.diff = Some(DiffView::new(before_label, before, label, after));
+                    state.mode = Mode::Diff;
+                }
+            }
+            Action::History => {
+                if state.cells.current().is_some_and(|c| !c.runs.is_empty()) {
+                    state.mode = Mode::History;
+                } else {
+                    state.message = Some("No runs of this cell yet".to_string());
+                }
+            }
+            Action::HistoryUp | Action::HistoryDown => {
+                if let Some(cell) = state.cells.current_mut() {
+                    let last = cell.runs.len().saturating_sub(1);
+                    cell.selected_run = match action {
+                        Action::HistoryUp => cell.selected_run.saturating_sub(1),
+                        _ => (cell.selected_run + 1).min(last),
+                    };
+                }
+            }
+            Action::HistoryRestore => {
+                let Some(cell) = state.cells.current_mut() else {
+                    return Ok(());
+                };
+                let Some(run) = cell.runs.get(cell.selected_run) else {
+                    return Ok(());
+                };
+                let finished_at = run.finished_at.format("%H:%M:%S");
+                let restored = run.code.clone();
+                if restored != cell.code {
+                    let id = cell.id;
+                    let code = std::mem::replace(&mut cell.code, restored);
+                    state.history.record(Change::Code { id, code });
+                    self.switch_cell(state, id);
+                }
+                state.message = Some(format!("Code of the run at {} restored", finished_at));
+                state.mode = Mode::Navigate;
+            }
+            Action::HistoryCompare => {
+                let Some(cell) = state.cells.current() else {
+                    return Ok(());
+                };
+                let Some(run) = cell.runs.get(cell.selected_run) else {
+                    return Ok(());
+                };
+                if run.outcome.is_err() {
+                    state.message = Some("The run failed, it has no result to compare".to_string());
+                    return Ok(());
+                }
+                let Some(before) = &run.result else {
+                    state.message = Some(
+                        "The result of this run is no longer kept, see `run_results` in the config"
+                            .to_string(),
+                    );
+                    return Ok(());
+                };
+                let Some(after) = cell.result.clone() else {
+                    state.message = Some("No result to compare".to_string());
+                    return Ok(());
+                };
+                let before_label = format!("run at {}", run.finished_at.format("%H:%M:%S"));
+                let view = DiffView::new(
+                    before_label,
+                    before.clone(),
+                    Self::cell_label(state, cell.id),
+                    after,
+                );
+                if let Some(cell) = state.cells.current_mut() {
+                    cell.diff = Some(view);
+                    state.mode = Mode::Diff;
+                }
+            }
+            Action::HistoryClose => {
+                state.mode = Mode::Navigate;
+            }
+            Action::FocusResult => {
+                let Some(cell) = state.cells.current_mut() else {
+                    return Ok(());
+                };
+                if cell.result.as_ref().is_none_or(|r| r.batches.is_empty()) {
+                    state.message = Some("No result to focus".to_string());
+                    return Ok(());
+                }
+                // the table takes the place of a chart or a profile
+                cell.chart = None;
+                cell.chart_data = None;
+                cell.profile = None;
+                state.mode = Mode::Result;
+            }
+            Action::ResultUp
+            | Action::ResultDown
+            | Action::ResultLeft
+            | Action::ResultRight
+            | Action::ResultPageUp
+            | Action::ResultPageDown
+            | Action::ResultTop
+            | Action::ResultBottom => {
+                if let Some(cell) = state.cells.current_mut() {
+                    Self::navigate_result(cell, action);
+                }
+            }
+            Action::ResultSort | Action::ResultClearSort => {
+                let Some(cell) = state.cells.current_mut() else {
+                    return Ok(());
+                };
+                let table = &mut cell.table;
+                if action == Action::ResultClearSort {
+                    table.sort.clear();
+                } else {
+                    let column = table.column;
+                    match table.sort_key(column).map(|(i, key)| (i, key.descending)) {
+                        None => table.sort.push(SortKey {
+                            column,
+                            descending: false,
+                        }),
+                        Some((i, false)) => table.sort[i].descending = true,
+                        Some((i, true)) => {
+                            table.sort.remove(i);
+                        }
+                    }
+                }
+                if let Err(err) = Self::arrange_result(cell, &state.config.format) {
+                    state.message = Some(err.to_string());
+                }
+            }
+            Action::ResultFilter => {
+                if let Some(cell) = state.cells.current_mut() {
+                    let mut editor = TextArea::from([cell.table.filter_text.clone()]);
+                    editor.move_cursor(CursorMove::End);
+                    cell.table.filter_editor = Some(editor);
+                }
+            }
+            Action::ResultClearFilter => {
+                if let Some(cell) = state.cells.current_mut() {
+                    cell.table.filter = None;
+                    cell.table.filter_text.clear();
+                    if let Err(err) = Self::arrange_result(cell, &state.config.format) {
+                        state.message = Some(err.to_string());
+                    }
+                }
+            }
+            Action::ResultSearch => {
+                if let Some(cell) = state.cells.current_mut() {
+                    let search = cell.table.search.get_or_insert_with(ResultSearch::default);
+                    search.editing = true;
+                    search.origin = cell.table.row;
+                }
+            }
+            Action::ResultNextMatch | Action::ResultPreviousMatch => {
+                let Some(cell) = state.cells.current_mut() else {
+                    return Ok(());
+                };
+                let selected = (cell.table.row, cell.table.column);
+                let backward = action == Action::ResultPreviousMatch;
+                match cell
+                    .table
+                    .search
+                    .as_ref()
+                    .map(|s| s.next(selected, backward))
+                {
+                    Some(Some((row, column))) => {
+                        cell.table.row = row;
+                        cell.table.column = column;
+                    }
+                    Some(None) => state.message = Some("No matches".to_string()),
+                    None => {}
+                }
+            }
+            Action::ResultHideColumn => {
+                let Some(cell) = state.cells.current_mut() else {
+                    return Ok(());
+                };
+                let columns = cell.shown_columns();
+                if columns.len() <= 1 {
+                    state.message = Some("Cannot hide the last column".to_string());
+                    return Ok(());
+                }
+                let Some(position) = columns.iter().position(|c| *c == cell.table.column) else {
+                    return Ok(());
+                };
+                cell.table.hidden.insert(cell.table.column);
+                // the next column takes the place of the hidden one
+                let next = columns
+                    .get(position + 1)
+                    .or_else(|| columns.get(position - 1));
+                cell.table.column = next.copied().unwrap_or(0);
+                if position < cell.table.frozen {
+                    cell.table.frozen -= 1;
+                }
+                // sorting and the filter stay the same, only the searched columns change
+                Self::refresh_search(cell, &state.config.format);
+            }
+            Action::ResultShowColumns => {
+                if let Some(cell) = state.cells.current_mut() {
+                    cell.table.hidden.clear();
+                    Self::refresh_search(cell, &state.config.format);
+                }
+            }
+            Action::ResultMoveLeft | Action::ResultMoveRight => {
+                let Some(cell) = state.cells.current_mut() else {
+                    return Ok(());
+                };
+                let columns = cell.shown_columns();
+                let Some(position) = columns.iter().position(|c| *c == cell.table.column) else {
+                    return Ok(());
+                };
+                let neighbour = match action {
+                    Action::ResultMoveLeft => position.checked_sub(1).map(|p| columns[p]),
+                    _ => columns.get(position + 1).copied(),
+                };
+                let Some(neighbour) = neighbour else {
+                    return Ok(());
+                };
+                let table = &mut cell.table;
+                let count = cell.result.as_ref().map_or(0, |r| r.schema.fields().len());
+                if table.order.len() != count {
+                    table.order = (0..count).collect();
+                }
+                // hidden columns keep their place, the selected one swaps with the shown neighbour
+                let from = table.order.iter().position(|c| *c == table.column);
+                let to = table.order.iter().position(|c| *c == neighbour);
+                if let (Some(from), Some(to)) = (from, to) {
+                    table.order.swap(from, to);
+                }
+                // matches follow the new order of the columns
+                Self::refresh_search(cell, &state.config.format);
+            }
+            Action::ResultFreeze => {
+                if let Some(cell) = state.cells.current_mut() {
+                    let columns = cell.shown_columns();
+                    let position = columns.iter().position(|c| *c == cell.table.column);
+                    let frozen = position.map_or(0, |p| p + 1);
+                    cell.table.frozen = if cell.table.frozen == frozen {
+                        0
+                    } else {
+                        frozen
+                    };
+                }
+            }
+            Action::ResultRecord => {
+                if let Some(cell) = state.cells.current_mut() {
+                    cell.table.record = !cell.table.record;
+                }
+            }
+            Action::ResultClose => match state.cells.current_mut().filter(|c| c.table.record) {
+                Some(cell) => cell.table.record = false,
+                None => state.mode = Mode::Navigate,
+            },
+            Action::ProfileUp | Action::ProfileDown => {
+                if let Some(view) = state.cells.current_mut().and_then(|c| c.profile.as_mut()) {
+                    let last = view.profile.columns.len().saturating_sub(1);
+                    view.selected = match action {
+                        Action::ProfileUp => view.selected.saturating_sub(1),
+                        _ => (view.selected + 1).min(last),
+                    };
+                }
+            }
+            Action::DiffUp | Action::DiffDown | Action::DiffPageUp | Action::DiffPageDown => {
+                if let Some(view) = state.cells.current_mut().and_then(|c| c.diff.as_mut()) {
+                    let last = view
+                        .diff
+                        .as_ref()
+                        .map_or(0, |d| d.rows.len().saturating_sub(1));
+                    view.selected = match action {
+                        Action::DiffUp => view.selected.saturating_sub(1),
+                        Action::DiffDown => (view.selected + 1).min(last),
+                        Action::DiffPageUp => view.selected.saturating_sub(RESULT_PAGE),
+                        _ => (view.selected + RESULT_PAGE).min(last),
+                    };
+                }
+            }
+            Action::DiffKey => {
+                if let Some(view) = state.cells.current_mut().and_then(|c| c.diff.as_mut()) {
+                    let mut editor = TextArea::new(vec![view.key_text.clone()]);
+                    editor.move_cursor(CursorMove::End);
+                    view.key_editor = Some(editor);
+                }
+            }
+            Action::DiffClose => {
+                if let Some(cell) = state.cells.current_mut() {
+                    cell.diff = None;
+                }
+                state.mode = Mode::Navigate;
+            }
+            Action::ProfileClose => {
+                // the profile of a result gives way to the table, a SUMMARIZE cell has nothing else
+                if let Some(cell) = state.cells.current_mut() {
+                    if cell.result.is_some() {
+                        cell.profile = None;
+                    }
+                }
+                state.mode = Mode::Navigate;
+            }
+            Action::PlanUp
+            | Action::PlanDown
+            | Action::PlanToggle
+            | Action::PlanExpand
+            | Action::PlanCollapse
+            | Action::PlanSwitchTab => {
+                if let Some(view) = state.cells.current_mut().and_then(|c| c.plan.as_mut()) {
+                    Self::navigate_plan(view, action);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn navigate_result(cell: &mut Cell, action: Action) {
+        let last_row = cell.visible_rows().saturating_sub(1);
+        let columns = cell.shown_columns();
+        let table = &mut cell.table;
+        let position = columns.iter().position(|c| *c == table.column).unwrap_or(0);
+        // a record lists the fields from top to bottom, so the directions are swapped
+        let action = match (table.record, action) {
+            (true, Action::ResultUp) => Action::ResultLeft,
+            (true, Action::ResultDown) => Action::ResultRight,
+            (true, Action::ResultLeft) => Action::ResultUp,
+            (true, Action::ResultRight) => Action::ResultDown,
+            _ => action,
+        };
+        match action {
+            Action::ResultUp => table.row = table.row.saturating_sub(1),
+            Action::ResultDown => table.row = (table.row + 1).min(last_row),
+            Action::ResultLeft => {
+                if let Some(column) = columns.get(position.saturating_sub(1)) {
+                    table.column = *column;
+                }
+            }
+            Action::ResultRight => {
+                if let Some(column) = columns.get(position + 1) {
+                    table.column = *column;
+                }
+            }
+            Action::ResultPageUp => table.row = table.row.saturating_sub(RESULT_PAGE),
+            Action::ResultPageDown => table.row = (table.row + RESULT_PAGE).min(last_row),
+            Action::ResultTop => table.row = 0,
+            Action::ResultBottom => table.row = last_row,
             _ => {}
         }
+    }
+
+    fn handle_result_search_keys(&self, state: &mut State, key: KeyEvent) -> Result<()> {
+        let format = &state.config.format;
+        let Some(cell) = state.cells.current_mut() else {
+            return Ok(());
+        };
+        let columns = cell.shown_columns();
+        let batches = match &cell.table.arranged {
+            Some(batch) => std::slice::from_ref(batch),
+            None => cell
+                .result
+                .as_ref()
+                .map_or(&[][..], |r| r.batches.as_slice()),
+        };
+        let Some(search) = cell.table.search.as_mut() else {
+            return Ok(());
+        };
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let changed = match key.code {
+            KeyCode::Esc => {
+                cell.table.search = None;
+                return Ok(());
+            }
+            KeyCode::Enter => {
+                search.editing = false;
+                return Ok(());
+            }
+            KeyCode::Char('r') if ctrl => {
+                search.regex = !search.regex;
+                true
+            }
+            _ => search.query.input(key),
+        };
+        if changed {
+            search.update(batches, &columns, format);
+            // incremental: the first match from where the search started
+            let found = search.matches.iter().find(|m| m.0 >= search.origin);
+            if let Some((row, column)) = found.or(search.matches.first()).copied() {
+                cell.table.row = row;
+                cell.table.column = column;
+            }
+        }
         Ok(())
     }
 
-    fn handle_edit_messages(&self, state: &mut State, key: KeyEvent) -> Result<()> {
-        if key.code == KeyCode::Char('x') && key.modifiers.contains(KeyModifiers::CONTROL) {
-            self.handle(state, Message::Cells(CellsMessage::SaveCurrent))?;
-            self.handle(state, Message::Cells(CellsMessage::ExecuteCurrent))?;
-        } else if key.code == KeyCode::Esc {
+    /// Applies the typed filter with Enter, an empty one clears it
+    fn handle_filter_keys(&self, state: &mut State, key: KeyEvent) -> Result<()> {
+        let Some(cell) = state.cells.current_mut() else {
+            return Ok(());
+        };
+        let Some(editor) = cell.table.filter_editor.as_mut() else {
+            return Ok(());
+        };
+        match key.code {
+            KeyCode::Esc => cell.table.filter_editor = None,
+            KeyCode::Enter => {
+                let text = editor.lines().join("");
+                let filter = match text.parse::<Filter>() {
+                    Ok(filter) => Some(filter).filter(|f| !f.is_empty()),
+                    Err(err) => {
+                        state.message = Some(format!("Invalid filter: {}", err));
+                        return Ok(());
+                    }
+                };
+                let previous = std::mem::replace(&mut cell.table.filter, filter);
+                if let Err(err) = Self::arrange_result(cell, &state.config.format) {
+                    // the previous filter stays, the text can be corrected
+                    cell.table.filter = previous;
+                    Self::arrange_result(cell, &state.config.format)?;
+                    state.message = Some(format!("Invalid filter: {}", err));
+                    return Ok(());
+                }
+                cell.table.filter_text = text;
+                cell.table.filter_editor = None;
+            }
+            _ => {
+                editor.input(key);
+            }
+        }
+        Ok(())
+    }
+
+    /// Cycles the chart kind or one of its columns, y only through numeric columns
+    fn change_chart(cell: &mut Cell, action: Action) {
+        let Some(schema) = cell.result.as_ref().map(|r| r.schema.clone()) else {
+            return;
+        };
+        let Some(chart) = cell.chart.as_mut() else {
+            return;
+        };
+        let columns = schema.fields().len();
+        match action {
+            Action::ChartKind => chart.kind = chart.kind.next(),
+            Action::ChartX => chart.x = (chart.x + 1) % columns,
+            Action::ChartY => {
+                if let Some(y) = (1..=columns)
+                    .map(|step| (chart.y + step) % columns)
+                    .find(|i| schema.field(*i).data_type().is_numeric())
+                {
+                    chart.y = y;
+                }
+            }
+            Action::ChartSeries => {
+                let next = chart.series.map_or(0, |series| series + 1);
+                chart.series = (next..columns).find(|i| *i != chart.x && *i != chart.y);
+            }
+            _ => {}
+        }
+    }
+
+    fn navigate_plan(view: &mut PlanView, action: Action) {
+        let visible = view.visible_nodes().len();
+        match action {
+            Action::PlanSwitchTab => view.switch_tab(),
+            Action::PlanUp => {
+                view.selected = view.selected.saturating_sub(1);
+            }
+            Action::PlanDown if view.selected + 1 < visible => {
+                view.selected += 1;
+            }
+            Action::PlanToggle => {
+                if let Some((path, _)) = view.selected_node() {
+                    let collapsed = view.is_collapsed(&path);
+                    view.set_collapsed(path, !collapsed);
+                }
+            }
+            Action::PlanExpand => {
+                if let Some((path, _)) = view.selected_node() {
+                    view.set_collapsed(path, false);
+                }
+            }
+            Action::PlanCollapse => {
+                if let Some((path, node)) = view.selected_node() {
+                    if !node.children.is_empty() && !view.is_collapsed(&path) {
+                        view.set_collapsed(path, true);
+                    } else if let Some((_, parent)) = path.split_last() {
+                        // jump to the parent node
+                        let parent = parent.to_vec();
+                        if let Some(index) =
+                            view.visible_nodes().iter().position(|(p, _)| *p == parent)
+                        {
+                            view.selected = index;
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_palette_keys(&self, state: &mut State, key: KeyEvent) -> Result<()> {
+        if key.code == KeyCode::Enter {
+            let Some(palette) = state.palette.take() else {
+                return Ok(());
+            };
+            let entries = palette.entries(state);
+            let item = entries.get(palette.selected).map(|entry| entry.item);
+            match item {
+                Some(PaletteItem::Cell(id)) => {
+                    self.handle(state, Message::Cells(CellsMessage::SaveCurrent))?;
+                    self.switch_cell(state, id);
+                }
+                Some(PaletteItem::Action(action)) => self.perform(state, action)?,
+                None => {}
+            }
+            return Ok(());
+        }
+
+        let count = state.palette.as_ref().map_or(0, |p| p.entries(state).len());
+        let Some(palette) = state.palette.as_mut() else {
+            return Ok(());
+        };
+        let last = count.saturating_sub(1);
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => state.palette = None,
+            KeyCode::Up => palette.selected = palette.selected.saturating_sub(1),
+            KeyCode::Char('p') if ctrl => palette.selected = palette.selected.saturating_sub(1),
+            KeyCode::Down => palette.selected = (palette.selected + 1).min(last),
+            KeyCode::Char('n') if ctrl => palette.selected = (palette.selected + 1).min(last),
+            _ => {
+                if palette.query.input(key) {
+                    palette.selected = 0;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Selects the match in the editor, switching to its cell
+    fn goto_match(&self, state: &mut State, m: Match) {
+        if state.cells.current_cell_id != Some(m.cell_id) {
+            self.switch_cell(state, m.cell_id);
+        }
+        let (_, row, col) = m.position;
+        state
+            .cells
+            .editor
+            .move_cursor(CursorMove::Jump(row as u16, col as u16));
+        self.highlight_matches(state);
+    }
+
+    fn highlight_matches(&self, state: &mut State) {
+        let pattern = state
+            .search
+            .as_ref()
+            .map(|s| s.pattern())
+            .unwrap_or_default();
+        let editor = &mut state.cells.editor;
+        // the pattern is escaped, so it is always valid
+        let _ = editor.set_search_pattern(pattern);
+        editor.set_search_style(state.ui.theme.search());
+    }
+
+    /// Replaces matches in their cells, recording a single change
+    fn replace_matches(&self, state: &mut State, matches: &[Match], replacement: &str) {
+        let mut changes = Vec::new();
+        for cell_id in search::cells(matches) {
+            let cell_matches = matches
+                .iter()
+                .filter(|m| m.cell_id == cell_id)
+                .copied()
+                .collect::<Vec<_>>();
+            if let Some(cell) = state.cells.all.get_mut(&cell_id) {
+                let code = cell.code.as_deref().unwrap_or_default();
+                let replaced = search::replace(code, &cell_matches, replacement);
+                let code = cell.code.replace(replaced);
+                changes.push(Change::Code { id: cell_id, code });
+            }
+        }
+        state.history.record(Change::Group(changes));
+        if let Some(cell_id) = state.cells.current_cell_id {
+            let cursor = state.cells.editor.cursor();
+            self.switch_cell(state, cell_id);
             state
                 .cells
                 .editor
-                .set_cursor_style(Style::from((Color::White, Color::Gray)));
+                .move_cursor(CursorMove::Jump(cursor.0 as u16, cursor.1 as u16));
+        }
+    }
+
+    fn handle_search_keys(&self, state: &mut State, key: KeyEvent) -> Result<()> {
+        let Some(search) = state.search.as_mut() else {
+            return Ok(());
+        };
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let matches = search.matches(&state.cells);
+        let (row, col) = state.cells.editor.cursor();
+        let cursor = (state.cells.current_cell_index().unwrap_or(0), row, col);
+        let next = matches
+            .iter()
+            .find(|m| m.position > cursor)
+            .or(matches.first())
+            .copied();
+        let previous = matches
+            .iter()
+            .rev()
+            .find(|m| m.position < cursor)
+            .or(matches.last())
+            .copied();
+        let at_cursor = matches.iter().find(|m| m.position == cursor).copied();
+
+        match key.code {
+            KeyCode::Esc => {
+                state.search = None;
+                self.highlight_matches(state);
+            }
+            KeyCode::Tab if search.replacing => {
+                search.replacement_focused = !search.replacement_focused;
+            }
+            KeyCode::Down => next.into_iter().for_each(|m| self.goto_match(state, m)),
+            KeyCode::Char('n') if ctrl => next.into_iter().for_each(|m| self.goto_match(state, m)),
+            KeyCode::Up => previous.into_iter().for_each(|m| self.goto_match(state, m)),
+            KeyCode::Char('p') if ctrl => {
+                previous.into_iter().for_each(|m| self.goto_match(state, m))
+            }
+            KeyCode::Char('a') if ctrl && search.replacement_focused => {
+                if matches.is_empty() {
+                    state.message = Some("No matches".to_string());
+                    return Ok(());
+                }
+                let replacement = search.replacement_text();
+                self.replace_matches(state, &matches, &replacement);
+                let cells = search::cells(&matches).len();
+                state.message = Some(format!(
+                    "Replaced {} matches in {} cells",
+                    matches.len(),
+                    cells
+                ));
+                self.highlight_matches(state);
+            }
+            KeyCode::Enter if search.replacement_focused => match at_cursor {
+                Some(m) => {
+                    let replacement = search.replacement_text();
+                    self.replace_matches(state, &[m], &replacement);
+                    let (index, row, col) = m.position;
+                    let after = (index, row, col + replacement.chars().count());
+                    let matches = state
+                        .search
+                        .as_ref()
+                        .map(|s| s.matches(&state.cells))
+                        .unwrap_or_default();
+                    match matches
+                        .iter()
+                        .find(|m| m.position >= after)
+                        .or(matches.first())
+                    {
+                        Some(m) => self.goto_match(state, *m),
+                        None => self.highlight_matches(state),
+                    }
+                }
+                None => next.into_iter().for_each(|m| self.goto_match(state, m)),
+            },
+            KeyCode::Enter => next.into_iter().for_each(|m| self.goto_match(state, m)),
+            _ if search.replacement_focused => {
+                search.replacement.input(key);
+            }
+            _ => {
+                if search.query.input(key) {
+                    // incremental: the first match from where the search started
+                    let origin = search.origin;
+                    let matches = search.matches(&state.cells);
+                    match matches
+                        .iter()
+                        .find(|m| m.position >= origin)
+                        .or(matches.first())
+                    {
+                        Some(m) => self.goto_match(state, *m),
+                        None => self.highlight_matches(state),
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_navigate_messages(&self, state: &mut State, key: KeyEvent) -> Result<()> {
+        if let Some(action) = state.ui.keymap.action(KeyContext::Navigate, &key) {
+            self.perform(state, action)?;
+        }
+        Ok(())
+    }
+
+    fn handle_edit_messages(&self, state: &mut State, key: KeyEvent) -> Result<()> {
+        let action = state.ui.keymap.action(KeyContext::Edit, &key);
+        if !state.ui.vim {
+            match action {
+                Some(action) => self.perform(state, action)?,
+                None => {
+                    state.cells.editor.input(key);
+                }
+            }
+            return Ok(());
+        }
+
+        // Esc belongs to vim, leaving the cell is an explicit command like `:q`
+        match action.filter(|action| *action != Action::LeaveEditor) {
+            Some(action) => self.perform(state, action)?,
+            None => match state.vim.input(&mut state.cells.editor, key) {
+                Some(VimRequest::Save) => {
+                    self.handle(state, Message::Cells(CellsMessage::SaveCurrent))?
+                }
+                Some(VimRequest::Leave) => self.perform(state, Action::LeaveEditor)?,
+                Some(VimRequest::Discard) => {
+                    if let Some(cell_id) = state.cells.current_cell_id {
+                        self.switch_cell(state, cell_id);
+                    }
+                    state
+                        .cells
+                        .editor
+                        .set_cursor_style(state.ui.theme.cursor_inactive());
+                    state.mode = Mode::Navigate;
+                }
+                None => {}
+            },
+        }
+        Ok(())
+    }
+
+    fn handle_plan_messages(&self, state: &mut State, key: KeyEvent) -> Result<()> {
+        if state.cells.current().is_none_or(|c| c.plan.is_none()) {
+            state.mode = Mode::Navigate;
+            return Ok(());
+        }
+        if let Some(action) = state.ui.keymap.action(KeyContext::Plan, &key) {
+            self.perform(state, action)?;
+        }
+        Ok(())
+    }
+
+    fn handle_chart_messages(&self, state: &mut State, key: KeyEvent) -> Result<()> {
+        if state.cells.current().is_none_or(|c| c.chart.is_none()) {
+            state.mode = Mode::Navigate;
+            return Ok(());
+        }
+        if let Some(action) = state.ui.keymap.action(KeyContext::Chart, &key) {
+            self.perform(state, action)?;
+        }
+        Ok(())
+    }
+
+    fn handle_result_messages(&self, state: &mut State, key: KeyEvent) -> Result<()> {
+        let Some(cell) = state.cells.current() else {
+            state.mode = Mode::Navigate;
+            return Ok(());
+        };
+        if cell.result.is_none() {
             state.mode = Mode::Navigate;
-            self.handle(state, Message::Cells(CellsMessage::SaveCurrent))?;
-        } else {
-            state.cells.editor.input(key);
+            return Ok(());
+        }
+        if cell.table.filter_editor.is_some() {
+            return self.handle_filter_keys(state, key);
+        }
+        if cell.table.search.as_ref().is_some_and(|s| s.editing) {
+            return self.handle_result_search_keys(state, key);
+        }
+        if let Some(action) = state.ui.keymap.action(KeyContext::Result, &key) {
+            self.perform(state, action)?;
+        }
+        Ok(())
+    }
+
+    fn handle_diff_messages(&self, state: &mut State, key: KeyEvent) -> Result<()> {
+        let Some(view) = state.cells.current_mut().and_then(|c| c.diff.as_mut()) else {
+            state.mode = Mode::Navigate;
+            return Ok(());
+        };
+        let Some(editor) = view.key_editor.as_mut() else {
+            if let Some(action) = state.ui.keymap.action(KeyContext::Diff, &key) {
+                self.perform(state, action)?;
+            }
+            return Ok(());
+        };
+        match key.code {
+            KeyCode::Esc => view.key_editor = None,
+            KeyCode::Enter => {
+                let text = editor.lines().join("");
+                let previous = std::mem::replace(&mut view.key_text, text);
+                view.update();
+                if let Err(err) = &view.diff {
+                    // the previous key stays, the text can be corrected
+                    state.message = Some(format!("Invalid key: {}", err));
+                    view.key_text = previous;
+                    view.update();
+                    return Ok(());
+                }
+                view.key_editor = None;
+            }
+            _ => {
+                editor.input(key);
+            }
+        }
+        Ok(())
+    }
+
+    /// Name of the cell, or its number when it has none
+    fn cell_label(state: &State, id: Uuid) -> String {
+        let name = state.cells.all.get(&id).and_then(|c| c.name());
+        match (name, state.cells.order.iter().position(|i| *i == id)) {
+            (Some(name), _) => name.to_string(),
+            (None, Some(i)) => format!("cell {}", i + 1),
+            (None, None) => "cell".to_string(),
+        }
+    }
+
+    fn handle_history_messages(&self, state: &mut State, key: KeyEvent) -> Result<()> {
+        if state.cells.current().is_none_or(|c| c.runs.is_empty()) {
+            state.mode = Mode::Navigate;
+            return Ok(());
+        }
+        if let Some(action) = state.ui.keymap.action(KeyContext::History, &key) {
+            self.perform(state, action)?;
+        }
+        Ok(())
+    }
+
+    fn handle_profile_messages(&self, state: &mut State, key: KeyEvent) -> Result<()> {
+        if state.cells.current().is_none_or(|c| c.profile.is_none()) {
+            state.mode = Mode::Navigate;
+            return Ok(());
+        }
+        if let Some(action) = state.ui.keymap.action(KeyContext::Profile, &key) {
+            self.perform(state, action)?;
         }
         Ok(())
     }
 
     pub fn handle(&self, state: &mut State, msg: Message) -> Result<()> {
-        state.show_help = false; // make sure help is hidden immediately on any action
+        if msg != Message::Tick {
+            state.show_help = false; // make sure help is hidden immediately on any action
+        }
+        if matches!(msg, Message::KeyPressed(_)) {
+            state.message = None;
+        }
         match msg {
+            Message::Tick => {
+                state.tick = state.tick.wrapping_add(1);
+            }
             Message::ConfirmQuit => {
                 state.popup = Some(ConfirmDialog {
                     message: Message::Quit,
@@ -226,13 +1731,28 @@ impl Handler {
                 state.quit = true;
             }
             Message::Cells(cells_msg) => self.handle_cell_messages(state, cells_msg)?,
+            Message::Settings(settings_msg) => {
+                self.handle_settings_messages(state, settings_msg)?
+            }
             Message::KeyPressed(key) => {
                 if state.popup.is_some() {
                     self.handle_popup_messages(state, key)?;
+                } else if state.settings.is_some() {
+                    self.handle_settings_keys(state, key)?;
+                } else if state.palette.is_some() {
+                    self.handle_palette_keys(state, key)?;
+                } else if state.search.is_some() {
+                    self.handle_search_keys(state, key)?;
                 } else {
                     match state.mode {
                         Mode::Navigate => self.handle_navigate_messages(state, key)?,
                         Mode::EditCell => self.handle_edit_messages(state, key)?,
+                        Mode::Plan => self.handle_plan_messages(state, key)?,
+                        Mode::Chart => self.handle_chart_messages(state, key)?,
+                        Mode::Profile => self.handle_profile_messages(state, key)?,
+                        Mode::Result => self.handle_result_messages(state, key)?,
+                        Mode::Diff => self.handle_diff_messages(state, key)?,
+                        Mode::History => self.handle_history_messages(state, key)?,
                     }
                 }
             }
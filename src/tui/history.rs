@@ -0,0 +1,120 @@
+use crate::core::notebook::CellKind;
+use crate::tui::state::{Cell, Cells};
+use uuid::Uuid;
+
+/// Changes kept for undo
+const HISTORY_LIMIT: usize = 100;
+
+/// A structural change of the notebook, reverting it yields the change which redoes it
+#[derive(Debug)]
+pub enum Change {
+    /// The cell was inserted at the index
+    Insert { index: usize, id: Uuid },
+    /// The cell was removed from the index, kept with its result
+    Remove { index: usize, cell: Box<Cell> },
+    /// A cell was moved between positions in the order
+    Move { from: usize, to: usize },
+    /// Code of the cell before the change
+    Code { id: Uuid, code: Option<String> },
+    /// Kind of the cell before the change
+    Kind { id: Uuid, kind: CellKind },
+    /// Changes made by a single command, e.g. a split
+    Group(Vec<Change>),
+}
+
+impl Change {
+    /// Reverts the change, returns its inverse and the cell to select
+    fn revert(self, cells: &mut Cells) -> (Change, Option<Uuid>) {
+        match self {
+            Change::Insert { index, id } => {
+                cells.order.retain(|item| *item != id);
+                // cells are inserted after the selected one, which is selected again
+                let focus = index
+                    .checked_sub(1)
+                    .and_then(|i| cells.order.get(i))
+                    .or_else(|| cells.order.get(index))
+                    .copied();
+                let inverse = match cells.all.remove(&id) {
+                    Some(cell) => Change::Remove {
+                        index,
+                        cell: Box::new(cell),
+                    },
+                    None => Change::Group(Vec::new()),
+                };
+                (inverse, focus)
+            }
+            Change::Remove { index, cell } => {
+                let id = cell.id;
+                cells.order.insert(index.min(cells.order.len()), id);
+                cells.all.insert(id, *cell);
+                (Change::Insert { index, id }, Some(id))
+            }
+            Change::Move { from, to } => {
+                let id = cells.order.remove(to);
+                cells.order.insert(from, id);
+                (Change::Move { from: to, to: from }, Some(id))
+            }
+            Change::Code { id, code } => {
+                let previous = cells
+                    .all
+                    .get_mut(&id)
+                    .and_then(|cell| std::mem::replace(&mut cell.code, code));
+                (Change::Code { id, code: previous }, Some(id))
+            }
+            Change::Kind { id, kind } => {
+                let previous = cells
+                    .all
+                    .get_mut(&id)
+                    .map_or(kind, |cell| std::mem::replace(&mut cell.kind, kind));
+                (Change::Kind { id, kind: previous }, Some(id))
+            }
+            Change::Group(changes) => {
+                let mut focus = None;
+                let mut inverse = Vec::new();
+                for change in changes.into_iter().rev() {
+                    let (change, cell) = change.revert(cells);
+                    inverse.push(change);
+                    focus = cell.or(focus);
+                }
+                inverse.reverse();
+                (Change::Group(inverse), focus)
+            }
+        }
+    }
+}
+
+/// Undo and redo stacks of notebook changes
+#[derive(Debug, Default)]
+pub struct History {
+    undo: Vec<Change>,
+    redo: Vec<Change>,
+}
+
+impl History {
+    /// Remembers a change made by the user, which makes earlier undone changes unavailable
+    pub fn record(&mut self, change: Change) {
+        if matches!(&change, Change::Group(changes) if changes.is_empty()) {
+            return;
+        }
+        self.undo.push(change);
+        if self.undo.len() > HISTORY_LIMIT {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+
+    /// Reverts the last change, `None` if there is nothing to undo.
+    /// Otherwise returns the cell to select, if any is left.
+    pub fn undo(&mut self, cells: &mut Cells) -> Option<Option<Uuid>> {
+        let (inverse, focus) = self.undo.pop()?.revert(cells);
+        self.redo.push(inverse);
+        Some(focus)
+    }
+
+    /// Applies the last undone change again, like `undo`
+    pub fn redo(&mut self, cells: &mut Cells) -> Option<Option<Uuid>> {
+        let (inverse, focus) = self.redo.pop()?.revert(cells);
+        self.undo.push(inverse);
+        Some(focus)
+    }
+}
@@ -0,0 +1,1079 @@
+use anyhow::{anyhow, bail, Error, Result};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::str::FromStr;
+
+/// Where an action is available
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyContext {
+    Navigate,
+    Edit,
+    Plan,
+    Chart,
+    Profile,
+    Result,
+    Diff,
+    History,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Help,
+    Quit,
+    Execute,
+    Create,
+    Delete,
+    Edit,
+    EditExternally,
+    MoveUp,
+    MoveDown,
+    Duplicate,
+    Merge,
+    Split,
+    Undo,
+    Redo,
+    ToggleSidebar,
+    ToggleKind,
+    Palette,
+    Find,
+    Replace,
+    Previous,
+    Next,
+    Explain,
+    ExplainAnalyze,
+    FocusPlan,
+    Settings,
+    SaveAndExecute,
+    Format,
+    LeaveEditor,
+    PlanUp,
+    PlanDown,
+    PlanToggle,
+    PlanExpand,
+    PlanCollapse,
+    PlanSwitchTab,
+    PlanClose,
+    Chart,
+    ChartKind,
+    ChartX,
+    ChartY,
+    ChartSeries,
+    ChartTable,
+    ChartClose,
+    Profile,
+    ProfileUp,
+    ProfileDown,
+    ProfileClose,
+    Compare,
+    CompareBase,
+    DiffUp,
+    DiffDown,
+    DiffPageUp,
+    DiffPageDown,
+    DiffKey,
+    DiffClose,
+    History,
+    HistoryUp,
+    HistoryDown,
+    HistoryRestore,
+    HistoryCompare,
+    HistoryClose,
+    FocusResult,
+    ResultUp,
+    ResultDown,
+    ResultLeft,
+    ResultRight,
+    ResultPageUp,
+    ResultPageDown,
+    ResultTop,
+    ResultBottom,
+    ResultSort,
+    ResultClearSort,
+    ResultFilter,
+    ResultClearFilter,
+    ResultSearch,
+    ResultNextMatch,
+    ResultPreviousMatch,
+    ResultRecord,
+    ResultHideColumn,
+    ResultShowColumns,
+    ResultMoveLeft,
+    ResultMoveRight,
+    ResultFreeze,
+    ResultClose,
+}
+
+pub struct ActionInfo {
+    pub action: Action,
+    /// Used in the `[keymap]` section of the config
+    pub name: &'static str,
+    pub description: &'static str,
+    pub context: KeyContext,
+    pub defaults: &'static [KeyBinding],
+}
+
+const fn key(c: char) -> KeyBinding {
+    KeyBinding::new(KeyCode::Char(c), KeyModifiers::NONE)
+}
+
+const fn code(code: KeyCode) -> KeyBinding {
+    KeyBinding::new(code, KeyModifiers::NONE)
+}
+
+const fn ctrl(c: char) -> KeyBinding {
+    KeyBinding::new(KeyCode::Char(c), KeyModifiers::CONTROL)
+}
+
+const fn alt(c: char) -> KeyBinding {
+    KeyBinding::new(KeyCode::Char(c), KeyModifiers::ALT)
+}
+
+/// All actions which can be bound to keys, in the order they are listed in help
+pub const ACTIONS: &[ActionInfo] = &[
+    ActionInfo {
+        action: Action::Create,
+        name: "create",
+        description: "create new cell",
+        context: KeyContext::Navigate,
+        defaults: &[key('n')],
+    },
+    ActionInfo {
+        action: Action::Delete,
+        name: "delete",
+        description: "delete selected cell",
+        context: KeyContext::Navigate,
+        defaults: &[key('d')],
+    },
+    ActionInfo {
+        action: Action::Previous,
+        name: "previous",
+        description: "select previous cell",
+        context: KeyContext::Navigate,
+        defaults: &[code(KeyCode::Up), key('k')],
+    },
+    ActionInfo {
+        action: Action::Next,
+        name: "next",
+        description: "select next cell",
+        context: KeyContext::Navigate,
+        defaults: &[code(KeyCode::Down), key('j')],
+    },
+    ActionInfo {
+        action: Action::MoveUp,
+        name: "move_up",
+        description: "move selected cell up",
+        context: KeyContext::Navigate,
+        defaults: &[KeyBinding::new(KeyCode::Char('K'), KeyModifiers::SHIFT)],
+    },
+    ActionInfo {
+        action: Action::MoveDown,
+        name: "move_down",
+        description: "move selected cell down",
+        context: KeyContext::Navigate,
+        defaults: &[KeyBinding::new(KeyCode::Char('J'), KeyModifiers::SHIFT)],
+    },
+    ActionInfo {
+        action: Action::Duplicate,
+        name: "duplicate",
+        description: "duplicate selected cell",
+        context: KeyContext::Navigate,
+        defaults: &[key('c')],
+    },
+    ActionInfo {
+        action: Action::Merge,
+        name: "merge",
+        description: "merge selected cell with the next one",
+        context: KeyContext::Navigate,
+        defaults: &[key('m')],
+    },
+    ActionInfo {
+        action: Action::ToggleKind,
+        name: "toggle_kind",
+        description: "switch selected cell between SQL and Markdown",
+        context: KeyContext::Navigate,
+        defaults: &[KeyBinding::new(KeyCode::Char('M'), KeyModifiers::SHIFT)],
+    },
+    ActionInfo {
+        action: Action::Undo,
+        name: "undo",
+        description: "undo last change of the notebook",
+        context: KeyContext::Navigate,
+        defaults: &[key('u')],
+    },
+    ActionInfo {
+        action: Action::Redo,
+        name: "redo",
+        description: "redo undone change of the notebook",
+        context: KeyContext::Navigate,
+        defaults: &[ctrl('r')],
+    },
+    ActionInfo {
+        action: Action::Palette,
+        name: "palette",
+        description: "jump to a cell or run a command",
+        context: KeyContext::Navigate,
+        defaults: &[key(':'), ctrl('p')],
+    },
+    ActionInfo {
+        action: Action::Find,
+        name: "find",
+        description: "find in all cells",
+        context: KeyContext::Navigate,
+        defaults: &[key('/')],
+    },
+    ActionInfo {
+        action: Action::Replace,
+        name: "replace",
+        description: "find and replace in all cells",
+        context: KeyContext::Navigate,
+        defaults: &[key('r')],
+    },
+    ActionInfo {
+        action: Action::ToggleSidebar,
+        name: "toggle_sidebar",
+        description: "show or hide the list of cells",
+        context: KeyContext::Navigate,
+        defaults: &[key('b')],
+    },
+    ActionInfo {
+        action: Action::Edit,
+        name: "edit",
+        description: "edit selected cell",
+        context: KeyContext::Navigate,
+        defaults: &[code(KeyCode::Left), key('h'), code(KeyCode::Enter)],
+    },
+    ActionInfo {
+        action: Action::EditExternally,
+        name: "edit_externally",
+        description: "edit selected cell in $VISUAL or $EDITOR",
+        context: KeyContext::Navigate,
+        defaults: &[key('e')],
+    },
+    ActionInfo {
+        action: Action::Execute,
+        name: "execute",
+        description: "execute selected cell",
+        context: KeyContext::Navigate,
+        defaults: &[key('x')],
+    },
+    ActionInfo {
+        action: Action::Explain,
+        name: "explain",
+        description: "show query plan of selected cell",
+        context: KeyContext::Navigate,
+        defaults: &[key('p')],
+    },
+    ActionInfo {
+        action: Action::ExplainAnalyze,
+        name: "explain_analyze",
+        description: "run EXPLAIN ANALYZE for selected cell",
+        context: KeyContext::Navigate,
+        defaults: &[KeyBinding::new(KeyCode::Char('P'), KeyModifiers::SHIFT)],
+    },
+    ActionInfo {
+        action: Action::FocusPlan,
+        name: "focus_plan",
+        description: "focus query plan",
+        context: KeyContext::Navigate,
+        defaults: &[code(KeyCode::Tab)],
+    },
+    ActionInfo {
+        action: Action::FocusResult,
+        name: "focus_result",
+        description: "focus result table",
+        context: KeyContext::Navigate,
+        defaults: &[key('v')],
+    },
+    ActionInfo {
+        action: Action::Chart,
+        name: "chart",
+        description: "show result as a chart",
+        context: KeyContext::Navigate,
+        defaults: &[key('g')],
+    },
+    ActionInfo {
+        action: Action::Profile,
+        name: "profile",
+        description: "profile columns of the result",
+        context: KeyContext::Navigate,
+        defaults: &[key('i')],
+    },
+    ActionInfo {
+        action: Action::Compare,
+        name: "compare",
+        description: "compare result with the marked cell or the previous run",
+        context: KeyContext::Navigate,
+        defaults: &[KeyBinding::new(KeyCode::Char('D'), KeyModifiers::SHIFT)],
+    },
+    ActionInfo {
+        action: Action::CompareBase,
+        name: "compare_base",
+        description: "mark cell as the base of comparisons",
+        context: KeyContext::Navigate,
+        defaults: &[KeyBinding::new(KeyCode::Char('B'), KeyModifiers::SHIFT)],
+    },
+    ActionInfo {
+        action: Action::History,
+        name: "history",
+        description: "browse previous runs",
+        context: KeyContext::Navigate,
+        defaults: &[KeyBinding::new(KeyCode::Char('H'), KeyModifiers::SHIFT)],
+    },
+    ActionInfo {
+        action: Action::Settings,
+        name: "settings",
+        description: "session settings",
+        context: KeyContext::Navigate,
+        defaults: &[key('s')],
+    },
+    ActionInfo {
+        action: Action::Quit,
+        name: "quit",
+        description: "quit",
+        context: KeyContext::Navigate,
+        defaults: &[key('q')],
+    },
+    ActionInfo {
+        action: Action::Help,
+        name: "help",
+        description: "show this help",
+        context: KeyContext::Navigate,
+        defaults: &[key('?'), code(KeyCode::F(1))],
+    },
+    ActionInfo {
+        action: Action::SaveAndExecute,
+        name: "save_and_execute",
+        description: "save and execute cell",
+        context: KeyContext::Edit,
+        defaults: &[ctrl('x')],
+    },
+    ActionInfo {
+        action: Action::Split,
+        name: "split",
+        description: "split cell at the cursor",
+        context: KeyContext::Edit,
+        defaults: &[alt('s')],
+    },
+    ActionInfo {
+        action: Action::Format,
+        name: "format",
+        description: "format SQL of the cell",
+        context: KeyContext::Edit,
+        defaults: &[ctrl('l')],
+    },
+    ActionInfo {
+        action: Action::LeaveEditor,
+        name: "leave_editor",
+        description: "save cell and back to navigation",
+        context: KeyContext::Edit,
+        defaults: &[code(KeyCode::Esc)],
+    },
+    ActionInfo {
+        action: Action::PlanUp,
+        name: "plan_up",
+        description: "select previous plan node",
+        context: KeyContext::Plan,
+        defaults: &[code(KeyCode::Up), key('k')],
+    },
+    ActionInfo {
+        action: Action::PlanDown,
+        name: "plan_down",
+        description: "select next plan node",
+        context: KeyContext::Plan,
+        defaults: &[code(KeyCode::Down), key('j')],
+    },
+    ActionInfo {
+        action: Action::PlanToggle,
+        name: "plan_toggle",
+        description: "collapse or expand plan node",
+        context: KeyContext::Plan,
+        defaults: &[code(KeyCode::Enter), key(' ')],
+    },
+    ActionInfo {
+        action: Action::PlanExpand,
+        name: "plan_expand",
+        description: "expand plan node",
+        context: KeyContext::Plan,
+        defaults: &[code(KeyCode::Right), key('l')],
+    },
+    ActionInfo {
+        action: Action::PlanCollapse,
+        name: "plan_collapse",
+        description: "collapse plan node or select parent",
+        context: KeyContext::Plan,
+        defaults: &[code(KeyCode::Left), key('h')],
+    },
+    ActionInfo {
+        action: Action::PlanSwitchTab,
+        name: "plan_switch_tab",
+        description: "switch logical and physical plan",
+        context: KeyContext::Plan,
+        defaults: &[code(KeyCode::Tab)],
+    },
+    ActionInfo {
+        action: Action::PlanClose,
+        name: "plan_close",
+        description: "back to navigation",
+        context: KeyContext::Plan,
+        defaults: &[code(KeyCode::Esc), key('q')],
+    },
+    ActionInfo {
+        action: Action::ChartKind,
+        name: "chart_kind",
+        description: "switch bar, line and scatter chart",
+        context: KeyContext::Chart,
+        defaults: &[key('t')],
+    },
+    ActionInfo {
+        action: Action::ChartX,
+        name: "chart_x",
+        description: "next column for the x axis",
+        context: KeyContext::Chart,
+        defaults: &[key('x')],
+    },
+    ActionInfo {
+        action: Action::ChartY,
+        name: "chart_y",
+        description: "next numeric column for the y axis",
+        context: KeyContext::Chart,
+        defaults: &[key('y')],
+    },
+    ActionInfo {
+        action: Action::ChartSeries,
+        name: "chart_series",
+        description: "next column splitting series",
+        context: KeyContext::Chart,
+        defaults: &[key('s')],
+    },
+    ActionInfo {
+        action: Action::ChartTable,
+        name: "chart_table",
+        description: "back to the table",
+        context: KeyContext::Chart,
+        defaults: &[key('g')],
+    },
+    ActionInfo {
+        action: Action::ChartClose,
+        name: "chart_close",
+        description: "back to navigation",
+        context: KeyContext::Chart,
+        defaults: &[code(KeyCode::Esc), key('q')],
+    },
+    ActionInfo {
+        action: Action::ResultUp,
+        name: "result_up",
+        description: "select previous row",
+        context: KeyContext::Result,
+        defaults: &[code(KeyCode::Up), key('k')],
+    },
+    ActionInfo {
+        action: Action::ResultDown,
+        name: "result_down",
+        description: "select next row",
+        context: KeyContext::Result,
+        defaults: &[code(KeyCode::Down), key('j')],
+    },
+    ActionInfo {
+        action: Action::ResultLeft,
+        name: "result_left",
+        description: "select previous column",
+        context: KeyContext::Result,
+        defaults: &[code(KeyCode::Left), key('h')],
+    },
+    ActionInfo {
+        action: Action::ResultRight,
+        name: "result_right",
+        description: "select next column",
+        context: KeyContext::Result,
+        defaults: &[code(KeyCode::Right), key('l')],
+    },
+    ActionInfo {
+        action: Action::ResultPageUp,
+        name: "result_page_up",
+        description: "scroll a page up",
+        context: KeyContext::Result,
+        defaults: &[code(KeyCode::PageUp), ctrl('u')],
+    },
+    ActionInfo {
+        action: Action::ResultPageDown,
+        name: "result_page_down",
+        description: "scroll a page down",
+        context: KeyContext::Result,
+        defaults: &[code(KeyCode::PageDown), ctrl('d')],
+    },
+    ActionInfo {
+        action: Action::ResultTop,
+        name: "result_top",
+        description: "select first row",
+        context: KeyContext::Result,
+        defaults: &[code(KeyCode::Home), key('g')],
+    },
+    ActionInfo {
+        action: Action::ResultBottom,
+        name: "result_bottom",
+        description: "select last row",
+        context: KeyContext::Result,
+        defaults: &[
+            code(KeyCode::End),
+            KeyBinding::new(KeyCode::Char('G'), KeyModifiers::SHIFT),
+        ],
+    },
+    ActionInfo {
+        action: Action::ResultSort,
+        name: "result_sort",
+        description: "sort by column: ascending, descending, off",
+        context: KeyContext::Result,
+        defaults: &[key('s')],
+    },
+    ActionInfo {
+        action: Action::ResultClearSort,
+        name: "result_clear_sort",
+        description: "clear sorting",
+        context: KeyContext::Result,
+        defaults: &[KeyBinding::new(KeyCode::Char('S'), KeyModifiers::SHIFT)],
+    },
+    ActionInfo {
+        action: Action::ResultFilter,
+        name: "result_filter",
+        description: "filter rows",
+        context: KeyContext::Result,
+        defaults: &[key('f')],
+    },
+    ActionInfo {
+        action: Action::ResultClearFilter,
+        name: "result_clear_filter",
+        description: "clear filter",
+        context: KeyContext::Result,
+        defaults: &[KeyBinding::new(KeyCode::Char('F'), KeyModifiers::SHIFT)],
+    },
+    ActionInfo {
+        action: Action::ResultSearch,
+        name: "result_search",
+        description: "search values",
+        context: KeyContext::Result,
+        defaults: &[key('/')],
+    },
+    ActionInfo {
+        action: Action::ResultNextMatch,
+        name: "result_next_match",
+        description: "select next match",
+        context: KeyContext::Result,
+        defaults: &[key('n')],
+    },
+    ActionInfo {
+        action: Action::ResultPreviousMatch,
+        name: "result_previous_match",
+        description: "select previous match",
+        context: KeyContext::Result,
+        defaults: &[KeyBinding::new(KeyCode::Char('N'), KeyModifiers::SHIFT)],
+    },
+    ActionInfo {
+        action: Action::ResultHideColumn,
+        name: "result_hide_column",
+        description: "hide column",
+        context: KeyContext::Result,
+        defaults: &[key('x')],
+    },
+    ActionInfo {
+        action: Action::ResultShowColumns,
+        name: "result_show_columns",
+        description: "show hidden columns",
+        context: KeyContext::Result,
+        defaults: &[KeyBinding::new(KeyCode::Char('X'), KeyModifiers::SHIFT)],
+    },
+    ActionInfo {
+        action: Action::ResultMoveLeft,
+        name: "result_move_left",
+        description: "move column left",
+        context: KeyContext::Result,
+        defaults: &[key('<')],
+    },
+    ActionInfo {
+        action: Action::ResultMoveRight,
+        name: "result_move_right",
+        description: "move column right",
+        context: KeyContext::Result,
+        defaults: &[key('>')],
+    },
+    ActionInfo {
+        action: Action::ResultFreeze,
+        name: "result_freeze",
+        description: "freeze columns up to the selected one, again to unfreeze",
+        context: KeyContext::Result,
+        defaults: &[key('z')],
+    },
+    ActionInfo {
+        action: Action::ResultRecord,
+        name: "result_record",
+        description: "show selected row as a record",
+        context: KeyContext::Result,
+        defaults: &[code(KeyCode::Enter), key('r')],
+    },
+    ActionInfo {
+        action: Action::ResultClose,
+        name: "result_close",
+        description: "back to the table or navigation",
+        context: KeyContext::Result,
+        defaults: &[code(KeyCode::Esc), key('q')],
+    },
+    ActionInfo {
+        action: Action::ProfileUp,
+        name: "profile_up",
+        description: "select previous column",
+        context: KeyContext::Profile,
+        defaults: &[code(KeyCode::Up), key('k')],
+    },
+    ActionInfo {
+        action: Action::ProfileDown,
+        name: "profile_down",
+        description: "select next column",
+        context: KeyContext::Profile,
+        defaults: &[code(KeyCode::Down), key('j')],
+    },
+    ActionInfo {
+        action: Action::ProfileClose,
+        name: "profile_close",
+        description: "back to the result",
+        context: KeyContext::Profile,
+        defaults: &[code(KeyCode::Esc), key('q')],
+    },
+    ActionInfo {
+        action: Action::DiffUp,
+        name: "diff_up",
+        description: "select previous row",
+        context: KeyContext::Diff,
+        defaults: &[code(KeyCode::Up), key('k')],
+    },
+    ActionInfo {
+        action: Action::DiffDown,
+        name: "diff_down",
+        description: "select next row",
+        context: KeyContext::Diff,
+        defaults: &[code(KeyCode::Down), key('j')],
+    },
+    ActionInfo {
+        action: Action::DiffPageUp,
+        name: "diff_page_up",
+        description: "scroll a page up",
+        context: KeyContext::Diff,
+        defaults: &[code(KeyCode::PageUp), ctrl('u')],
+    },
+    ActionInfo {
+        action: Action::DiffPageDown,
+        name: "diff_page_down",
+        description: "scroll a page down",
+        context: KeyContext::Diff,
+        defaults: &[code(KeyCode::PageDown), ctrl('d')],
+    },
+    ActionInfo {
+        action: Action::DiffKey,
+        name: "diff_key",
+        description: "set key columns",
+        context: KeyContext::Diff,
+        defaults: &[key('e')],
+    },
+    ActionInfo {
+        action: Action::DiffClose,
+        name: "diff_close",
+        description: "back to the result",
+        context: KeyContext::Diff,
+        defaults: &[code(KeyCode::Esc), key('q')],
+    },
+    ActionInfo {
+        action: Action::HistoryUp,
+        name: "history_up",
+        description: "select newer run",
+        context: KeyContext::History,
+        defaults: &[code(KeyCode::Up), key('k')],
+    },
+    ActionInfo {
+        action: Action::HistoryDown,
+        name: "history_down",
+        description: "select older run",
+        context: KeyContext::History,
+        defaults: &[code(KeyCode::Down), key('j')],
+    },
+    ActionInfo {
+        action: Action::HistoryRestore,
+        name: "history_restore",
+        description: "restore code of the run",
+        context: KeyContext::History,
+        defaults: &[code(KeyCode::Enter), key('r')],
+    },
+    ActionInfo {
+        action: Action::HistoryCompare,
+        name: "history_compare",
+        description: "compare run with the current result",
+        context: KeyContext::History,
+        defaults: &[KeyBinding::new(KeyCode::Char('D'), KeyModifiers::SHIFT)],
+    },
+    ActionInfo {
+        action: Action::HistoryClose,
+        name: "history_close",
+        description: "back to navigation",
+        context: KeyContext::History,
+        defaults: &[code(KeyCode::Esc), key('q')],
+    },
+];
+
+impl Action {
+    pub fn info(&self) -> &'static ActionInfo {
+        ACTIONS
+            .iter()
+            .find(|info| info.action == *self)
+            .expect("every action is registered")
+    }
+}
+
+/// Active key bindings: defaults with overrides from the config applied
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: HashMap<Action, Vec<KeyBinding>>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let bindings = ACTIONS
+            .iter()
+            .map(|info| (info.action, info.defaults.to_vec()))
+            .collect();
+        Self { bindings }
+    }
+}
+
+impl Keymap {
+    /// Applies overrides (action name to bindings), an override replaces all default bindings
+    /// of the action
+    pub fn new(overrides: &BTreeMap<String, Vec<KeyBinding>>) -> Result<Self> {
+        let mut keymap = Self::default();
+        for (name, bindings) in overrides {
+            let info = ACTIONS
+                .iter()
+                .find(|info| info.name == name)
+                .ok_or_else(|| {
+                    let names = ACTIONS.iter().map(|i| i.name).collect::<Vec<_>>();
+                    anyhow!(
+                        "keymap: unknown action '{}', expected one of: {}",
+                        name,
+                        names.join(", ")
+                    )
+                })?;
+            keymap.bindings.insert(info.action, bindings.clone());
+        }
+
+        // the same key must not trigger two actions in one context
+        for (i, a) in ACTIONS.iter().enumerate() {
+            for b in ACTIONS
+                .iter()
+                .skip(i + 1)
+                .filter(|b| b.context == a.context)
+            {
+                if let Some(key) = keymap
+                    .bindings(a.action)
+                    .iter()
+                    .find(|key| keymap.bindings(b.action).contains(key))
+                {
+                    bail!(
+                        "keymap: '{}' is bound to both '{}' and '{}'",
+                        key,
+                        a.name,
+                        b.name
+                    );
+                }
+            }
+        }
+        Ok(keymap)
+    }
+
+    pub fn bindings(&self, action: Action) -> &[KeyBinding] {
+        self.bindings.get(&action).map_or(&[], |b| b.as_slice())
+    }
+
+    pub fn action(&self, context: KeyContext, key: &KeyEvent) -> Option<Action> {
+        ACTIONS
+            .iter()
+            .filter(|info| info.context == context)
+            .find(|info| self.bindings(info.action).iter().any(|b| b.matches(key)))
+            .map(|info| info.action)
+    }
+
+    /// Keys of the action joined for display, e.g. `↑, k`
+    pub fn keys(&self, action: Action) -> String {
+        self.bindings(action)
+            .iter()
+            .map(|b| b.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Help lines `keys - description` for all actions of the context
+    pub fn help(&self, context: KeyContext) -> Vec<String> {
+        help_lines(self.help_entries(context, &[]))
+    }
+
+    /// Keys and descriptions of the actions of the context except the given ones
+    pub fn help_entries(&self, context: KeyContext, except: &[Action]) -> Vec<(String, &str)> {
+        ACTIONS
+            .iter()
+            .filter(|info| info.context == context && !except.contains(&info.action))
+            .map(|info| (self.keys(info.action), info.description))
+            .filter(|(keys, _)| !keys.is_empty())
+            .collect()
+    }
+}
+
+/// Formats help entries as `keys - description` with descriptions aligned
+pub fn help_lines(entries: Vec<(String, &str)>) -> Vec<String> {
+    let width = entries
+        .iter()
+        .map(|(keys, _)| keys.chars().count())
+        .max()
+        .unwrap_or_default();
+    entries
+        .into_iter()
+        .map(|(keys, description)| {
+            let pad = width - keys.chars().count();
+            format!("{}{} - {}", keys, " ".repeat(pad), description)
+        })
+        .collect()
+}
+
+/// A key with modifiers, written in the config as e.g. `ctrl+x`, `shift+tab`, `f1` or `?`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(try_from = "String")]
+pub struct KeyBinding {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyBinding {
+    pub const fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        Self { code, modifiers }
+    }
+
+    pub fn matches(&self, key: &KeyEvent) -> bool {
+        // Shift is already reflected by the key itself, e.g. `P` or back tab
+        let modifiers = match key.code {
+            KeyCode::Char(_) | KeyCode::BackTab => key.modifiers - KeyModifiers::SHIFT,
+            _ => key.modifiers,
+        };
+        let own = match self.code {
+            KeyCode::Char(_) | KeyCode::BackTab => self.modifiers - KeyModifiers::SHIFT,
+            _ => self.modifiers,
+        };
+        self.code == key.code && own == modifiers
+    }
+}
+
+impl FromStr for KeyBinding {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut parts = value.split('+').collect::<Vec<_>>();
+        // `+` itself and combinations like `ctrl++`
+        if value.ends_with("++") || value == "+" {
+            parts.retain(|p| !p.is_empty());
+            parts.push("+");
+        }
+        let Some((key, mods)) = parts.split_last() else {
+            bail!("empty key binding");
+        };
+        for m in mods {
+            modifiers |= match m.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" | "meta" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => bail!("unknown modifier '{}' in key binding '{}'", m, value),
+            };
+        }
+
+        let code = match key.to_ascii_lowercase().as_str() {
+            "enter" | "return" => KeyCode::Enter,
+            "esc" | "escape" => KeyCode::Esc,
+            "tab" if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::BackTab,
+            "tab" => KeyCode::Tab,
+            "backtab" => KeyCode::BackTab,
+            "space" => KeyCode::Char(' '),
+            "backspace" => KeyCode::Backspace,
+            "delete" | "del" => KeyCode::Delete,
+            "insert" | "ins" => KeyCode::Insert,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            f if f.len() > 1 && f.starts_with('f') => {
+                let n = f[1..]
+                    .parse::<u8>()
+                    .map_err(|_| anyhow!("unknown key '{}' in key binding '{}'", key, value))?;
+                KeyCode::F(n)
+            }
+            _ => {
+                let mut chars = key.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => {
+                        if modifiers.contains(KeyModifiers::SHIFT) && c.is_ascii_alphabetic() {
+                            KeyCode::Char(c.to_ascii_uppercase())
+                        } else {
+                            KeyCode::Char(c)
+                        }
+                    }
+                    _ => bail!("unknown key '{}' in key binding '{}'", key, value),
+                }
+            }
+        };
+        if let KeyCode::Char(c) = code {
+            if c.is_ascii_uppercase() {
+                modifiers |= KeyModifiers::SHIFT;
+            }
+        }
+        Ok(Self { code, modifiers })
+    }
+}
+
+impl TryFrom<String> for KeyBinding {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        value.parse()
+    }
+}
+
+impl fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "Ctrl+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "Alt+")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::Enter => write!(f, "↵"),
+            KeyCode::Esc => write!(f, "Esc"),
+            KeyCode::Tab => write!(f, "Tab"),
+            KeyCode::BackTab => write!(f, "Shift+Tab"),
+            KeyCode::Backspace => write!(f, "Backspace"),
+            KeyCode::Delete => write!(f, "Del"),
+            KeyCode::Insert => write!(f, "Ins"),
+            KeyCode::Up => write!(f, "↑"),
+            KeyCode::Down => write!(f, "↓"),
+            KeyCode::Left => write!(f, "←"),
+            KeyCode::Right => write!(f, "→"),
+            KeyCode::Home => write!(f, "Home"),
+            KeyCode::End => write!(f, "End"),
+            KeyCode::PageUp => write!(f, "PgUp"),
+            KeyCode::PageDown => write!(f, "PgDn"),
+            KeyCode::F(n) => write!(f, "F{}", n),
+            other => write!(f, "{:?}", other),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(value: &str) -> KeyBinding {
+        value.parse().unwrap()
+    }
+
+    fn error(value: &str) -> String {
+        value.parse::<KeyBinding>().unwrap_err().to_string()
+    }
+
+    #[test]
+    fn bindings() {
+        assert_eq!(parse("x"), key('x'));
+        assert_eq!(parse("Ctrl+e"), ctrl('e'));
+        // an uppercase letter means shift
+        assert_eq!(
+            parse("ctrl+E"),
+            KeyBinding::new(
+                KeyCode::Char('E'),
+                KeyModifiers::CONTROL | KeyModifiers::SHIFT
+            )
+        );
+        assert_eq!(parse("meta+x"), alt('x'));
+        assert_eq!(
+            parse("P"),
+            KeyBinding::new(KeyCode::Char('P'), KeyModifiers::SHIFT)
+        );
+        assert_eq!(parse("shift+p"), parse("P"));
+        assert_eq!(parse("?"), key('?'));
+        assert_eq!(parse("+"), key('+'));
+        assert_eq!(parse("ctrl++"), ctrl('+'));
+        assert_eq!(parse("space"), key(' '));
+        assert_eq!(parse("Enter"), code(KeyCode::Enter));
+        assert_eq!(parse("pagedown"), code(KeyCode::PageDown));
+        assert_eq!(parse("f"), key('f'));
+        assert_eq!(parse("F12"), code(KeyCode::F(12)));
+        assert_eq!(
+            parse("ctrl+alt+delete"),
+            KeyBinding::new(KeyCode::Delete, KeyModifiers::CONTROL | KeyModifiers::ALT)
+        );
+        assert_eq!(parse("shift+tab").code, KeyCode::BackTab);
+    }
+
+    #[test]
+    fn invalid_bindings() {
+        assert_eq!(error(""), "unknown key '' in key binding ''");
+        assert_eq!(
+            error("hyper+x"),
+            "unknown modifier 'hyper' in key binding 'hyper+x'"
+        );
+        assert_eq!(
+            error("ctrl+xy"),
+            "unknown key 'xy' in key binding 'ctrl+xy'"
+        );
+        assert_eq!(
+            error("f99999"),
+            "unknown key 'f99999' in key binding 'f99999'"
+        );
+    }
+
+    #[test]
+    fn matches_events() {
+        let event = |code, modifiers| KeyEvent::new(code, modifiers);
+        assert!(parse("P").matches(&event(KeyCode::Char('P'), KeyModifiers::SHIFT)));
+        assert!(parse("P").matches(&event(KeyCode::Char('P'), KeyModifiers::NONE)));
+        assert!(!parse("p").matches(&event(KeyCode::Char('P'), KeyModifiers::SHIFT)));
+        assert!(!parse("x").matches(&event(KeyCode::Char('x'), KeyModifiers::CONTROL)));
+        assert!(parse("ctrl+x").matches(&event(KeyCode::Char('x'), KeyModifiers::CONTROL)));
+        for binding in ["shift+tab", "backtab"] {
+            for modifiers in [KeyModifiers::SHIFT, KeyModifiers::NONE] {
+                assert!(parse(binding).matches(&event(KeyCode::BackTab, modifiers)));
+            }
+        }
+    }
+
+    #[test]
+    fn overrides() {
+        let overrides = |name: &str, keys: &[&str]| {
+            BTreeMap::from([(name.to_string(), keys.iter().map(|k| parse(k)).collect())])
+        };
+        let keymap = Keymap::new(&overrides("execute", &["ctrl+e", "F5"])).unwrap();
+        assert_eq!(keymap.keys(Action::Execute), "Ctrl+e, F5");
+        let event = KeyEvent::new(KeyCode::F(5), KeyModifiers::NONE);
+        assert_eq!(
+            keymap.action(KeyContext::Navigate, &event),
+            Some(Action::Execute)
+        );
+        let event = KeyEvent::new(KeyCode::Char('x'), KeyModifiers::NONE);
+        assert_eq!(keymap.action(KeyContext::Navigate, &event), None);
+
+        let err = Keymap::new(&overrides("launch", &["x"])).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("keymap: unknown action 'launch'"));
+        let err = Keymap::new(&overrides("delete", &["x"])).unwrap_err();
+        assert!(err.to_string().contains("'x' is bound to both"), "{}", err);
+    }
+}
@@ -0,0 +1,226 @@
+use crate::tui::theme::Theme;
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span};
+
+/// Renders the subset of Markdown used for notes: headings, lists, quotes, rules,
+/// fenced code blocks and inline emphasis, strong text and code. Rules span the width.
+pub fn render(text: &str, width: u16, theme: &Theme) -> Vec<Line<'static>> {
+    let code_style = Style::new().fg(theme.header_fg).bg(theme.header_bg);
+    let mut lines = Vec::new();
+    let mut in_code = false;
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            lines.push(Line::from(Span::styled(format!(" {} ", line), code_style)));
+            continue;
+        }
+
+        let indent = " ".repeat(line.chars().count() - trimmed.chars().count());
+        let heading = trimmed.chars().take_while(|c| *c == '#').count();
+        if (1..=6).contains(&heading) && trimmed[heading..].starts_with(' ') {
+            let mut style = Style::new().add_modifier(Modifier::BOLD);
+            if heading == 1 {
+                style = style.add_modifier(Modifier::UNDERLINED);
+            }
+            lines.push(Line::from(inline(trimmed[heading..].trim(), theme)).style(style));
+        } else if trimmed.len() >= 3
+            && ['-', '*', '_']
+                .iter()
+                .any(|rule| trimmed.chars().all(|c| c == *rule))
+        {
+            lines.push(Line::from(Span::styled(
+                "─".repeat(width as usize),
+                Style::new().fg(theme.muted),
+            )));
+        } else if let Some(item) = ["- ", "* ", "+ "]
+            .iter()
+            .find_map(|marker| trimmed.strip_prefix(marker))
+        {
+            let mut spans = vec![Span::raw(format!("{}• ", indent))];
+            spans.extend(inline(item, theme));
+            lines.push(Line::from(spans));
+        } else if let Some(quote) = trimmed.strip_prefix('>') {
+            let mut spans = vec![Span::styled(
+                format!("{}│ ", indent),
+                Style::new().fg(theme.muted),
+            )];
+            spans.extend(inline(quote.trim_start(), theme));
+            lines.push(Line::from(spans).style(Style::new().add_modifier(Modifier::ITALIC)));
+        } else {
+            // ordered list items are kept as written
+            let mut spans = vec![Span::raw(indent)];
+            spans.extend(inline(trimmed, theme));
+            lines.push(Line::from(spans));
+        }
+    }
+    lines
+}
+
+/// Spans of a line with `**strong**`, `*emphasis*`, `_emphasis_` and `` `code` ``
+fn inline(text: &str, theme: &Theme) -> Vec<Span<'static>> {
+    let code_style = Style::new().fg(theme.header_fg).bg(theme.header_bg);
+    let mut spans = Vec::new();
+    let mut plain = String::new();
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let marker = match c {
+            '`' => "`",
+            '*' if rest.starts_with("**") => "**",
+            '_' if rest.starts_with("__") => "__",
+            '*' | '_' => &rest[..1],
+            _ => "",
+        };
+        let closed = (!marker.is_empty())
+            .then(|| rest[marker.len()..].find(marker))
+            .flatten()
+            .filter(|end| *end > 0);
+        let Some(end) = closed else {
+            plain.push(c);
+            rest = &rest[c.len_utf8()..];
+            continue;
+        };
+        if !plain.is_empty() {
+            spans.push(Span::raw(std::mem::take(&mut plain)));
+        }
+        let content = rest[marker.len()..marker.len() + end].to_string();
+        let style = match marker {
+            "`" => code_style,
+            "**" | "__" => Style::new().add_modifier(Modifier::BOLD),
+            _ => Style::new().add_modifier(Modifier::ITALIC),
+        };
+        spans.push(Span::styled(content, style));
+        rest = &rest[2 * marker.len() + end..];
+    }
+    if !plain.is_empty() {
+        spans.push(Span::raw(plain));
+    }
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(lines: &[Line]) -> Vec<String> {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    fn spans(text: &str) -> Vec<(String, Style)> {
+        inline(text, &Theme::default())
+            .into_iter()
+            .map(|span| (span.content.to_string(), span.style))
+            .collect()
+    }
+
+    fn plain(text: &str) -> (String, Style) {
+        (text.to_string(), Style::new())
+    }
+
+    fn bold(text: &str) -> (String, Style) {
+        (text.to_string(), Style::new().add_modifier(Modifier::BOLD))
+    }
+
+    fn italic(text: &str) -> (String, Style) {
+        (
+            text.to_string(),
+            Style::new().add_modifier(Modifier::ITALIC),
+        )
+    }
+
+    fn code(text: &str) -> (String, Style) {
+        let theme = Theme::default();
+        let style = Style::new().fg(theme.header_fg).bg(theme.header_bg);
+        (text.to_string(), style)
+    }
+
+    #[test]
+    fn headings() {
+        let lines = render(
+            "# Title\n### Section *one*\n#hashtag\n####### seven",
+            10,
+            &Theme::default(),
+        );
+        assert_eq!(
+            text(&lines),
+            ["Title", "Section one", "#hashtag", "####### seven"]
+        );
+        let title = Style::new().add_modifier(Modifier::BOLD | Modifier::UNDERLINED);
+        assert_eq!(lines[0].style, title);
+        assert_eq!(lines[1].style, Style::new().add_modifier(Modifier::BOLD));
+        assert_eq!(lines[2].style, Style::new());
+    }
+
+    #[test]
+    fn rules() {
+        let lines = render("---\n***\n__\n- - -", 5, &Theme::default());
+        assert_eq!(text(&lines), ["─────", "─────", "__", "• - -"]);
+    }
+
+    #[test]
+    fn nested_lists_and_quotes() {
+        let lines = render(
+            "- one\n  * two\n    + three\n1. first\n> said",
+            10,
+            &Theme::default(),
+        );
+        assert_eq!(
+            text(&lines),
+            ["• one", "  • two", "    • three", "1. first", "│ said"]
+        );
+    }
+
+    #[test]
+    fn fenced_code() {
+        let lines = render(
+            "```sql\nselect *\n  from t\n```\n# after",
+            10,
+            &Theme::default(),
+        );
+        assert_eq!(text(&lines), [" select * ", "   from t ", "after"]);
+        assert_eq!(lines[0].spans[0].style, code("").1);
+        // an unclosed fence keeps the rest as code
+        let lines = render("```\n# not a heading", 10, &Theme::default());
+        assert_eq!(text(&lines), [" # not a heading "]);
+    }
+
+    #[test]
+    fn inline_markers() {
+        assert_eq!(
+            spans("a **b** _c_ `d*e`"),
+            [
+                plain("a "),
+                bold("b"),
+                plain(" "),
+                italic("c"),
+                plain(" "),
+                code("d*e")
+            ]
+        );
+        assert_eq!(spans("__strong__"), [bold("strong")]);
+        // unclosed and empty markers stay as written
+        assert_eq!(spans("2 * 3 and `x"), [plain("2 * 3 and `x")]);
+        assert_eq!(spans("**b* c"), [plain("*"), italic("b"), plain(" c")]);
+        assert_eq!(spans("`` x"), [plain("`` x")]);
+        assert_eq!(spans("snake_case"), [plain("snake_case")]);
+    }
+
+    #[test]
+    fn multibyte_text() {
+        assert_eq!(
+            spans("привіт *світ* `日本` ü"),
+            [
+                plain("привіт "),
+                italic("світ"),
+                plain(" "),
+                code("日本"),
+                plain(" ü")
+            ]
+        );
+        let lines = render("\u{3000}- ünïcödé\n## Заголовок", 10, &Theme::default());
+        assert_eq!(text(&lines), [" • ünïcödé", "Заголовок"]);
+    }
+}
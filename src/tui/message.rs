@@ -1,11 +1,18 @@
+use crate::core::notebook::NotebookCell;
+use crate::core::plan::QueryPlan;
+use crate::core::profile::{Profile, ProfileSource};
+use crate::core::{Progress, QueryResult, Setting};
 use crossterm::event::KeyEvent;
-use datafusion::arrow::array::RecordBatch;
+use std::time::Duration;
 use uuid::Uuid;
 
 #[derive(PartialEq, Debug, Clone)]
 pub enum Message {
     Cells(CellsMessage),
+    Settings(SettingsMessage),
     KeyPressed(KeyEvent),
+    /// Sent periodically while any cell is running, to animate progress
+    Tick,
     ConfirmQuit,
     Quit,
 }
@@ -14,8 +21,72 @@ pub enum Message {
 pub enum CellsMessage {
     ExecuteCurrent,
     SaveCurrent,
-    SetResult(Uuid, Vec<RecordBatch>),
+    SetResult(Uuid, QueryResult),
     SetError(Uuid, String),
+    SetProgress(Uuid, Progress),
+    ExplainCurrent(bool),
+    SetPlan(Uuid, Box<QueryPlan>),
+    /// Profiles the result of the current cell
+    ProfileCurrent,
+    /// Column statistics of the cell, or why they could not be computed
+    SetProfile(Uuid, Result<Box<Profile>, String>),
     Create(Option<String>),
+    /// Creates a cell read from a notebook file
+    Load(NotebookCell),
     DeleteCurrent,
+    /// Moves the current cell by one position, up when `true`
+    MoveCurrent(bool),
+    DuplicateCurrent,
+    /// Splits the current cell at the editor cursor, the second part becomes a new cell
+    SplitCurrent,
+    /// Appends the code of the next cell to the current one and deletes the next cell
+    MergeWithNext,
+    /// Switches the current cell between SQL and Markdown
+    ToggleKind,
+    /// Reverts the last structural change or code change of the notebook
+    Undo,
+    Redo,
+    /// Opens the current cell in `$VISUAL` or `$EDITOR`
+    EditExternally,
+    /// Code of the cell after it was edited in the external editor, or why that failed
+    SetEditedCode(Uuid, Result<String, String>),
+    /// Pretty-prints SQL in the editor
+    FormatCurrent,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum SettingsMessage {
+    Open,
+    Loaded(Vec<Setting>),
+    Set(String, String),
+    Failed(String),
+    /// Writes changed settings to the config file
+    Persist,
+}
+
+/// Work sent to the DataFusion loop
+#[derive(PartialEq, Debug, Clone)]
+pub enum SessionRequest {
+    Execute {
+        cell_id: Uuid,
+        expr: String,
+        /// Overrides the default timeout from the configuration
+        timeout: Option<Duration>,
+    },
+    Explain {
+        cell_id: Uuid,
+        expr: String,
+        analyze: bool,
+        timeout: Option<Duration>,
+    },
+    Profile {
+        cell_id: Uuid,
+        source: ProfileSource,
+        timeout: Option<Duration>,
+    },
+    LoadSettings,
+    SetSetting {
+        key: String,
+        value: String,
+    },
 }
@@ -1,7 +1,15 @@
 pub mod app;
+pub mod config;
 pub mod handler;
+pub mod history;
+pub mod keymap;
+pub mod markdown;
 pub mod message;
+pub mod palette;
+pub mod search;
 pub mod state;
+pub mod theme;
 pub mod view;
+pub mod vim;
 
 pub use app::*;
@@ -0,0 +1,106 @@
+use crate::tui::keymap::{Action, KeyContext, ACTIONS};
+use crate::tui::state::State;
+use tui_textarea::TextArea;
+use uuid::Uuid;
+
+/// Fuzzy search over cells and actions, opened from navigation
+#[derive(Debug, Default)]
+pub struct Palette {
+    pub query: TextArea<'static>,
+    pub selected: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PaletteItem {
+    Cell(Uuid),
+    Action(Action),
+}
+
+pub struct PaletteEntry {
+    pub item: PaletteItem,
+    pub label: String,
+    /// Cell number or key bindings of the action
+    pub detail: String,
+}
+
+impl Palette {
+    pub fn query_text(&self) -> String {
+        self.query.lines().join("")
+    }
+
+    /// Cells and actions matching the query, best matches first
+    pub fn entries(&self, state: &State) -> Vec<PaletteEntry> {
+        let query = self.query_text();
+        let cells = state.cells.order.iter().enumerate().filter_map(|(i, id)| {
+            let cell = state.cells.all.get(id)?;
+            let label = match cell.name() {
+                Some(name) => format!("{}: {}", name, cell.preview()),
+                None => cell.preview().to_string(),
+            };
+            // names and previews count more than a match somewhere in the code
+            let score = fuzzy_score(&query, &label).or_else(|| {
+                cell.code
+                    .as_deref()
+                    .unwrap_or_default()
+                    .lines()
+                    .filter_map(|line| fuzzy_score(&query, line))
+                    .max()
+                    .map(|score| score / 2)
+            })?;
+            let entry = PaletteEntry {
+                item: PaletteItem::Cell(*id),
+                label,
+                detail: format!("cell {}", i + 1),
+            };
+            Some((score, entry))
+        });
+        let actions = ACTIONS
+            .iter()
+            .filter(|info| matches!(info.context, KeyContext::Navigate | KeyContext::Edit))
+            .filter(|info| !matches!(info.action, Action::Palette | Action::LeaveEditor))
+            .filter_map(|info| {
+                let score =
+                    fuzzy_score(&query, info.description).max(fuzzy_score(&query, info.name))?;
+                let entry = PaletteEntry {
+                    item: PaletteItem::Action(info.action),
+                    label: info.description.to_string(),
+                    detail: state.ui.keymap.keys(info.action),
+                };
+                Some((score, entry))
+            });
+
+        let mut entries = cells.chain(actions).collect::<Vec<_>>();
+        // stable, so equally good matches keep the notebook and help order
+        entries.sort_by_key(|(score, _)| -score);
+        entries.into_iter().map(|(_, entry)| entry).collect()
+    }
+}
+
+/// Matches characters of the pattern in order, ignoring case. Consecutive characters
+/// and ones at the start of words score higher, `None` if the pattern does not match.
+fn fuzzy_score(pattern: &str, text: &str) -> Option<i64> {
+    let mut score = 0;
+    let mut chars = text.chars().enumerate();
+    let mut previous: Option<char> = None;
+    let mut last_match: Option<usize> = None;
+    for p in pattern.chars().filter(|c| !c.is_whitespace()) {
+        let p = p.to_lowercase().next().unwrap_or(p);
+        loop {
+            let (i, c) = chars.next()?;
+            let before = previous.replace(c);
+            if c.to_lowercase().next() != Some(p) {
+                continue;
+            }
+            score += 1;
+            if last_match.is_some_and(|last| last + 1 == i) {
+                score += 4;
+            }
+            if before.is_none_or(|b| !b.is_alphanumeric()) {
+                score += 3;
+            }
+            last_match = Some(i);
+            break;
+        }
+    }
+    Some(score)
+}
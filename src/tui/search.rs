@@ -0,0 +1,280 @@
+use crate::config::FormatConfig;
+use crate::core::display::ValueFormatter;
+use crate::tui::state::Cells;
+use datafusion::arrow::array::RecordBatch;
+use regex::RegexBuilder;
+use tui_textarea::TextArea;
+use uuid::Uuid;
+
+/// Find and replace over the code of all cells. The search is case-insensitive
+/// unless the query has uppercase letters.
+#[derive(Debug, Default)]
+pub struct Search {
+    pub query: TextArea<'static>,
+    pub replacement: TextArea<'static>,
+    /// The replacement field is shown
+    pub replacing: bool,
+    /// Keys go to the replacement field instead of the query
+    pub replacement_focused: bool,
+    /// Where the search started, the first match after it is selected while typing
+    pub origin: Position,
+}
+
+/// Index of the cell in the notebook order, line and character in it
+pub type Position = (usize, usize, usize);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Match {
+    pub cell_id: Uuid,
+    pub position: Position,
+    /// Length in characters
+    pub len: usize,
+}
+
+impl Search {
+    pub fn query_text(&self) -> String {
+        self.query.lines().join("")
+    }
+
+    pub fn replacement_text(&self) -> String {
+        self.replacement.lines().join("")
+    }
+
+    fn case_sensitive(&self) -> bool {
+        self.query_text().chars().any(char::is_uppercase)
+    }
+
+    /// Regex highlighting matches in the editor
+    pub fn pattern(&self) -> String {
+        let query = self.query_text();
+        if query.is_empty() {
+            return String::new();
+        }
+        let flags = if self.case_sensitive() { "" } else { "(?i)" };
+        format!("{}{}", flags, regex::escape(&query))
+    }
+
+    /// All matches in the notebook order
+    pub fn matches(&self, cells: &Cells) -> Vec<Match> {
+        let query = self.normalize(&self.query_text());
+        if query.is_empty() {
+            return Vec::new();
+        }
+        let mut matches = Vec::new();
+        for (index, cell_id) in cells.order.iter().enumerate() {
+            let Some(code) = cells.all.get(cell_id).and_then(|c| c.code.as_deref()) else {
+                continue;
+            };
+            for (row, line) in code.lines().enumerate() {
+                let line = self.normalize(line);
+                let mut col = 0;
+                while col + query.len() <= line.len() {
+                    if line[col..col + query.len()] == query[..] {
+                        matches.push(Match {
+                            cell_id: *cell_id,
+                            position: (index, row, col),
+                            len: query.len(),
+                        });
+                        col += query.len();
+                    } else {
+                        col += 1;
+                    }
+                }
+            }
+        }
+        matches
+    }
+
+    /// Characters compared by the search, one per character of the text
+    fn normalize(&self, text: &str) -> Vec<char> {
+        if self.case_sensitive() {
+            text.chars().collect()
+        } else {
+            text.chars()
+                .map(|c| c.to_lowercase().next().unwrap_or(c))
+                .collect()
+        }
+    }
+}
+
+/// Cells with matches, in the notebook order
+pub fn cells(matches: &[Match]) -> Vec<Uuid> {
+    let mut cells: Vec<Uuid> = Vec::new();
+    for m in matches {
+        if !cells.contains(&m.cell_id) {
+            cells.push(m.cell_id);
+        }
+    }
+    cells
+}
+
+/// Code with the matches replaced, all of them must be in this code. The rest of the code,
+/// including line endings, stays as it is.
+pub fn replace(code: &str, matches: &[Match], replacement: &str) -> String {
+    let mut result = String::with_capacity(code.len());
+    // rows are the same as of `lines`, which the matches are found in
+    for (row, line) in code.split_inclusive('\n').enumerate() {
+        let mut chars = line.chars().enumerate().peekable();
+        for m in matches.iter().filter(|m| m.position.1 == row) {
+            let col = m.position.2;
+            while let Some((_, c)) = chars.next_if(|(i, _)| *i < col) {
+                result.push(c);
+            }
+            result.push_str(replacement);
+            for _ in 0..m.len {
+                chars.next();
+            }
+        }
+        result.extend(chars.map(|(_, c)| c));
+    }
+    result
+}
+
+/// Search in the values of a result as they are shown, ignoring case
+#[derive(Debug, Default)]
+pub struct ResultSearch {
+    pub query: TextArea<'static>,
+    /// The query is a regular expression instead of plain text
+    pub regex: bool,
+    /// Keys go to the query, otherwise the matches are only navigated
+    pub editing: bool,
+    /// Row and column of every match, in the order they are shown
+    pub matches: Vec<(usize, usize)>,
+    /// Columns which were searched, in the order they are shown
+    columns: Vec<usize>,
+    /// Why the regular expression is not valid
+    pub error: Option<String>,
+    /// Selected row when the search started, the first match from it is selected while typing
+    pub origin: usize,
+}
+
+impl ResultSearch {
+    pub fn query_text(&self) -> String {
+        self.query.lines().join("")
+    }
+
+    /// Finds the matches in all batches, in the order the rows are shown.
+    /// Only the given columns are searched, hidden ones are left out.
+    pub fn update(&mut self, batches: &[RecordBatch], columns: &[usize], format: &FormatConfig) {
+        self.matches.clear();
+        self.columns = columns.to_vec();
+        self.error = None;
+        let query = self.query_text();
+        if query.is_empty() {
+            return;
+        }
+        let pattern = if self.regex {
+            query
+        } else {
+            regex::escape(&query)
+        };
+        let regex = match RegexBuilder::new(&pattern).case_insensitive(true).build() {
+            Ok(regex) => regex,
+            Err(err) => {
+                self.error = Some(err.to_string());
+                return;
+            }
+        };
+        let mut offset = 0;
+        for batch in batches {
+            let Ok(formatters) = batch
+                .columns()
+                .iter()
+                .map(|c| ValueFormatter::try_new(c.as_ref(), format))
+                .collect::<Result<Vec<_>, _>>()
+            else {
+                continue;
+            };
+            for row in 0..batch.num_rows() {
+                for &column in columns {
+                    let Some(formatter) = formatters.get(column) else {
+                        continue;
+                    };
+                    if regex.is_match(&formatter.value(row)) {
+                        self.matches.push((offset + row, column));
+                    }
+                }
+            }
+            offset += batch.num_rows();
+        }
+    }
+
+    pub fn is_match(&self, row: usize, column: usize) -> bool {
+        let cell = self.shown((row, column));
+        self.matches
+            .binary_search_by_key(&cell, |m| self.shown(*m))
+            .is_ok()
+    }
+
+    /// The first match after the cell, or before it with `backward`, wrapping around
+    pub fn next(&self, cell: (usize, usize), backward: bool) -> Option<(usize, usize)> {
+        let cell = self.shown(cell);
+        if backward {
+            let previous = self.matches.iter().rev().find(|m| self.shown(**m) < cell);
+            previous.or(self.matches.last()).copied()
+        } else {
+            let next = self.matches.iter().find(|m| self.shown(**m) > cell);
+            next.or(self.matches.first()).copied()
+        }
+    }
+
+    /// Row and position of the column among the shown ones, the order in which cells are seen
+    fn shown(&self, (row, column): (usize, usize)) -> (usize, usize) {
+        let position = self.columns.iter().position(|c| *c == column);
+        (row, position.unwrap_or(usize::MAX))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::{ArrayRef, StringArray};
+    use std::sync::Arc;
+
+    fn at(row: usize, col: usize, len: usize) -> Match {
+        Match {
+            cell_id: Uuid::nil(),
+            position: (0, row, col),
+            len,
+        }
+    }
+
+    #[test]
+    fn replace_keeps_line_endings() {
+        let matches = [at(0, 7, 1), at(1, 5, 1), at(1, 12, 1)];
+        assert_eq!(
+            replace("select a\r\nfrom a join a\r\n", &matches, "bb"),
+            "select bb\r\nfrom bb join bb\r\n"
+        );
+        assert_eq!(
+            replace("select a\nfrom t\n\n", &[at(1, 5, 1)], "ü"),
+            "select a\nfrom ü\n\n"
+        );
+        assert_eq!(replace("select 1", &[], "x"), "select 1");
+    }
+
+    #[test]
+    fn result_matches_follow_the_shown_columns() {
+        let column = |values: Vec<&str>| Arc::new(StringArray::from(values)) as ArrayRef;
+        let batch = RecordBatch::try_from_iter([
+            ("a", column(vec!["x", "-"])),
+            ("b", column(vec!["x", "x"])),
+            ("c", column(vec!["X", "x"])),
+        ])
+        .unwrap();
+        let mut search = ResultSearch::default();
+        search.query.insert_str("x");
+        // c was moved to the front and b is hidden
+        search.update(&[batch], &[2, 0], &FormatConfig::default());
+        assert_eq!(search.matches, [(0, 2), (0, 0), (1, 2)]);
+        assert!(search.is_match(0, 0));
+        assert!(search.is_match(1, 2));
+        assert!(!search.is_match(1, 1));
+
+        assert_eq!(search.next((0, 2), false), Some((0, 0)));
+        assert_eq!(search.next((0, 0), false), Some((1, 2)));
+        assert_eq!(search.next((1, 2), false), Some((0, 2)));
+        assert_eq!(search.next((1, 0), true), Some((1, 2)));
+        assert_eq!(search.next((0, 2), true), Some((1, 2)));
+    }
+}
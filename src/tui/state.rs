@@ -1,6 +1,23 @@
+use crate::config::{Config, FormatConfig};
+use crate::core::chart::{chart_data, ChartColumns, ChartData};
+use crate::core::diff::{diff, parse_key, ResultDiff};
+use crate::core::notebook::CellKind;
+use crate::core::plan::{PlanNode, QueryPlan};
+use crate::core::profile::Profile;
+use crate::core::table::{Filter, SortKey};
+use crate::core::{directive, Progress, QueryResult, Setting};
+use crate::tui::config::TuiConfig;
+use crate::tui::history::History;
 use crate::tui::message::Message;
+use crate::tui::palette::Palette;
+use crate::tui::search::{ResultSearch, Search};
+use crate::tui::vim::Vim;
+use chrono::{DateTime, Local};
 use datafusion::arrow::array::RecordBatch;
-use std::collections::HashMap;
+use datafusion::execution::memory_pool::MemoryPool;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tui_textarea::TextArea;
 use uuid::Uuid;
 
@@ -10,7 +27,27 @@ pub struct State<'a> {
     pub mode: Mode,
     pub quit: bool,
     pub show_help: bool,
+    /// The list of cells is shown next to the editor
+    pub show_sidebar: bool,
     pub popup: Option<ConfirmDialog>,
+    /// Number of ticks received, drives spinner animation
+    pub tick: usize,
+    pub memory_pool: Option<Arc<dyn MemoryPool>>,
+    pub config: Config,
+    pub ui: TuiConfig,
+    pub settings: Option<SettingsPanel>,
+    pub palette: Option<Palette>,
+    pub search: Option<Search>,
+    /// Modal editing state of the cell editor, used when `ui.vim` is enabled
+    pub vim: Vim,
+    /// Cell to be opened in the external editor, the UI is suspended meanwhile
+    pub external_edit: Option<Uuid>,
+    /// Shown in the status line until the next key is pressed
+    pub message: Option<String>,
+    /// Changes of the notebook for undo and redo
+    pub history: History,
+    /// Cell whose result others are compared with
+    pub compare_base: Option<Uuid>,
 }
 
 #[derive(Debug, Default, PartialEq)]
@@ -18,6 +55,12 @@ pub enum Mode {
     #[default]
     Navigate,
     EditCell,
+    Plan,
+    Chart,
+    Profile,
+    Result,
+    Diff,
+    History,
 }
 
 #[derive(Debug, Clone)]
@@ -33,6 +76,40 @@ pub enum ConfirmDialogButton {
     No,
 }
 
+#[derive(Debug, Default)]
+pub struct SettingsPanel {
+    pub entries: Vec<Setting>,
+    pub selected: usize,
+    pub filter: TextArea<'static>,
+    pub filtering: bool,
+    /// Value being edited for the selected entry
+    pub editor: Option<TextArea<'static>>,
+    pub message: Option<String>,
+    pub error: Option<String>,
+}
+
+impl SettingsPanel {
+    pub fn filter_text(&self) -> String {
+        self.filter.lines().join("")
+    }
+
+    /// Entries matching the filter by key or description
+    pub fn visible(&self) -> Vec<&Setting> {
+        let filter = self.filter_text().to_lowercase();
+        self.entries
+            .iter()
+            .filter(|s| {
+                s.key.to_lowercase().contains(&filter)
+                    || s.description.to_lowercase().contains(&filter)
+            })
+            .collect()
+    }
+
+    pub fn selected_entry(&self) -> Option<&Setting> {
+        self.visible().get(self.selected).copied()
+    }
+}
+
 #[derive(Debug)]
 pub struct Cells<'a> {
     pub editor: TextArea<'a>,
@@ -69,15 +146,43 @@ impl Cells<'_> {
         self.current_cell_id
             .and_then(|id| self.order.iter().position(|item| *item == id))
     }
+
+    pub fn any_running(&self) -> bool {
+        self.all
+            .values()
+            .any(|c| matches!(c.status, CellStatus::Running))
+    }
 }
 
 #[derive(Debug)]
 pub struct Cell {
     pub id: Uuid,
+    pub kind: CellKind,
     pub code: Option<String>,
-    pub result: Option<Vec<RecordBatch>>,
+    pub result: Option<QueryResult>,
+    /// Selection, sorting and filtering of the result table
+    pub table: ResultView,
     pub error: Option<String>,
+    pub plan: Option<PlanView>,
+    /// Column statistics, shown instead of the result until closed
+    pub profile: Option<ProfileView>,
+    /// Comparison with another result, shown instead of the result until closed
+    pub diff: Option<DiffView>,
+    /// Finished runs, the latest first
+    pub runs: VecDeque<Run>,
+    /// Run selected while browsing the history
+    pub selected_run: usize,
+    /// The result is shown as a chart of these columns instead of a table
+    pub chart: Option<ChartColumns>,
+    /// Points of the chart, computed when the result or the chart columns change
+    pub chart_data: Option<Result<ChartData, String>>,
     pub status: CellStatus,
+    pub started_at: Option<Instant>,
+    /// Time taken by the last execution
+    pub duration: Option<Duration>,
+    pub progress: Option<Progress>,
+    /// Code of the last execution, to tell if the result is stale
+    pub executed_code: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -98,10 +203,351 @@ impl Cell {
     pub fn new() -> Self {
         Self {
             id: Uuid::new_v4(),
+            kind: CellKind::Sql,
             code: None,
             result: None,
+            table: ResultView::default(),
             error: None,
+            plan: None,
+            profile: None,
+            diff: None,
+            runs: VecDeque::new(),
+            selected_run: 0,
+            chart: None,
+            chart_data: None,
             status: CellStatus::Clean,
+            started_at: None,
+            duration: None,
+            progress: None,
+            executed_code: None,
         }
     }
+
+    /// Name given with the `-- @name` directive
+    pub fn name(&self) -> Option<&str> {
+        self.code.as_deref().and_then(directive::name)
+    }
+
+    /// First line of a comment, or of SQL if the cell starts with it. Directives are skipped.
+    /// For Markdown the first line without heading marks.
+    pub fn preview(&self) -> &str {
+        self.code
+            .as_deref()
+            .unwrap_or_default()
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with("```"))
+            .map(|line| match self.kind {
+                CellKind::Sql => line.strip_prefix("--").map_or(line, str::trim),
+                CellKind::Markdown => line.trim_start_matches('#').trim(),
+            })
+            .find(|line| !line.is_empty() && !line.starts_with('@'))
+            .unwrap_or_default()
+    }
+
+    /// Batches shown in the result table, sorted and filtered
+    pub fn table_batches(&self) -> &[RecordBatch] {
+        match (&self.table.arranged, &self.result) {
+            (Some(batch), _) => std::slice::from_ref(batch),
+            (None, Some(result)) => &result.batches,
+            (None, None) => &[],
+        }
+    }
+
+    /// Rows shown in the result table, after the filter if there is one
+    pub fn visible_rows(&self) -> usize {
+        match &self.table.arranged {
+            Some(batch) => batch.num_rows(),
+            None => self.result_rows(),
+        }
+    }
+
+    /// Columns of the result table in the order they are shown, by index in the schema
+    pub fn shown_columns(&self) -> Vec<usize> {
+        let count = self.result.as_ref().map_or(0, |r| r.schema.fields().len());
+        self.table.columns(count)
+    }
+
+    pub fn result_rows(&self) -> usize {
+        self.result
+            .iter()
+            .flat_map(|result| &result.batches)
+            .map(|batch| batch.num_rows())
+            .sum()
+    }
+
+    /// The code was changed after the last execution
+    pub fn is_stale(&self) -> bool {
+        matches!(self.status, CellStatus::Finished | CellStatus::Failed)
+            && self.executed_code.is_some()
+            && self.executed_code != self.code
+    }
+
+    pub fn start(&mut self) {
+        self.status = CellStatus::Running;
+        self.started_at = Some(Instant::now());
+        self.duration = None;
+        self.progress = None;
+    }
+
+    pub fn finish(&mut self, status: CellStatus) {
+        self.status = status;
+        self.duration = self.started_at.map(|s| s.elapsed());
+        self.progress = None;
+    }
+
+    /// Keeps the finished run in the history, the oldest ones beyond the limit are dropped.
+    /// Results are held outside of the memory pool, so only the latest ones are kept.
+    pub fn record_run(&mut self, outcome: Result<QueryResult, String>, config: &Config) {
+        let (outcome, result) = match outcome {
+            Ok(result) => (
+                Ok(result.batches.iter().map(|b| b.num_rows()).sum()),
+                Some(result),
+            ),
+            Err(err) => (Err(err), None),
+        };
+        self.runs.push_front(Run {
+            code: self.executed_code.clone(),
+            finished_at: Local::now(),
+            duration: self.duration,
+            outcome,
+            result,
+        });
+        self.runs.truncate(config.run_history);
+        for run in self
+            .runs
+            .iter_mut()
+            .filter(|run| run.result.is_some())
+            .skip(config.run_results)
+        {
+            run.result = None;
+        }
+        self.selected_run = 0;
+    }
+
+    /// Computes the points of the chart, so they are not computed again on every frame
+    pub fn refresh_chart(&mut self, format: &FormatConfig) {
+        self.chart_data = match (&self.chart, &self.result) {
+            (Some(columns), Some(result)) => {
+                Some(chart_data(&result.batches, columns, format).map_err(|err| err.to_string()))
+            }
+            _ => None,
+        };
+    }
+
+    /// Result of the successful run before the one shown, a failed run keeps the result it follows
+    pub fn previous_result(&self) -> Option<&QueryResult> {
+        self.runs
+            .iter()
+            .filter(|run| run.outcome.is_ok())
+            .nth(1)
+            .and_then(|run| run.result.as_ref())
+    }
+}
+
+/// A finished execution of a cell
+#[derive(Debug, Clone)]
+pub struct Run {
+    pub code: Option<String>,
+    pub finished_at: DateTime<Local>,
+    pub duration: Option<Duration>,
+    /// Number of rows returned, or why the run failed
+    pub outcome: Result<usize, String>,
+    /// Only kept for the latest successful runs
+    pub result: Option<QueryResult>,
+}
+
+/// Selected cell of the result table with the sorting and the filter applied to it
+#[derive(Debug, Default)]
+pub struct ResultView {
+    pub row: usize,
+    pub column: usize,
+    /// Sort keys in order of priority
+    pub sort: Vec<SortKey>,
+    pub filter: Option<Filter>,
+    /// Text of the applied filter, the editor starts with it
+    pub filter_text: String,
+    /// Filter being typed, applied with Enter
+    pub filter_editor: Option<TextArea<'static>>,
+    /// The result sorted and filtered, `None` when it is shown as collected
+    pub arranged: Option<RecordBatch>,
+    pub search: Option<ResultSearch>,
+    /// The selected row is shown transposed, one field per line
+    pub record: bool,
+    /// Order in which the columns are shown, by index in the schema; empty until one is moved
+    pub order: Vec<usize>,
+    pub hidden: HashSet<usize>,
+    /// Number of shown columns which stay in place when scrolling horizontally
+    pub frozen: usize,
+}
+
+impl ResultView {
+    pub fn is_filtered(&self) -> bool {
+        self.filter.is_some()
+    }
+
+    /// Columns in the order they are shown, without the hidden ones
+    pub fn columns(&self, count: usize) -> Vec<usize> {
+        let order = if self.order.len() == count {
+            self.order.clone()
+        } else {
+            (0..count).collect()
+        };
+        order
+            .into_iter()
+            .filter(|c| !self.hidden.contains(c))
+            .collect()
+    }
+
+    /// Position of the column among the sort keys and its direction
+    pub fn sort_key(&self, column: usize) -> Option<(usize, &SortKey)> {
+        self.sort
+            .iter()
+            .enumerate()
+            .find(|(_, k)| k.column == column)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum PlanTab {
+    #[default]
+    Physical,
+    Logical,
+}
+
+/// Plan tree of a cell together with its navigation state
+#[derive(Debug)]
+pub struct PlanView {
+    pub plan: QueryPlan,
+    pub tab: PlanTab,
+    pub selected: usize,
+    /// Paths (child indexes from the root) of collapsed nodes
+    pub collapsed: HashSet<(PlanTab, Vec<usize>)>,
+}
+
+impl PlanView {
+    pub fn new(plan: QueryPlan) -> Self {
+        Self {
+            plan,
+            tab: PlanTab::default(),
+            selected: 0,
+            collapsed: HashSet::new(),
+        }
+    }
+
+    pub fn root(&self) -> &PlanNode {
+        match self.tab {
+            PlanTab::Physical => &self.plan.physical,
+            PlanTab::Logical => &self.plan.logical,
+        }
+    }
+
+    pub fn is_collapsed(&self, path: &[usize]) -> bool {
+        self.collapsed.contains(&(self.tab, path.to_vec()))
+    }
+
+    /// Nodes of the current tab which are not hidden by a collapsed parent, in display order
+    pub fn visible_nodes(&self) -> Vec<(Vec<usize>, &PlanNode)> {
+        fn walk<'a>(
+            view: &PlanView,
+            node: &'a PlanNode,
+            path: Vec<usize>,
+            acc: &mut Vec<(Vec<usize>, &'a PlanNode)>,
+        ) {
+            let collapsed = view.is_collapsed(&path);
+            acc.push((path.clone(), node));
+            if !collapsed {
+                for (i, child) in node.children.iter().enumerate() {
+                    let mut child_path = path.clone();
+                    child_path.push(i);
+                    walk(view, child, child_path, acc);
+                }
+            }
+        }
+
+        let mut acc = Vec::new();
+        walk(self, self.root(), Vec::new(), &mut acc);
+        acc
+    }
+
+    pub fn selected_node(&self) -> Option<(Vec<usize>, &PlanNode)> {
+        self.visible_nodes().into_iter().nth(self.selected)
+    }
+
+    pub fn set_collapsed(&mut self, path: Vec<usize>, collapsed: bool) {
+        if collapsed {
+            self.collapsed.insert((self.tab, path));
+        } else {
+            self.collapsed.remove(&(self.tab, path));
+        }
+    }
+
+    pub fn switch_tab(&mut self) {
+        self.tab = match self.tab {
+            PlanTab::Physical => PlanTab::Logical,
+            PlanTab::Logical => PlanTab::Physical,
+        };
+        self.selected = 0;
+    }
+}
+
+/// Column statistics of a cell with the selected column
+#[derive(Debug)]
+pub struct ProfileView {
+    pub profile: Profile,
+    pub selected: usize,
+}
+
+impl ProfileView {
+    pub fn new(profile: Profile) -> Self {
+        Self {
+            profile,
+            selected: 0,
+        }
+    }
+}
+
+/// Rows of two results compared by the key columns, the result after is the one of the cell
+#[derive(Debug)]
+pub struct DiffView {
+    pub before_label: String,
+    pub after_label: String,
+    pub before: QueryResult,
+    pub after: QueryResult,
+    /// Key columns as typed, e.g. `id, day`
+    pub key_text: String,
+    /// Key columns being typed, applied with Enter
+    pub key_editor: Option<TextArea<'static>>,
+    /// Why the results cannot be compared with the key
+    pub diff: Result<ResultDiff, String>,
+    pub selected: usize,
+}
+
+impl DiffView {
+    pub fn new(
+        before_label: String,
+        before: QueryResult,
+        after_label: String,
+        after: QueryResult,
+    ) -> Self {
+        let mut view = Self {
+            before_label,
+            after_label,
+            before,
+            after,
+            key_text: String::new(),
+            key_editor: None,
+            diff: Err(String::new()),
+            selected: 0,
+        };
+        view.update();
+        view
+    }
+
+    /// Compares the results again with the key columns of `key_text`
+    pub fn update(&mut self) {
+        self.diff = diff(&self.before, &self.after, &parse_key(&self.key_text))
+            .map_err(|err| err.to_string());
+        self.selected = 0;
+    }
 }
@@ -0,0 +1,83 @@
+use ratatui::style::{Color, Style};
+use serde::Deserialize;
+
+/// Colours used across the UI, every field can be overridden in the `[theme]` section of the config
+/// with a colour name (`dark-gray`), an index (`42`) or a hex value (`#ff8800`)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    pub status_bar: Color,
+    pub border: Color,
+    pub separator: Color,
+    pub header_fg: Color,
+    pub header_bg: Color,
+    pub row_alt_bg: Color,
+    pub error: Color,
+    pub selection_fg: Color,
+    pub selection_bg: Color,
+    pub button_fg: Color,
+    pub button_bg: Color,
+    pub muted: Color,
+    pub cursor_fg: Color,
+    pub cursor_bg: Color,
+    pub cursor_inactive_bg: Color,
+    /// Highlighting of the most expensive plan nodes
+    pub hot: Color,
+    pub warm: Color,
+    /// Background of search matches
+    pub search: Color,
+    /// Rows and columns in a comparison of results
+    pub added: Color,
+    pub removed: Color,
+    pub changed: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            status_bar: Color::Gray,
+            border: Color::DarkGray,
+            separator: Color::Gray,
+            header_fg: Color::Black,
+            header_bg: Color::Gray,
+            row_alt_bg: Color::White,
+            error: Color::Red,
+            selection_fg: Color::White,
+            selection_bg: Color::DarkGray,
+            button_fg: Color::Black,
+            button_bg: Color::Gray,
+            muted: Color::DarkGray,
+            cursor_fg: Color::White,
+            cursor_bg: Color::Black,
+            cursor_inactive_bg: Color::Gray,
+            hot: Color::Red,
+            warm: Color::Yellow,
+            search: Color::LightBlue,
+            added: Color::Green,
+            removed: Color::Red,
+            changed: Color::Yellow,
+        }
+    }
+}
+
+impl Theme {
+    pub fn selection(&self) -> Style {
+        Style::from((self.selection_fg, self.selection_bg))
+    }
+
+    pub fn header(&self) -> Style {
+        Style::from((self.header_fg, self.header_bg))
+    }
+
+    pub fn cursor(&self) -> Style {
+        Style::from((self.cursor_fg, self.cursor_bg))
+    }
+
+    pub fn cursor_inactive(&self) -> Style {
+        Style::from((self.cursor_fg, self.cursor_inactive_bg))
+    }
+
+    pub fn search(&self) -> Style {
+        Style::new().bg(self.search)
+    }
+}
@@ -1,40 +1,170 @@
-use crate::tui::state::{CellStatus, ConfirmDialogButton, Mode, State};
+use crate::config::FormatConfig;
+use crate::core::chart::{format_number, ChartColumns, ChartData, ChartKind, Series};
+use crate::core::diff::{RowDiff, SchemaChange};
+use crate::core::display::{localize_array, ValueFormatter};
+use crate::core::notebook::CellKind;
+use crate::core::profile::ColumnProfile;
+use crate::tui::keymap::{help_lines, Action, KeyContext};
+use crate::tui::markdown;
+use crate::tui::palette::Palette;
+use crate::tui::search::{ResultSearch, Search};
+use crate::tui::state::{
+    Cell, CellStatus, ConfirmDialogButton, DiffView, Mode, PlanTab, PlanView, ProfileView,
+    SettingsPanel, State,
+};
+use crate::tui::theme::Theme;
+use crate::tui::vim::VimMode;
+use datafusion::arrow::array::RecordBatch;
 use datafusion::arrow::error::ArrowError;
-use datafusion::arrow::util::display::{ArrayFormatter, FormatOptions};
-use indoc::indoc;
+use datafusion::common::ScalarValue;
+use indoc::formatdoc;
 use ratatui::layout::{Alignment, Constraint, Direction, Flex, Layout, Rect};
 use ratatui::prelude::{Color, Modifier, Style};
-use ratatui::widgets::{Block, BorderType, Borders, Clear, Padding, Paragraph, Wrap};
+use ratatui::symbols::Marker;
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{
+    Axis, Bar, BarChart, BarGroup, Block, BorderType, Borders, Chart, Clear, Dataset, GraphType,
+    Padding, Paragraph, Tabs, Wrap,
+};
 use ratatui::{widgets, Frame};
+use std::time::Duration;
+
+const SPINNER: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs >= 60 {
+        format!("{}m {:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{:.1}s", duration.as_secs_f64())
+    }
+}
+
+fn format_bytes(bytes: usize) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
 
 fn render_status_line(state: &State, frame: &mut Frame, rect: Rect) {
-    let style = Style::new().bg(Color::Gray);
+    let style = Style::new().bg(state.ui.theme.status_bar);
     let mode_str = match state.mode {
-        Mode::Navigate => "🚀 NAVI",
-        Mode::EditCell => "✏️ EDIT",
+        Mode::Navigate => "🚀 NAVI".to_string(),
+        Mode::EditCell if state.ui.vim => format!("✏️ {}", state.vim.mode.name()),
+        Mode::EditCell => "✏️ EDIT".to_string(),
+        Mode::Plan => "🌳 PLAN".to_string(),
+        Mode::Chart => "📈 CHART".to_string(),
+        Mode::Profile => "📊 PROFILE".to_string(),
+        Mode::Result => "📋 RESULT".to_string(),
+        Mode::Diff => "🔀 DIFF".to_string(),
+        Mode::History => "🕘 HISTORY".to_string(),
     };
     let cell_no = state
         .cells
         .current_cell_index()
         .map(|i| format!("{}/{}", i + 1, state.cells.all.len()));
 
-    let cell_status =
-        state
-            .cells
-            .current_cell_id
-            .map(|id| match state.cells.all.get(&id).unwrap().status {
-                CellStatus::Clean => "Not Executed",
-                CellStatus::Running => "Running",
-                CellStatus::Finished => "Finished",
-                CellStatus::Failed => "Failed",
-            });
+    let cell_status = state.cells.current().map(|cell| {
+        if cell.kind == CellKind::Markdown {
+            return "Markdown".to_string();
+        }
+        let status = match cell.status {
+            CellStatus::Clean => "Not Executed",
+            CellStatus::Running => "Running",
+            CellStatus::Finished => "Finished",
+            CellStatus::Failed => "Failed",
+        };
+        match (&cell.status, cell.duration, cell.started_at) {
+            (CellStatus::Running, _, Some(started_at)) => {
+                format!("{} {}", status, format_duration(started_at.elapsed()))
+            }
+            (_, Some(duration), _) => format!("{} in {}", status, format_duration(duration)),
+            _ => status.to_string(),
+        }
+    });
+    let truncated = state
+        .cells
+        .current()
+        .filter(|cell| cell.result.as_ref().is_some_and(|r| r.truncated))
+        .map(|cell| format!("truncated to {} rows", cell.result_rows()));
+    let filtered = state
+        .cells
+        .current()
+        .filter(|cell| cell.table.is_filtered())
+        .map(|cell| {
+            format!(
+                "filtered {} of {} rows",
+                cell.visible_rows(),
+                cell.result_rows()
+            )
+        });
+    let hidden = state
+        .cells
+        .current()
+        .filter(|cell| !cell.table.hidden.is_empty() && cell.result.is_some())
+        .map(|cell| match cell.table.hidden.len() {
+            1 => "1 hidden column".to_string(),
+            n => format!("{} hidden columns", n),
+        });
+    let position = state
+        .cells
+        .current()
+        .filter(|cell| state.mode == Mode::Result && cell.visible_rows() > 0)
+        .map(|cell| format!("row {}/{}", cell.table.row + 1, cell.visible_rows()));
 
-    let mut parts = vec![mode_str.to_string()];
+    let mut parts = vec![mode_str];
+    if state.ui.vim && state.mode == Mode::EditCell {
+        if state.vim.mode == VimMode::Command {
+            parts.push(format!(":{}", state.vim.command));
+        } else if !state.vim.pending().is_empty() {
+            parts.push(state.vim.pending().to_string());
+        }
+        if let Some(message) = &state.vim.message {
+            parts.push(message.clone());
+        }
+    }
+    if let Some(message) = &state.message {
+        parts.push(message.clone());
+    }
     if let Some(val) = cell_no {
         parts.push(val);
     }
     if let Some(val) = cell_status {
-        parts.push(val.to_string());
+        parts.push(val);
+    }
+    if let Some(val) = truncated {
+        parts.push(val);
+    }
+    if let Some(val) = filtered {
+        parts.push(val);
+    }
+    if let Some(val) = hidden {
+        parts.push(val);
+    }
+    if let Some(val) = position {
+        parts.push(val);
+    }
+    if let Some(pool) = &state.memory_pool {
+        let reserved = pool.reserved();
+        match state.config.memory.limit {
+            Some(limit) => parts.push(format!(
+                "mem {} / {} ({:.0}%)",
+                format_bytes(reserved),
+                format_bytes(limit),
+                reserved as f64 * 100.0 / limit.max(1) as f64
+            )),
+            None if reserved > 0 => parts.push(format!("mem {}", format_bytes(reserved))),
+            None => {}
+        }
     }
 
     let mut status = parts.join(" • ");
@@ -63,7 +193,7 @@ fn render_popup(state: &State, frame: &mut Frame) {
         let block = Block::new()
             .border_type(BorderType::Rounded)
             .borders(Borders::all())
-            .border_style(Style::default().fg(Color::DarkGray))
+            .border_style(Style::default().fg(state.ui.theme.border))
             .padding(Padding::new(pad, pad, pad, pad));
 
         let text = popup.body.clone();
@@ -90,8 +220,9 @@ fn render_popup(state: &State, frame: &mut Frame) {
         frame.render_widget(block, area);
         frame.render_widget(text_widget, layout[0]);
 
-        let active = Style::from((Color::White, Color::DarkGray));
-        let not_active = Style::from((Color::Black, Color::Gray));
+        let theme = &state.ui.theme;
+        let active = theme.selection();
+        let not_active = Style::from((theme.button_fg, theme.button_bg));
 
         let btn_yes = Paragraph::new("Yes").alignment(Alignment::Center).style(
             if popup.active_button == ConfirmDialogButton::Yes {
@@ -113,149 +244,1689 @@ fn render_popup(state: &State, frame: &mut Frame) {
     }
 }
 
-fn render_help(frame: &mut Frame) {
-    let help = indoc! {"
-            n        - create new cell
-            d        - delete selected cell
-            ↑, k     - select previous cell
-            ↓, j     - select next cell
-            ←, h, ↵  - edit selected cell
-            x        - execute selected cell
-            q        - quit
-            ?, F1    - show this help
-            "};
-
-    let height = help.lines().count() + 2;
-    let width = help
-        .lines()
-        .map(|l| l.len())
-        .max()
-        .map(|l| l + 4)
-        .unwrap_or_default();
-    let area = centered_area(frame.area(), width as u16, height as u16);
+/// Vim commands shown in help instead of the `leave_editor` key
+const VIM_HELP: [(&str, &str); 3] = [
+    (":w", "save cell"),
+    (":q, :wq, ZZ", "save cell and back to navigation"),
+    (":q!, ZQ", "discard changes and back to navigation"),
+];
+
+/// Key bindings of every context, flowing into more columns when they do not fit the height
+fn render_help(state: &State, frame: &mut Frame) {
+    let keymap = &state.ui.keymap;
+    let mut sections = vec![keymap.help(KeyContext::Navigate)];
+    for (title, context) in [
+        ("Editor", KeyContext::Edit),
+        ("Plan", KeyContext::Plan),
+        ("Chart", KeyContext::Chart),
+        ("Result", KeyContext::Result),
+        ("Profile", KeyContext::Profile),
+        ("Diff", KeyContext::Diff),
+        ("History", KeyContext::History),
+    ] {
+        let mut lines = vec![format!("{}:", title)];
+        if context == KeyContext::Edit && state.ui.vim {
+            let mut entries = keymap.help_entries(context, &[Action::LeaveEditor]);
+            entries.extend(VIM_HELP.map(|(keys, description)| (keys.to_string(), description)));
+            lines.extend(help_lines(entries));
+        } else {
+            lines.extend(keymap.help(context));
+        }
+        sections.push(lines);
+    }
+
+    let max_height = frame.area().height.saturating_sub(2) as usize;
+    let mut columns: Vec<Vec<String>> = vec![Vec::new()];
+    for section in sections {
+        let column = columns.last_mut().expect("there is a column");
+        if !column.is_empty() && column.len() + 1 + section.len() > max_height {
+            columns.push(section);
+        } else {
+            if !column.is_empty() {
+                column.push(String::new());
+            }
+            column.extend(section);
+        }
+    }
+
+    let widths = columns
+        .iter()
+        .map(|lines| lines.iter().map(|l| l.chars().count()).max().unwrap_or(0) as u16)
+        .collect::<Vec<_>>();
+    let height = columns.iter().map(Vec::len).max().unwrap_or(0) + 2;
+    let gaps = 3 * (columns.len() as u16 - 1);
+    let width = widths.iter().sum::<u16>() + gaps + 4;
+    let area = centered_area(frame.area(), width, height as u16);
 
     let block = Block::default()
         .borders(Borders::ALL)
         .border_type(BorderType::Rounded)
         .padding(Padding::new(1u16, 1u16, 0, 0));
+    let inner = block.inner(area);
     frame.render_widget(Clear, area);
-    frame.render_widget(Paragraph::new(help).block(block), area);
+    frame.render_widget(block, area);
+    let areas = Layout::horizontal(widths.iter().map(|w| Constraint::Length(*w)))
+        .spacing(3)
+        .split(inner);
+    for (lines, area) in columns.into_iter().zip(areas.iter()) {
+        frame.render_widget(Paragraph::new(lines.join("\n")), *area);
+    }
 }
 
-fn render_table(state: &State, frame: &mut Frame, area: Rect) {
-    if let Some(result) = state
-        .cells
-        .current()
-        .and_then(|c| state.cells.all.get(&c.id))
-        .and_then(|cell| cell.result.as_ref())
-    {
-        frame.render_widget(Clear, area);
+/// Values of `count` rows from `offset` on, every batch has its own formatters
+fn format_rows(
+    batches: &[RecordBatch],
+    offset: usize,
+    count: usize,
+    format: &FormatConfig,
+) -> Result<Vec<Vec<String>>, ArrowError> {
+    let mut rows = Vec::new();
+    let mut skip = offset;
+    for batch in batches {
+        if rows.len() == count {
+            break;
+        }
+        if skip >= batch.num_rows() {
+            skip -= batch.num_rows();
+            continue;
+        }
+        let formatters = batch
+            .columns()
+            .iter()
+            .map(|c| ValueFormatter::try_new(c.as_ref(), format))
+            .collect::<Result<Vec<_>, ArrowError>>()?;
+        for i in (skip..batch.num_rows()).take(count - rows.len()) {
+            rows.push(formatters.iter().map(|f| f.value(i)).collect());
+        }
+        skip = 0;
+    }
+    Ok(rows)
+}
 
-        if result.is_empty() {
+fn render_table(state: &State, cell: &Cell, focused: bool, frame: &mut Frame, area: Rect) {
+    let Some(result) = &cell.result else {
+        return;
+    };
+    let theme = &state.ui.theme;
+    frame.render_widget(Clear, area);
+
+    if result.batches.is_empty() {
+        frame.render_widget(
+            Paragraph::new("SQL statement did not return any data"),
+            area,
+        );
+        return;
+    }
+
+    let view = &cell.table;
+    let batches = cell.table_batches();
+    let input_height = view.filter_editor.is_some() || view.search.is_some();
+    let [area, input_area] = Layout::vertical([
+        Constraint::Min(1),
+        Constraint::Length(u16::from(input_height)),
+    ])
+    .areas(area);
+
+    if let Some(editor) = &view.filter_editor {
+        let line = Line::from(vec![
+            Span::styled("filter: ", Style::new().fg(theme.muted)),
+            Span::raw(format!("{}█", editor.lines().join(""))),
+            Span::styled(
+                "  ↵ apply • Esc cancel • col=value col>value col~text",
+                Style::new().fg(theme.muted),
+            ),
+        ]);
+        frame.render_widget(Paragraph::new(line), input_area);
+    } else if let Some(search) = &view.search {
+        render_result_search(state, search, (view.row, view.column), frame, input_area);
+    }
+
+    if view.record {
+        render_record(state, cell, focused, frame, area);
+        return;
+    }
+
+    let schema = &result.schema;
+    let shown = cell.shown_columns();
+    let names = shown
+        .iter()
+        .map(|&i| {
+            let mut name = schema.field(i).name().clone();
+            if let Some((priority, key)) = view.sort_key(i) {
+                name.push_str(if key.descending { " ▼" } else { " ▲" });
+                if view.sort.len() > 1 {
+                    name.push_str(&(priority + 1).to_string());
+                }
+            }
+            name
+        })
+        .collect::<Vec<_>>();
+
+    let height = area.height.saturating_sub(1) as usize;
+    // the selected row is kept in sight
+    let offset = (view.row + 1).saturating_sub(height);
+    let values = match format_rows(batches, offset, height, &state.config.format) {
+        Ok(values) => values,
+        Err(err) => {
             frame.render_widget(
-                Paragraph::new("SQL statement did not return any data"),
+                Paragraph::new(err.to_string()).style(Style::new().fg(theme.error)),
                 area,
             );
             return;
         }
+    };
 
-        let max_rows = area.height as usize - 1;
+    // columns are as wide as the header and the values in sight, up to a limit
+    let widths = shown
+        .iter()
+        .zip(&names)
+        .map(|(&i, name)| {
+            let width = values.iter().map(|row| row[i].chars().count());
+            let width = width.chain([name.chars().count()]).max().unwrap_or(0);
+            width.min(RESULT_COLUMN_WIDTH) as u16
+        })
+        .collect::<Vec<_>>();
+    let selected = shown.iter().position(|c| *c == view.column).unwrap_or(0);
+    let layout = fit_columns(&widths, view.frozen.min(shown.len()), selected, area.width);
 
-        let batch = result.first().unwrap();
+    let separator = || widgets::Cell::from("│").style(Style::new().fg(theme.border));
+    let header = layout
+        .iter()
+        .map(|(position, _)| match position {
+            Some(p) => {
+                let cell = widgets::Cell::from(names[*p].clone());
+                if focused && shown[*p] == view.column {
+                    cell.style(Style::new().add_modifier(Modifier::UNDERLINED))
+                } else {
+                    cell
+                }
+            }
+            None => separator(),
+        })
+        .collect::<widgets::Row>()
+        .height(1)
+        .style(theme.header().add_modifier(Modifier::BOLD));
 
-        let header = batch
-            .schema()
-            .fields
-            .iter()
-            .map(|f| f.name().clone())
-            .map(widgets::Cell::from)
-            .collect::<widgets::Row>()
-            .height(1)
-            .style(Style::from((Color::Black, Color::Gray)).add_modifier(Modifier::BOLD));
+    let rows = values.into_iter().enumerate().map(|(i, mut values)| {
+        let index = offset + i;
+        let selected = focused && index == view.row;
+        let cells = layout.iter().map(|(position, _)| {
+            let Some(position) = position else {
+                return separator();
+            };
+            let column = shown[*position];
+            let mut style = Style::new();
+            if view
+                .search
+                .as_ref()
+                .is_some_and(|s| s.is_match(index, column))
+            {
+                style = style.patch(theme.search());
+            }
+            if selected && column == view.column {
+                style = style.add_modifier(Modifier::REVERSED);
+            }
+            widgets::Cell::from(std::mem::take(&mut values[column])).style(style)
+        });
+        let row = widgets::Row::new(cells.collect::<Vec<_>>());
+        if selected {
+            row.style(theme.selection())
+        } else if index % 2 == 1 {
+            row.style(Style::new().bg(theme.row_alt_bg))
+        } else {
+            row
+        }
+    });
 
-        let mut rows: Vec<widgets::Row> = vec![];
+    let widths = layout.iter().map(|(_, width)| Constraint::Length(*width));
+    let table = widgets::Table::new(rows, widths).header(header);
+    frame.render_widget(table, area);
+}
 
-        let formatters = batch
-            .columns()
+/// Widest a column of the result table gets, longer values are cut
+const RESULT_COLUMN_WIDTH: usize = 40;
+
+/// Columns which fit the width, by position among the shown ones with their widths.
+/// The frozen columns always come first, followed by a separator (`None`), then the
+/// other columns scrolled so the selected one is in sight.
+fn fit_columns(
+    widths: &[u16],
+    frozen: usize,
+    selected: usize,
+    width: u16,
+) -> Vec<(Option<usize>, u16)> {
+    let mut layout = (0..frozen)
+        .map(|p| (Some(p), widths[p]))
+        .collect::<Vec<_>>();
+    if frozen > 0 && frozen < widths.len() {
+        layout.push((None, 1));
+    }
+    // every column is followed by a space
+    let used = layout.iter().map(|(_, w)| w + 1).sum::<u16>();
+    let mut remaining = width.saturating_sub(used);
+
+    let mut start = frozen;
+    if selected > frozen {
+        start = selected;
+        let mut needed = widths[selected] + 1;
+        while start > frozen && needed + widths[start - 1] < remaining {
+            start -= 1;
+            needed += widths[start] + 1;
+        }
+    }
+    for (p, width) in widths.iter().enumerate().skip(start) {
+        if remaining == 0 {
+            break;
+        }
+        layout.push((Some(p), (*width).min(remaining)));
+        remaining = remaining.saturating_sub(width + 1);
+    }
+    layout
+}
+
+/// Longest a value gets in the record view before it is cut
+const RECORD_VALUE_LINES: usize = 8;
+
+/// The selected row transposed, one line per shown field with its type and value
+fn render_record(state: &State, cell: &Cell, focused: bool, frame: &mut Frame, area: Rect) {
+    let Some(schema) = cell.result.as_ref().map(|r| r.schema.clone()) else {
+        return;
+    };
+    let theme = &state.ui.theme;
+    let view = &cell.table;
+    let values = match format_rows(cell.table_batches(), view.row, 1, &state.config.format) {
+        Ok(mut rows) if !rows.is_empty() => rows.swap_remove(0),
+        Ok(_) => {
+            frame.render_widget(Paragraph::new("No rows to show"), area);
+            return;
+        }
+        Err(err) => {
+            frame.render_widget(
+                Paragraph::new(err.to_string()).style(Style::new().fg(theme.error)),
+                area,
+            );
+            return;
+        }
+    };
+
+    // fields follow the order of the table, hidden ones are left out
+    let shown = cell.shown_columns();
+    let names = shown
+        .iter()
+        .map(|&i| schema.field(i).name().clone())
+        .collect::<Vec<_>>();
+    let types = shown
+        .iter()
+        .map(|&i| schema.field(i).data_type().to_string())
+        .collect::<Vec<_>>();
+    let width = |texts: &[String], header: &str, max: usize| {
+        let width = texts.iter().map(|t| t.chars().count()).max().unwrap_or(0);
+        width.max(header.len()).min(max) as u16
+    };
+    let name_width = width(&names, "field", 32);
+    let type_width = width(&types, "type", 24);
+    let value_width = area
+        .width
+        .saturating_sub(name_width + type_width + 2)
+        .max(1) as usize;
+
+    // long values and values with line breaks take several lines
+    let values = shown
+        .iter()
+        .map(|&i| {
+            let mut lines = values[i]
+                .lines()
+                .flat_map(|line| {
+                    let chars = line.chars().collect::<Vec<_>>();
+                    let chunks = chars.chunks(value_width).map(String::from_iter);
+                    chunks.collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>();
+            if lines.len() > RECORD_VALUE_LINES {
+                lines.truncate(RECORD_VALUE_LINES);
+                lines[RECORD_VALUE_LINES - 1].push('…');
+            }
+            if lines.is_empty() {
+                lines.push(String::new());
+            }
+            lines
+        })
+        .collect::<Vec<_>>();
+
+    // the selected field is kept in sight, rows above it are dropped until it fits
+    let height = area.height.saturating_sub(1) as usize;
+    let selected = shown.iter().position(|c| *c == view.column).unwrap_or(0);
+    let mut offset = selected;
+    let mut used = values.get(selected).map_or(0, |v| v.len());
+    while offset > 0 && used + values[offset - 1].len() <= height {
+        offset -= 1;
+        used += values[offset].len();
+    }
+
+    let header = widgets::Row::new(["field", "type", "value"])
+        .style(theme.header().add_modifier(Modifier::BOLD));
+    let rows = names
+        .into_iter()
+        .zip(types)
+        .zip(values)
+        .enumerate()
+        .skip(offset)
+        .map(|(i, ((name, data_type), lines))| {
+            let mut style = Style::new();
+            if view
+                .search
+                .as_ref()
+                .is_some_and(|s| s.is_match(view.row, shown[i]))
+            {
+                style = style.patch(theme.search());
+            }
+            if focused && i == selected {
+                style = style.add_modifier(Modifier::REVERSED);
+            }
+            let height = lines.len() as u16;
+            widgets::Row::new([
+                widgets::Cell::from(name).style(Style::new().add_modifier(Modifier::BOLD)),
+                widgets::Cell::from(data_type).style(Style::new().fg(theme.muted)),
+                widgets::Cell::from(lines.join("\n")).style(style),
+            ])
+            .height(height)
+        });
+    let widths = [
+        Constraint::Length(name_width),
+        Constraint::Length(type_width),
+        Constraint::Fill(1),
+    ];
+    frame.render_widget(widgets::Table::new(rows, widths).header(header), area);
+}
+
+/// Schema changes and differing rows of two results, changed values show both sides
+fn render_diff(state: &State, view: &DiffView, focused: bool, frame: &mut Frame, area: Rect) {
+    let theme = &state.ui.theme;
+    let muted = Style::new().fg(theme.muted);
+    frame.render_widget(Clear, area);
+
+    let diff = match &view.diff {
+        Ok(diff) => diff,
+        Err(err) => {
+            let text = format!("Cannot compare the results: {}", err);
+            let paragraph = Paragraph::new(text)
+                .style(Style::new().fg(theme.error))
+                .wrap(Wrap::default());
+            frame.render_widget(paragraph, area);
+            return;
+        }
+    };
+    let schema = diff
+        .schema
+        .iter()
+        .map(|change| match change {
+            SchemaChange::Added(name, data_type) => Line::styled(
+                format!("+ column {} {}", name, data_type),
+                Style::new().fg(theme.added),
+            ),
+            SchemaChange::Removed(name, data_type) => Line::styled(
+                format!("- column {} {}", name, data_type),
+                Style::new().fg(theme.removed),
+            ),
+            SchemaChange::TypeChanged {
+                name,
+                before,
+                after,
+                comparable,
+            } => Line::from(vec![
+                Span::styled(
+                    format!("~ column {} {} → {}", name, before, after),
+                    Style::new().fg(theme.changed),
+                ),
+                Span::styled(
+                    match comparable {
+                        true => "",
+                        false => "  values cannot be converted, not compared",
+                    },
+                    muted,
+                ),
+            ]),
+        })
+        .collect::<Vec<_>>();
+    let [title_area, schema_area, table_area, input_area] = Layout::vertical([
+        Constraint::Length(2),
+        Constraint::Length(schema.len() as u16),
+        Constraint::Min(1),
+        Constraint::Length(u16::from(view.key_editor.is_some())),
+    ])
+    .areas(area);
+
+    let key = match diff.key {
+        0 => format!(
+            "whole rows, {} to set key columns",
+            state.ui.keymap.keys(Action::DiffKey)
+        ),
+        key => format!("keyed by {}", diff.columns[..key].join(", ")),
+    };
+    let count = |kind: fn(&RowDiff) -> bool| diff.rows.iter().filter(|row| kind(row)).count();
+    let title = vec![
+        Line::from(vec![
+            Span::styled(
+                format!("{} → {}", view.before_label, view.after_label),
+                Style::new().add_modifier(Modifier::BOLD),
+            ),
+            Span::styled(format!("  {}", key), muted),
+        ]),
+        Line::from(vec![
+            Span::styled(
+                format!("+{} added", count(|r| matches!(r, RowDiff::Added(_)))),
+                Style::new().fg(theme.added),
+            ),
+            Span::raw(" • "),
+            Span::styled(
+                format!("-{} removed", count(|r| matches!(r, RowDiff::Removed(_)))),
+                Style::new().fg(theme.removed),
+            ),
+            Span::raw(" • "),
+            Span::styled(
+                format!(
+                    "~{} changed",
+                    count(|r| matches!(r, RowDiff::Changed { .. }))
+                ),
+                Style::new().fg(theme.changed),
+            ),
+            Span::styled(format!(" • {} unchanged", diff.unchanged), muted),
+        ]),
+    ];
+    frame.render_widget(Paragraph::new(title), title_area);
+    frame.render_widget(Paragraph::new(schema), schema_area);
+
+    if let Some(editor) = &view.key_editor {
+        let line = Line::from(vec![
+            Span::styled("key columns: ", muted),
+            Span::raw(format!("{}█", editor.lines().join(""))),
+            Span::styled("  ↵ apply • Esc cancel • id, day", muted),
+        ]);
+        frame.render_widget(Paragraph::new(line), input_area);
+    }
+
+    if diff.rows.is_empty() {
+        let text = if diff.schema.is_empty() {
+            "The results are the same"
+        } else {
+            "Rows are the same in the columns of both results"
+        };
+        frame.render_widget(Paragraph::new(text), table_area);
+        return;
+    }
+
+    let height = table_area.height.saturating_sub(1) as usize;
+    // the selected row is kept in sight
+    let offset = (view.selected + 1).saturating_sub(height);
+    let format = &state.config.format;
+    let row_values = |batch: &RecordBatch, row: usize| {
+        format_rows(std::slice::from_ref(batch), row, 1, format)
+            .ok()
+            .and_then(|mut rows| rows.pop())
+            .unwrap_or_default()
+    };
+    let rows = diff
+        .rows
+        .iter()
+        .skip(offset)
+        .take(height)
+        .map(|row| match row {
+            RowDiff::Added(i) => {
+                let values = row_values(&diff.after, *i);
+                ("+", values, Vec::new(), Style::new().fg(theme.added))
+            }
+            RowDiff::Removed(i) => {
+                let values = row_values(&diff.before, *i);
+                ("-", values, Vec::new(), Style::new().fg(theme.removed))
+            }
+            RowDiff::Changed {
+                before,
+                after,
+                columns,
+            } => {
+                let old = row_values(&diff.before, *before);
+                let mut values = row_values(&diff.after, *after);
+                for column in columns {
+                    if let (Some(value), Some(old)) = (values.get_mut(*column), old.get(*column)) {
+                        *value = format!("{} → {}", old, value);
+                    }
+                }
+                ("~", values, columns.clone(), Style::new())
+            }
+        })
+        .collect::<Vec<_>>();
+
+    // columns are as wide as the header and the values in sight, up to a limit
+    let widths = diff
+        .columns
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            let width = rows.iter().filter_map(|(_, values, _, _)| values.get(i));
+            let width = width
+                .map(|v| v.chars().count())
+                .chain([name.chars().count()]);
+            Constraint::Length(width.max().unwrap_or(0).min(RESULT_COLUMN_WIDTH) as u16)
+        })
+        .collect::<Vec<_>>();
+    let header = [widgets::Cell::from("")]
+        .into_iter()
+        .chain(diff.columns.iter().enumerate().map(|(i, name)| {
+            let cell = widgets::Cell::from(name.clone());
+            // key columns are underlined
+            if i < diff.key {
+                cell.style(Style::new().add_modifier(Modifier::UNDERLINED))
+            } else {
+                cell
+            }
+        }))
+        .collect::<widgets::Row>()
+        .style(theme.header().add_modifier(Modifier::BOLD));
+    let rows = rows
+        .into_iter()
+        .enumerate()
+        .map(|(i, (marker, values, changed, style))| {
+            let cells = values.into_iter().enumerate().map(|(column, value)| {
+                let cell = widgets::Cell::from(value);
+                if changed.contains(&column) {
+                    cell.style(Style::new().fg(theme.changed))
+                } else {
+                    cell
+                }
+            });
+            let marker = widgets::Cell::from(marker).style(match marker {
+                "~" => Style::new().fg(theme.changed),
+                _ => style,
+            });
+            let row = widgets::Row::new([marker].into_iter().chain(cells));
+            if focused && offset + i == view.selected {
+                row.style(style.patch(theme.selection()))
+            } else {
+                row.style(style)
+            }
+        });
+    let widths = [Constraint::Length(1)].into_iter().chain(widths);
+    frame.render_widget(widgets::Table::new(rows, widths).header(header), table_area);
+}
+
+/// Runs of the cell with the code and the result or error of the selected one
+fn render_history(state: &State, cell: &Cell, frame: &mut Frame, area: Rect) {
+    let theme = &state.ui.theme;
+    frame.render_widget(Clear, area);
+    let [list_area, detail_area] = Layout::horizontal([
+        Constraint::Length(40.min(area.width / 2)),
+        Constraint::Min(1),
+    ])
+    .spacing(2)
+    .areas(area);
+
+    let height = list_area.height.saturating_sub(1) as usize;
+    // the selected run is kept in sight
+    let offset = (cell.selected_run + 1).saturating_sub(height);
+    let rows = cell
+        .runs
+        .iter()
+        .enumerate()
+        .skip(offset)
+        .take(height)
+        .map(|(i, run)| {
+            let outcome = match run.outcome {
+                Ok(rows) => widgets::Cell::from(format!("{} rows", rows)),
+                Err(_) => widgets::Cell::from("failed").style(Style::new().fg(theme.error)),
+            };
+            let row = widgets::Row::new([
+                widgets::Cell::from(run.finished_at.format("%H:%M:%S").to_string()),
+                widgets::Cell::from(run.duration.map(format_duration).unwrap_or_default()),
+                outcome,
+            ]);
+            if i == cell.selected_run {
+                row.style(theme.selection())
+            } else {
+                row
+            }
+        });
+    let header = widgets::Row::new(["finished", "took", "result"])
+        .style(theme.header().add_modifier(Modifier::BOLD));
+    let widths = [
+        Constraint::Length(8),
+        Constraint::Length(8),
+        Constraint::Min(6),
+    ];
+    frame.render_widget(widgets::Table::new(rows, widths).header(header), list_area);
+
+    let Some(run) = cell.runs.get(cell.selected_run) else {
+        return;
+    };
+    let code = run.code.as_deref().unwrap_or_default();
+    let code_height = code.lines().count().min(8) as u16;
+    let [title_area, code_area, result_area] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Length(code_height + 1),
+        Constraint::Min(1),
+    ])
+    .areas(detail_area);
+    let mut title = vec![Span::styled(
+        format!("Run at {}", run.finished_at.format("%Y-%m-%d %H:%M:%S")),
+        Style::new().add_modifier(Modifier::BOLD),
+    )];
+    if run.code != cell.code {
+        title.push(Span::styled(
+            "  code differs from the cell",
+            Style::new().fg(theme.muted),
+        ));
+    }
+    frame.render_widget(Paragraph::new(Line::from(title)), title_area);
+    frame.render_widget(Paragraph::new(code), code_area);
+
+    let batches = match (&run.outcome, &run.result) {
+        (Err(err), _) => {
+            let paragraph = Paragraph::new(err.as_str())
+                .style(Style::new().fg(theme.error))
+                .wrap(Wrap::default());
+            frame.render_widget(paragraph, result_area);
+            return;
+        }
+        (Ok(_), Some(result)) => &result.batches,
+        (Ok(rows), None) => {
+            let paragraph = Paragraph::new(format!(
+                "{} rows. Only the results of the latest runs are kept, see `run_results` in the config",
+                rows
+            ))
+            .style(Style::new().fg(theme.muted))
+            .wrap(Wrap::default());
+            frame.render_widget(paragraph, result_area);
+            return;
+        }
+    };
+    let Some(schema) = batches.first().map(|b| b.schema()) else {
+        frame.render_widget(
+            Paragraph::new("SQL statement did not return any data"),
+            result_area,
+        );
+        return;
+    };
+    let height = result_area.height.saturating_sub(1) as usize;
+    let values = format_rows(batches, 0, height, &state.config.format).unwrap_or_default();
+    let names = schema
+        .fields()
+        .iter()
+        .map(|f| f.name().clone())
+        .collect::<Vec<_>>();
+    // columns are as wide as the header and the first values, up to a limit
+    let widths = names
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            let width = values.iter().map(|row| row[i].chars().count());
+            let width = width.chain([name.chars().count()]).max().unwrap_or(0);
+            Constraint::Length(width.min(RESULT_COLUMN_WIDTH) as u16)
+        })
+        .collect::<Vec<_>>();
+    let header = widgets::Row::new(names).style(theme.header().add_modifier(Modifier::BOLD));
+    let rows = values.into_iter().map(widgets::Row::new);
+    frame.render_widget(
+        widgets::Table::new(rows, widths).header(header),
+        result_area,
+    );
+}
+
+/// Query with the number of the selected match, or why the regular expression is invalid
+fn render_result_search(
+    state: &State,
+    search: &ResultSearch,
+    selected: (usize, usize),
+    frame: &mut Frame,
+    area: Rect,
+) {
+    let theme = &state.ui.theme;
+    let muted = Style::new().fg(theme.muted);
+    let prefix = if search.regex {
+        "search (regex): "
+    } else {
+        "search: "
+    };
+    let cursor = if search.editing { "█" } else { "" };
+    let mut spans = vec![
+        Span::styled(prefix, muted),
+        Span::raw(format!("{}{}", search.query_text(), cursor)),
+    ];
+    let count = match search.matches.iter().position(|m| *m == selected) {
+        _ if search.query_text().is_empty() => String::new(),
+        _ if search.error.is_some() => "invalid regex".to_string(),
+        _ if search.matches.is_empty() => "no matches".to_string(),
+        Some(i) => format!("{}/{}", i + 1, search.matches.len()),
+        None => format!("{} matches", search.matches.len()),
+    };
+    let style = if search.error.is_some() {
+        Style::new().fg(theme.error)
+    } else {
+        muted
+    };
+    spans.push(Span::styled(format!("  {}", count), style));
+    let hint = if search.editing {
+        "  ↵ done • ctrl+r regex • Esc clear"
+    } else {
+        "  n next • N previous"
+    };
+    spans.push(Span::styled(hint, muted));
+    frame.render_widget(Paragraph::new(Line::from(spans)), area);
+}
+
+const SERIES_COLORS: [Color; 6] = [
+    Color::Cyan,
+    Color::Yellow,
+    Color::Green,
+    Color::Magenta,
+    Color::Blue,
+    Color::Red,
+];
+
+/// Labels spread over the range, with the first and the last at its ends
+fn axis_labels(
+    min: f64,
+    max: f64,
+    count: usize,
+    label: impl Fn(f64) -> String,
+) -> Vec<Span<'static>> {
+    let steps = count.saturating_sub(1).max(1) as f64;
+    (0..count)
+        .map(|i| Span::raw(label(min + (max - min) * i as f64 / steps)))
+        .collect()
+}
+
+fn render_chart(
+    state: &State,
+    columns: &ChartColumns,
+    data: &Result<ChartData, String>,
+    result: &[RecordBatch],
+    focused: bool,
+    frame: &mut Frame,
+    area: Rect,
+) {
+    let theme = &state.ui.theme;
+    frame.render_widget(Clear, area);
+    let schema = match result.first() {
+        Some(batch) => batch.schema(),
+        None => return,
+    };
+    let name = |i: usize| schema.field(i).name().clone();
+    let mut title = format!(
+        " {} of {} by {}",
+        columns.kind.name(),
+        name(columns.y),
+        name(columns.x)
+    );
+    if let Some(series) = columns.series {
+        title.push_str(&format!(" per {}", name(series)));
+    }
+    title.push(' ');
+    let block = Block::default()
+        .title(title)
+        .borders(Borders::TOP)
+        .border_style(if focused {
+            Style::new()
+        } else {
+            Style::new().fg(theme.border)
+        });
+
+    let data = match data {
+        Ok(data) if data.series.iter().all(|s| s.points.is_empty()) => {
+            let paragraph = Paragraph::new("No values to chart").block(block);
+            frame.render_widget(paragraph, area);
+            return;
+        }
+        Ok(data) => data,
+        Err(err) => {
+            let paragraph = Paragraph::new(err.as_str())
+                .style(Style::new().fg(theme.error))
+                .wrap(Wrap::default())
+                .block(block);
+            frame.render_widget(paragraph, area);
+            return;
+        }
+    };
+
+    if columns.kind == ChartKind::Bar {
+        // points of bar charts are summed up per x and ordered by it
+        let total = |series: &Series, x: f64| {
+            let i = series.points.binary_search_by(|(px, _)| px.total_cmp(&x));
+            i.ok().map(|i| series.points[i].1)
+        };
+        let mut groups = Vec::new();
+        let mut labels = data
+            .series
+            .iter()
+            .flat_map(|s| s.points.iter().map(|(x, _)| *x))
+            .collect::<Vec<_>>();
+        labels.sort_by(f64::total_cmp);
+        labels.dedup();
+        // bars take integers, values are scaled and shown as they are
+        let max = data
+            .series
             .iter()
-            .map(|c| ArrayFormatter::try_new(c.as_ref(), &FormatOptions::default()))
-            .collect::<anyhow::Result<Vec<_>, ArrowError>>()
-            .unwrap();
+            .flat_map(|s| s.points.iter().map(|(_, y)| *y))
+            .fold(0.0, |max: f64, y| max.max(y.abs()));
+        let scale = if max > 0.0 { 1000.0 / max } else { 0.0 };
+        for x in labels {
+            let bars = data
+                .series
+                .iter()
+                .enumerate()
+                .filter_map(|(i, s)| {
+                    let y = total(s, x)?;
+                    Some(
+                        Bar::default()
+                            .value((y.max(0.0) * scale) as u64)
+                            .text_value(format_number(y))
+                            .style(Style::new().fg(SERIES_COLORS[i % SERIES_COLORS.len()])),
+                    )
+                })
+                .collect::<Vec<_>>();
+            groups.push(
+                BarGroup::default()
+                    .label(data.x_axis.label(x, &state.config.format).into())
+                    .bars(&bars),
+            );
+        }
+        let bars_per_group = data.series.len().max(1) as u16;
+        let groups_count = groups.len().max(1) as u16;
+        let width = (area.width / groups_count / bars_per_group)
+            .saturating_sub(1)
+            .clamp(1, 12);
+        let mut chart = BarChart::default()
+            .block(block)
+            .bar_width(width)
+            .bar_gap(0)
+            .group_gap(1);
+        for group in groups {
+            chart = chart.data(group);
+        }
+        frame.render_widget(chart, area);
+        return;
+    }
+
+    let points = data.series.iter().flat_map(|s| s.points.iter());
+    let (x_min, x_max, y_min, y_max) = points.fold(
+        (f64::MAX, f64::MIN, f64::MAX, f64::MIN),
+        |(x_min, x_max, y_min, y_max), (x, y)| {
+            (x_min.min(*x), x_max.max(*x), y_min.min(*y), y_max.max(*y))
+        },
+    );
+    // a single value still needs a range to be drawn
+    let (x_min, x_max) = if x_min < x_max {
+        (x_min, x_max)
+    } else {
+        (x_min - 1.0, x_max + 1.0)
+    };
+    let (y_min, y_max) = if y_min < y_max {
+        (y_min, y_max)
+    } else {
+        (y_min - 1.0, y_max + 1.0)
+    };
+
+    let graph_type = match columns.kind {
+        ChartKind::Scatter => GraphType::Scatter,
+        _ => GraphType::Line,
+    };
+    let datasets = data
+        .series
+        .iter()
+        .enumerate()
+        .map(|(i, s)| {
+            Dataset::default()
+                .name(s.name.clone())
+                .marker(Marker::Braille)
+                .graph_type(graph_type)
+                .style(Style::new().fg(SERIES_COLORS[i % SERIES_COLORS.len()]))
+                .data(&s.points)
+        })
+        .collect::<Vec<_>>();
+    let x_axis = Axis::default()
+        .bounds([x_min, x_max])
+        .labels(axis_labels(x_min, x_max, 3, |x| {
+            data.x_axis.label(x, &state.config.format)
+        }))
+        .style(Style::new().fg(theme.muted));
+    let y_axis = Axis::default()
+        .bounds([y_min, y_max])
+        .labels(axis_labels(y_min, y_max, 5, format_number))
+        .style(Style::new().fg(theme.muted));
+    let chart = Chart::new(datasets)
+        .block(block)
+        .x_axis(x_axis)
+        .y_axis(y_axis)
+        .hidden_legend_constraints((Constraint::Ratio(1, 2), Constraint::Ratio(1, 2)));
+    frame.render_widget(chart, area);
+}
+
+fn render_settings(panel: &SettingsPanel, theme: &Theme, frame: &mut Frame) {
+    let outer = frame.area();
+    let area = centered_area(
+        outer,
+        outer.width.saturating_sub(8).min(140),
+        outer.height.saturating_sub(4),
+    );
+    let block = Block::default()
+        .title(" Session settings ")
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .padding(Padding::new(1, 1, 0, 0));
+    let inner = block.inner(area);
+    frame.render_widget(Clear, area);
+    frame.render_widget(block, area);
+
+    let layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(vec![
+            Constraint::Length(1),
+            Constraint::Min(1),
+            Constraint::Length(4),
+            Constraint::Length(1),
+        ])
+        .split(inner);
+
+    let filter = panel.filter_text();
+    let filter_line = if panel.filtering {
+        Line::from(vec![Span::raw("/"), Span::raw(filter), Span::raw("█")])
+    } else if filter.is_empty() {
+        Line::from(Span::styled(
+            "press / to filter",
+            Style::new().fg(theme.muted),
+        ))
+    } else {
+        Line::from(format!("/{}", filter))
+    };
+    frame.render_widget(Paragraph::new(filter_line), layout[0]);
+
+    let visible = panel.visible();
+    let height = layout[1].height as usize;
+    let offset = (panel.selected + 1).saturating_sub(height);
+    let key_width = visible.iter().map(|s| s.key.len()).max().unwrap_or(0) as u16;
+    let rows = visible
+        .iter()
+        .enumerate()
+        .skip(offset)
+        .take(height)
+        .map(|(i, setting)| {
+            let value = match (&panel.editor, i == panel.selected) {
+                (Some(editor), true) => format!("{}█", editor.lines().join("")),
+                _ => setting.value.clone().unwrap_or_else(|| "NULL".to_string()),
+            };
+            let row = widgets::Row::new(vec![setting.key.clone(), value]);
+            if i == panel.selected {
+                row.style(theme.selection())
+            } else {
+                row
+            }
+        })
+        .collect::<Vec<_>>();
+    let table = widgets::Table::new(rows, [Constraint::Length(key_width), Constraint::Fill(1)])
+        .column_spacing(2);
+    frame.render_widget(table, layout[1]);
+
+    let description = panel
+        .selected_entry()
+        .map(|s| s.description.clone())
+        .unwrap_or_default();
+    frame.render_widget(
+        Paragraph::new(description).wrap(Wrap::default()).block(
+            Block::default()
+                .borders(Borders::TOP)
+                .border_style(theme.separator),
+        ),
+        layout[2],
+    );
+
+    let footer = if let Some(error) = &panel.error {
+        Span::styled(error.clone(), Style::new().fg(theme.error))
+    } else if let Some(message) = &panel.message {
+        Span::raw(message.clone())
+    } else if panel.editor.is_some() {
+        Span::styled("↵ apply • Esc cancel", Style::new().fg(theme.muted))
+    } else {
+        Span::styled(
+            "↵ edit • / filter • w save changed settings to config • Esc close",
+            Style::new().fg(theme.muted),
+        )
+    };
+    frame.render_widget(Paragraph::new(footer), layout[3]);
+}
+
+/// Query and replacement fields with the number of the match under the cursor
+fn render_search(state: &State, search: &Search, frame: &mut Frame, area: Rect) {
+    let theme = &state.ui.theme;
+    let matches = search.matches(&state.cells);
+    let (row, col) = state.cells.editor.cursor();
+    let cursor = (state.cells.current_cell_index().unwrap_or(0), row, col);
+    let count = match matches.iter().position(|m| m.position == cursor) {
+        _ if search.query_text().is_empty() => String::new(),
+        _ if matches.is_empty() => "no matches".to_string(),
+        Some(i) => format!("{}/{}", i + 1, matches.len()),
+        None => format!("{} matches", matches.len()),
+    };
+    let field = |prefix: &str, text: String, focused: bool| {
+        let cursor = if focused { "█" } else { "" };
+        Line::from(vec![
+            Span::styled(prefix.to_string(), Style::new().fg(theme.muted)),
+            Span::raw(format!("{}{}", text, cursor)),
+        ])
+    };
+    let mut query = field("find: ", search.query_text(), !search.replacement_focused);
+    query.push_span(Span::styled(
+        format!("  {}", count),
+        Style::new().fg(theme.muted),
+    ));
+    let mut lines = vec![query];
+    if search.replacing {
+        let hint = if search.replacement_focused {
+            "  ↵ replace • ctrl+a replace all"
+        } else {
+            "  tab to replace"
+        };
+        let mut line = field(
+            "with: ",
+            search.replacement_text(),
+            search.replacement_focused,
+        );
+        line.push_span(Span::styled(hint, Style::new().fg(theme.muted)));
+        lines.push(line);
+    }
+    frame.render_widget(Paragraph::new(lines), area);
+}
+
+fn render_palette(state: &State, palette: &Palette, frame: &mut Frame) {
+    let theme = &state.ui.theme;
+    let outer = frame.area();
+    let width = outer.width.saturating_sub(8).min(80);
+    let height = outer.height.saturating_sub(4).min(20);
+    let [area] = Layout::horizontal([Constraint::Length(width)])
+        .flex(Flex::Center)
+        .areas(outer);
+    let area = Rect {
+        y: area.y + 2,
+        height,
+        ..area
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .padding(Padding::new(1, 1, 0, 0));
+    let inner = block.inner(area);
+    frame.render_widget(Clear, area);
+    frame.render_widget(block, area);
+
+    let [input, list] = Layout::vertical([Constraint::Length(1), Constraint::Min(1)]).areas(inner);
+    let query = palette.query_text();
+    let input_line = if query.is_empty() {
+        Line::from(vec![
+            Span::raw("> █"),
+            Span::styled("cell or command", Style::new().fg(theme.muted)),
+        ])
+    } else {
+        Line::from(format!("> {}█", query))
+    };
+    frame.render_widget(Paragraph::new(input_line), input);
 
-        for batch in result.iter() {
-            if rows.len() == max_rows {
-                break;
+    let entries = palette.entries(state);
+    let height = list.height as usize;
+    let offset = (palette.selected + 1).saturating_sub(height);
+    let rows = entries
+        .iter()
+        .enumerate()
+        .skip(offset)
+        .take(height)
+        .map(|(i, entry)| {
+            let row = widgets::Row::new(vec![
+                Line::from(entry.label.clone()),
+                Line::from(entry.detail.clone())
+                    .alignment(Alignment::Right)
+                    .style(Style::new().fg(theme.muted)),
+            ]);
+            if i == palette.selected {
+                row.style(theme.selection())
+            } else {
+                row
             }
-            for i in 0..batch.num_rows() {
-                if rows.len() == max_rows {
-                    break;
+        })
+        .collect::<Vec<_>>();
+    let table =
+        widgets::Table::new(rows, [Constraint::Fill(1), Constraint::Length(16)]).column_spacing(2);
+    frame.render_widget(table, list);
+}
+
+/// Status glyph, name and preview of every cell, the current one selected
+fn render_sidebar(state: &State, frame: &mut Frame, area: Rect) {
+    let theme = &state.ui.theme;
+    let block = Block::default()
+        .borders(Borders::RIGHT)
+        .border_style(theme.separator);
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let height = inner.height as usize;
+    let selected = state.cells.current_cell_index().unwrap_or(0);
+    // the selected cell is kept in the middle once the list does not fit
+    let offset = selected
+        .saturating_sub(height / 2)
+        .min(state.cells.order.len().saturating_sub(height));
+    let lines = state
+        .cells
+        .order
+        .iter()
+        .enumerate()
+        .skip(offset)
+        .take(height)
+        .filter_map(|(i, id)| state.cells.all.get(id).map(|cell| (i, cell)))
+        .map(|(i, cell)| {
+            let glyph = match cell.status {
+                _ if cell.kind == CellKind::Markdown => {
+                    Span::styled("¶", Style::new().fg(theme.muted))
                 }
+                _ if cell.is_stale() => Span::styled("~", Style::new().fg(theme.warm)),
+                CellStatus::Clean => Span::styled("·", Style::new().fg(theme.muted)),
+                CellStatus::Running => Span::raw(SPINNER[state.tick % SPINNER.len()]),
+                CellStatus::Finished => Span::raw("✓"),
+                CellStatus::Failed => Span::styled("✗", Style::new().fg(theme.error)),
+            };
+            let mut spans = vec![glyph, Span::raw(format!(" {:>2} ", i + 1))];
+            if let Some(name) = cell.name() {
+                spans.push(Span::styled(
+                    format!("{} ", name),
+                    Style::new().add_modifier(Modifier::BOLD),
+                ));
+            }
+            spans.push(Span::styled(
+                cell.preview().to_string(),
+                Style::new().fg(theme.muted),
+            ));
+            let line = Line::from(spans);
+            if i == selected {
+                line.style(theme.selection())
+            } else {
+                line
+            }
+        })
+        .collect::<Vec<_>>();
+    frame.render_widget(Paragraph::new(lines), inner);
+}
+
+fn render_progress(state: &State, cell: &Cell, frame: &mut Frame, area: Rect) {
+    let spinner = SPINNER[state.tick % SPINNER.len()];
+    let elapsed = cell
+        .started_at
+        .map(|s| format_duration(s.elapsed()))
+        .unwrap_or_default();
+
+    let mut lines = vec![Line::from(format!("{} Running {}", spinner, elapsed))];
+    if let Some(progress) = &cell.progress {
+        lines.push(Line::from(""));
+        lines.push(Line::from(format!(
+            "{} rows • {} batches",
+            progress.rows, progress.batches
+        )));
+        lines.push(Line::from(format!(
+            "{} reserved",
+            format_bytes(progress.memory_reserved)
+        )));
+    }
+
+    let width = lines.iter().map(|l| l.width()).max().unwrap_or_default() as u16;
+    let area = centered_area(area, width, lines.len() as u16);
+    frame.render_widget(Paragraph::new(lines), area);
+}
+
+fn format_nanos(nanos: usize) -> String {
+    let nanos = nanos as f64;
+    if nanos >= 1e9 {
+        format!("{:.2}s", nanos / 1e9)
+    } else if nanos >= 1e6 {
+        format!("{:.2}ms", nanos / 1e6)
+    } else if nanos >= 1e3 {
+        format!("{:.2}µs", nanos / 1e3)
+    } else {
+        format!("{}ns", nanos)
+    }
+}
 
-                let mut cells = Vec::new();
-                for formatter in &formatters {
-                    cells.push(formatter.value(i).to_string());
+fn render_plan(view: &PlanView, focused: bool, theme: &Theme, frame: &mut Frame, area: Rect) {
+    frame.render_widget(Clear, area);
+
+    let layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(vec![
+            Constraint::Length(1),
+            Constraint::Min(1),
+            Constraint::Length(8),
+        ])
+        .split(area);
+
+    let title = if view.plan.analyzed {
+        "EXPLAIN ANALYZE"
+    } else {
+        "EXPLAIN"
+    };
+    let tabs = Tabs::new(vec!["Physical", "Logical"])
+        .select(match view.tab {
+            PlanTab::Physical => 0,
+            PlanTab::Logical => 1,
+        })
+        .highlight_style(Style::new().add_modifier(Modifier::BOLD | Modifier::REVERSED))
+        .padding(" ", " ")
+        .divider("|");
+    let tabs_layout =
+        Layout::horizontal([Constraint::Min(1), Constraint::Length(16)]).split(layout[0]);
+    frame.render_widget(tabs, tabs_layout[0]);
+    frame.render_widget(
+        Paragraph::new(title)
+            .alignment(Alignment::Right)
+            .style(Style::new().fg(theme.muted)),
+        tabs_layout[1],
+    );
+
+    // share of the total compute time is used to highlight hot spots
+    let total = view.root().total_elapsed_compute();
+    let nodes = view.visible_nodes();
+    let height = layout[1].height as usize;
+    let offset = (view.selected + 1).saturating_sub(height);
+
+    let lines = nodes
+        .iter()
+        .enumerate()
+        .skip(offset)
+        .take(height)
+        .map(|(i, (path, node))| {
+            let marker = if node.children.is_empty() {
+                "  "
+            } else if view.is_collapsed(path) {
+                "▸ "
+            } else {
+                "▾ "
+            };
+            let mut spans = vec![
+                Span::raw("  ".repeat(path.len())),
+                Span::raw(marker),
+                Span::styled(node.name.clone(), Style::new().add_modifier(Modifier::BOLD)),
+            ];
+            if let Some(metrics) = &node.metrics {
+                if let Some(rows) = metrics.output_rows {
+                    spans.push(Span::raw(format!(" rows={}", rows)));
+                }
+                if let Some(elapsed) = metrics.elapsed_compute {
+                    let share = if total > 0 {
+                        elapsed as f64 / total as f64
+                    } else {
+                        0.0
+                    };
+                    let color = if share >= 0.5 {
+                        theme.hot
+                    } else if share >= 0.2 {
+                        theme.warm
+                    } else {
+                        Color::Reset
+                    };
+                    spans.push(Span::styled(
+                        format!(" time={} ({:.0}%)", format_nanos(elapsed), share * 100.0),
+                        Style::new().fg(color),
+                    ));
                 }
-                let bg = if i % 2 == 0 {
-                    Color::Reset
+            }
+            spans.push(Span::styled(
+                format!(" {}", node.details),
+                Style::new().fg(theme.muted),
+            ));
+
+            let mut line = Line::from(spans);
+            if i == view.selected {
+                let style = if focused {
+                    theme.selection()
                 } else {
-                    Color::White
+                    Style::new().add_modifier(Modifier::REVERSED)
                 };
-                let table_row = widgets::Row::new(cells).style(Style::new().bg(bg));
-                rows.push(table_row);
+                line = line.style(style);
             }
+            line
+        })
+        .collect::<Vec<_>>();
+    frame.render_widget(Paragraph::new(lines), layout[1]);
+
+    if let Some((_, node)) = view.selected_node() {
+        let mut lines = vec![Line::from(Span::styled(
+            node.name.clone(),
+            Style::new().add_modifier(Modifier::BOLD),
+        ))];
+        if !node.details.is_empty() {
+            lines.push(Line::from(node.details.clone()));
+        }
+        if let Some(partitioning) = &node.partitioning {
+            lines.push(Line::from(format!("partitioning: {}", partitioning)));
+        }
+        if let Some(metrics) = &node.metrics {
+            let all = metrics
+                .all
+                .iter()
+                .map(|(name, value)| format!("{}={}", name, value))
+                .collect::<Vec<_>>()
+                .join(", ");
+            lines.push(Line::from(format!("metrics: {}", all)));
         }
+        let block = Block::default()
+            .borders(Borders::TOP)
+            .border_style(theme.separator);
+        frame.render_widget(
+            Paragraph::new(lines).block(block).wrap(Wrap::default()),
+            layout[2],
+        );
+    }
+}
+
+fn format_scalar(value: &ScalarValue, format: &FormatConfig) -> String {
+    value
+        .to_array()
+        .ok()
+        .and_then(|array| match &format.timezone {
+            Some(timezone) => localize_array(&array, timezone).ok(),
+            None => Some(array),
+        })
+        .and_then(|array| {
+            let formatter = ValueFormatter::try_new(array.as_ref(), format).ok()?;
+            Some(formatter.value(0))
+        })
+        .unwrap_or_else(|| value.to_string())
+}
+
+/// Statistics of all columns with top values and the histogram of the selected one
+fn render_profile(state: &State, view: &ProfileView, focused: bool, frame: &mut Frame, area: Rect) {
+    let theme = &state.ui.theme;
+    let format = &state.config.format;
+    let profile = &view.profile;
+    frame.render_widget(Clear, area);
+    let [title, table, detail] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Min(3),
+        Constraint::Length(10),
+    ])
+    .areas(area);
+
+    frame.render_widget(
+        Paragraph::new(format!(
+            "{} rows • {} columns",
+            profile.rows,
+            profile.columns.len()
+        ))
+        .style(Style::new().add_modifier(Modifier::BOLD)),
+        title,
+    );
+
+    let optional = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+    let header_names = [
+        "column", "type", "nulls", "distinct", "min", "max", "mean", "stddev",
+    ];
+    let header = header_names
+        .into_iter()
+        .map(widgets::Cell::from)
+        .collect::<widgets::Row>()
+        .style(theme.header().add_modifier(Modifier::BOLD));
+    let cells = profile
+        .columns
+        .iter()
+        .map(|column| {
+            let nulls = match profile.rows {
+                0 => "0".to_string(),
+                rows => format!(
+                    "{} ({:.1}%)",
+                    column.nulls,
+                    column.nulls as f64 * 100.0 / rows as f64
+                ),
+            };
+            vec![
+                column.name.clone(),
+                column.data_type.to_string(),
+                nulls,
+                optional(column.distinct.map(|d| format!("~{}", d))),
+                optional(column.min.as_ref().map(|v| format_scalar(v, format))),
+                optional(column.max.as_ref().map(|v| format_scalar(v, format))),
+                optional(column.mean.map(format_number)),
+                optional(column.stddev.map(format_number)),
+            ]
+        })
+        .collect::<Vec<_>>();
+    // columns are as wide as their values, up to a limit
+    let widths = (0..header_names.len())
+        .map(|i| {
+            let width = cells.iter().map(|row| row[i].chars().count());
+            let width = width.chain([header_names[i].len()]).max().unwrap_or(0);
+            Constraint::Length(width.min(24) as u16)
+        })
+        .collect::<Vec<_>>();
+    let height = table.height.saturating_sub(1) as usize;
+    let offset = (view.selected + 1).saturating_sub(height);
+    let rows = cells
+        .into_iter()
+        .enumerate()
+        .skip(offset)
+        .take(height)
+        .map(|(i, cells)| {
+            let row = widgets::Row::new(cells);
+            match (i == view.selected, focused) {
+                (true, true) => row.style(theme.selection()),
+                (true, false) => row.style(Style::new().add_modifier(Modifier::REVERSED)),
+                _ => row,
+            }
+        })
+        .collect::<Vec<_>>();
+    frame.render_widget(widgets::Table::new(rows, widths).header(header), table);
+
+    if let Some(column) = profile.columns.get(view.selected) {
+        render_column_detail(state, column, frame, detail);
+    }
+}
 
-        let table = widgets::Table::default()
-            .header(header)
-            .rows(rows)
-            .row_highlight_style(Style::from(Color::Red));
-        frame.render_widget(table, area);
+/// Most frequent values as bars and the histogram of numeric values
+fn render_column_detail(state: &State, column: &ColumnProfile, frame: &mut Frame, area: Rect) {
+    let theme = &state.ui.theme;
+    let format = &state.config.format;
+    let block = Block::default()
+        .title(format!(" {} ", column.name))
+        .borders(Borders::TOP)
+        .border_style(theme.separator);
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+    let [top, histogram] =
+        Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(inner);
+
+    let most = column.top.first().map_or(1, |(_, count)| *count).max(1);
+    let labels = column
+        .top
+        .iter()
+        .map(|(value, _)| format_scalar(value, format))
+        .collect::<Vec<_>>();
+    let label_width = labels.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+    let label_width = label_width.min(top.width as usize / 2);
+    let mut lines = vec![Line::from(Span::styled(
+        "top values",
+        Style::new().fg(theme.muted),
+    ))];
+    for ((_, count), label) in column.top.iter().zip(labels) {
+        let label = label.chars().take(label_width).collect::<String>();
+        let text = count.to_string();
+        let room = (top.width as usize).saturating_sub(label_width + text.len() + 3);
+        let bar = "█".repeat((room * count / most).max(1));
+        lines.push(Line::from(vec![
+            Span::raw(format!("{:<width$} ", label, width = label_width)),
+            Span::styled(bar, Style::new().fg(SERIES_COLORS[0])),
+            Span::raw(format!(" {}", text)),
+        ]));
     }
+    frame.render_widget(Paragraph::new(lines), top);
+
+    let Some(data) = &column.histogram else {
+        return;
+    };
+    let [label, bars, range] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Min(1),
+        Constraint::Length(1),
+    ])
+    .areas(histogram);
+    frame.render_widget(
+        Paragraph::new("histogram").style(Style::new().fg(theme.muted)),
+        label,
+    );
+    let values = data
+        .counts
+        .iter()
+        .map(|count| {
+            Bar::default()
+                .value(*count as u64)
+                .text_value(String::new())
+        })
+        .collect::<Vec<_>>();
+    let width = (bars.width / data.counts.len().max(1) as u16)
+        .saturating_sub(1)
+        .max(1);
+    let chart = BarChart::default()
+        .data(BarGroup::default().bars(&values))
+        .bar_width(width)
+        .bar_gap(1)
+        .bar_style(Style::new().fg(SERIES_COLORS[0]));
+    frame.render_widget(chart, bars);
+    let [min, max] =
+        Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(range);
+    let muted = Style::new().fg(theme.muted);
+    frame.render_widget(Paragraph::new(format_number(data.min)).style(muted), min);
+    frame.render_widget(
+        Paragraph::new(format_number(data.max))
+            .style(muted)
+            .alignment(Alignment::Right),
+        max,
+    );
 }
 
 pub fn render(state: &State, frame: &mut Frame) {
     let mut show_help = state.show_help;
 
+    let search_height = match &state.search {
+        Some(search) if search.replacing => 2,
+        Some(_) => 1,
+        None => 0,
+    };
     let layout = Layout::default()
         .direction(Direction::Vertical)
-        .constraints(vec![Constraint::Min(1), Constraint::Length(1)])
+        .constraints(vec![
+            Constraint::Min(1),
+            Constraint::Length(search_height),
+            Constraint::Length(1),
+        ])
         .split(frame.area());
 
-    render_status_line(state, frame, layout[1]);
+    render_status_line(state, frame, layout[2]);
+    if let Some(search) = &state.search {
+        render_search(state, search, frame, layout[1]);
+    }
+
+    let main_area = if state.show_sidebar && !state.cells.order.is_empty() {
+        let [sidebar, main] =
+            Layout::horizontal([Constraint::Length(32), Constraint::Min(1)]).areas(layout[0]);
+        render_sidebar(state, frame, sidebar);
+        main
+    } else {
+        layout[0]
+    };
 
     if let Some(cell) = state.cells.current() {
         let cell_layout = Layout::default()
             .direction(Direction::Horizontal)
             .constraints(vec![Constraint::Percentage(50), Constraint::Percentage(50)])
-            .split(layout[0]);
+            .split(main_area);
 
         if let Some(cell) = state.cells.all.get(&cell.id) {
             frame.render_widget(&state.cells.editor, cell_layout[0]);
 
             match cell.status {
+                _ if cell.kind == CellKind::Markdown => {
+                    // the editor has the latest text while the cell is edited
+                    let text = state.cells.editor.lines().join("\n");
+                    let block = Block::default().padding(Padding::horizontal(1));
+                    let width = block.inner(cell_layout[1]).width;
+                    let lines = markdown::render(&text, width, &state.ui.theme);
+                    frame.render_widget(
+                        Paragraph::new(lines)
+                            .wrap(Wrap { trim: false })
+                            .block(block),
+                        cell_layout[1],
+                    );
+                }
                 CellStatus::Clean => {
-                    let text = indoc! {"
+                    let keymap = &state.ui.keymap;
+                    let leave = if state.ui.vim {
+                        ":q".to_string()
+                    } else {
+                        keymap.keys(Action::LeaveEditor)
+                    };
+                    let text = formatdoc! {"
                         You can write and execute SQL in the DataFusion dialect.
-                        
+
                         Official reference: https://datafusion.apache.org/user-guide/sql/index.html
 
-                        To execute cell, press <Ctrl + x>
-                        You also may press <Esc> to back to the Navigation mode, and then press <x>
-                    "};
+                        To execute cell, press <{}>
+                        You also may press <{}> to back to the Navigation mode, and then press <{}>
+
+                        To limit execution time, add a directive like `-- @timeout 30s`
+                        ",
+                        keymap.keys(Action::SaveAndExecute),
+                        leave,
+                        keymap.keys(Action::Execute),
+                    };
 
                     frame.render_widget(Paragraph::new(text), cell_layout[1]);
                 }
-                CellStatus::Running => {
-                    let area = centered_area(cell_layout[1], 30, 1);
-                    frame.render_widget(Paragraph::new("Running 🏃‍➡️🏃‍♂️‍➡️🏃‍♀️‍➡️ "), area);
+                CellStatus::Running => render_progress(state, cell, frame, cell_layout[1]),
+                _ if state.mode == Mode::History => {
+                    render_history(state, cell, frame, cell_layout[1]);
                 }
-                CellStatus::Finished => {
-                    render_table(state, frame, cell_layout[1]);
+                CellStatus::Finished if cell.diff.is_some() => {
+                    if let Some(view) = &cell.diff {
+                        let focused = state.mode == Mode::Diff;
+                        render_diff(state, view, focused, frame, cell_layout[1]);
+                    }
                 }
+                CellStatus::Finished if cell.profile.is_some() => {
+                    if let Some(view) = &cell.profile {
+                        let focused = state.mode == Mode::Profile;
+                        render_profile(state, view, focused, frame, cell_layout[1]);
+                    }
+                }
+                CellStatus::Finished => match &cell.plan {
+                    Some(view) => render_plan(
+                        view,
+                        state.mode == Mode::Plan,
+                        &state.ui.theme,
+                        frame,
+                        cell_layout[1],
+                    ),
+                    None => match (&cell.chart, &cell.chart_data, &cell.result) {
+                        (Some(chart), Some(data), Some(result)) => render_chart(
+                            state,
+                            chart,
+                            data,
+                            &result.batches,
+                            state.mode == Mode::Chart,
+                            frame,
+                            cell_layout[1],
+                        ),
+                        _ => render_table(
+                            state,
+                            cell,
+                            state.mode == Mode::Result,
+                            frame,
+                            cell_layout[1],
+                        ),
+                    },
+                },
                 CellStatus::Failed => {
                     frame.render_widget(
                         Paragraph::new(cell.error.clone().unwrap_or(String::new()))
-                            .style(Style::new().fg(Color::Red))
+                            .style(Style::new().fg(state.ui.theme.error))
                             .wrap(Wrap::default()),
                         cell_layout[1],
                     );
@@ -266,8 +1937,12 @@ pub fn render(state: &State, frame: &mut Frame) {
         show_help = true;
     }
 
-    if show_help {
-        render_help(frame);
+    if let Some(panel) = &state.settings {
+        render_settings(panel, &state.ui.theme, frame);
+    } else if let Some(palette) = &state.palette {
+        render_palette(state, palette, frame);
+    } else if show_help {
+        render_help(state, frame);
     }
 
     if state.popup.is_some() {
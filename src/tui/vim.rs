@@ -0,0 +1,1531 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::HashMap;
+use std::mem;
+use tui_textarea::{CursorMove, TextArea};
+
+/// Number of changes which can be undone
+const UNDO_LIMIT: usize = 100;
+
+/// Copies made by a single put, larger counts are clamped
+const PUT_LIMIT: usize = 1000;
+
+/// Keys without a printable character are mapped to control characters while parsing commands
+const ENTER: char = '\r';
+const BACKSPACE: char = '\u{8}';
+const REDO: char = '\u{12}';
+
+/// Row and column (in characters) in the editor
+type Pos = (usize, usize);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum VimMode {
+    #[default]
+    Normal,
+    Insert,
+    Visual,
+    VisualLine,
+    /// Typing an ex command after `:`
+    Command,
+}
+
+impl VimMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            VimMode::Normal => "NORMAL",
+            VimMode::Insert => "INSERT",
+            VimMode::Visual => "VISUAL",
+            VimMode::VisualLine => "V-LINE",
+            VimMode::Command => "COMMAND",
+        }
+    }
+}
+
+/// What the editor asks the application to do with the cell
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VimRequest {
+    /// `:w`
+    Save,
+    /// `:q`, `:wq`, `:x`, `ZZ`, the cell is saved on leave
+    Leave,
+    /// `:q!`, `ZQ`
+    Discard,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Register {
+    text: String,
+    linewise: bool,
+}
+
+#[derive(Debug, Clone)]
+struct Snapshot {
+    lines: Vec<String>,
+    cursor: Pos,
+}
+
+impl Snapshot {
+    fn of(editor: &TextArea) -> Self {
+        Self {
+            lines: editor.lines().to_vec(),
+            cursor: editor.cursor(),
+        }
+    }
+}
+
+/// Vim-style modal editing on top of the cell editor.
+/// Registers live as long as the session, undo history is reset when another cell is selected.
+#[derive(Debug, Default)]
+pub struct Vim {
+    pub mode: VimMode,
+    /// Keys of an incomplete command, e.g. `"a2d`
+    pending: String,
+    /// Ex command typed after `:`
+    pub command: String,
+    /// Error of the last command
+    pub message: Option<String>,
+    /// Visual mode selection, the anchor stays where the selection started
+    anchor: Pos,
+    cursor: Pos,
+    registers: HashMap<char, Register>,
+    undo: Vec<Snapshot>,
+    redo: Vec<Snapshot>,
+    /// The editor before insert mode was entered, the whole insertion is undone at once
+    insert_start: Option<Snapshot>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Motion {
+    Left,
+    Right,
+    Up,
+    Down,
+    WordForward(bool),
+    WordEnd(bool),
+    WordBack(bool),
+    LineStart,
+    FirstNonBlank,
+    LineEnd,
+    ParagraphForward,
+    ParagraphBack,
+    MatchPair,
+    FirstLine,
+    LastLine,
+    Find { ch: char, forward: bool, till: bool },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum MotionKind {
+    Exclusive,
+    Inclusive,
+    Linewise,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TextObject {
+    Word(bool),
+    Quote(char),
+    Bracket(char, char),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operator {
+    Delete,
+    Change,
+    Yank,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Target {
+    Motion(Motion),
+    Object {
+        object: TextObject,
+        inner: bool,
+    },
+    /// `dd`, `cc`, `yy`
+    Lines,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Action {
+    Move(Motion),
+    Operate(Operator, Target),
+    /// `i`, `a`, `I`, `A`, `o`, `O`
+    Insert(char),
+    Put {
+        before: bool,
+    },
+    Replace(char),
+    ToggleCase,
+    Join,
+    Undo,
+    Redo,
+    Visual {
+        linewise: bool,
+    },
+    /// Applies the operator to the visual selection
+    VisualOperate(Operator),
+    /// Selects a text object in visual mode
+    VisualSelect {
+        object: TextObject,
+        inner: bool,
+    },
+    /// `o` in visual mode, moves the cursor to the other end of the selection
+    VisualSwap,
+    Ex,
+    Request(VimRequest),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Command {
+    register: Option<char>,
+    count: Option<usize>,
+    action: Action,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Range {
+    /// Characters from the start to the end, exclusive
+    Chars(Pos, Pos),
+    /// First and last line
+    Lines(usize, usize),
+}
+
+#[derive(Debug, PartialEq)]
+enum ParseError {
+    /// More keys are needed
+    Incomplete,
+    Invalid,
+}
+
+struct Keys<'a> {
+    keys: &'a [char],
+    pos: usize,
+}
+
+impl Keys<'_> {
+    fn peek(&self) -> Option<char> {
+        self.keys.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Result<char, ParseError> {
+        let key = self.peek().ok_or(ParseError::Incomplete)?;
+        self.pos += 1;
+        Ok(key)
+    }
+
+    /// Character argument of commands like `f` or `r`
+    fn argument(&mut self) -> Result<char, ParseError> {
+        match self.next()? {
+            c if c.is_control() => Err(ParseError::Invalid),
+            c => Ok(c),
+        }
+    }
+
+    fn count(&mut self) -> Option<usize> {
+        let mut count: Option<usize> = None;
+        while let Some(digit) = self.peek().and_then(|c| c.to_digit(10)) {
+            if digit == 0 && count.is_none() {
+                break; // `0` is a motion
+            }
+            count = Some(
+                count
+                    .unwrap_or_default()
+                    .saturating_mul(10)
+                    .saturating_add(digit as usize),
+            );
+            self.pos += 1;
+        }
+        count
+    }
+}
+
+fn key_char(key: &KeyEvent) -> Option<char> {
+    match key.code {
+        KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => Some(REDO),
+        _ if key.modifiers.contains(KeyModifiers::CONTROL) => None,
+        KeyCode::Char(c) => Some(c),
+        KeyCode::Left => Some('h'),
+        KeyCode::Right => Some('l'),
+        KeyCode::Up => Some('k'),
+        KeyCode::Down => Some('j'),
+        KeyCode::Home => Some('0'),
+        KeyCode::End => Some('$'),
+        KeyCode::Delete => Some('x'),
+        KeyCode::Enter => Some(ENTER),
+        KeyCode::Backspace => Some(BACKSPACE),
+        _ => None,
+    }
+}
+
+fn parse_motion(key: char, keys: &mut Keys) -> Result<Motion, ParseError> {
+    let motion = match key {
+        'h' | BACKSPACE => Motion::Left,
+        'l' | ' ' => Motion::Right,
+        'k' => Motion::Up,
+        'j' | ENTER => Motion::Down,
+        'w' | 'W' => Motion::WordForward(key == 'W'),
+        'e' | 'E' => Motion::WordEnd(key == 'E'),
+        'b' | 'B' => Motion::WordBack(key == 'B'),
+        '0' => Motion::LineStart,
+        '^' => Motion::FirstNonBlank,
+        '$' => Motion::LineEnd,
+        '}' => Motion::ParagraphForward,
+        '{' => Motion::ParagraphBack,
+        '%' => Motion::MatchPair,
+        'G' => Motion::LastLine,
+        'g' => match keys.next()? {
+            'g' => Motion::FirstLine,
+            _ => return Err(ParseError::Invalid),
+        },
+        'f' | 'F' | 't' | 'T' => Motion::Find {
+            ch: keys.argument()?,
+            forward: key == 'f' || key == 't',
+            till: key == 't' || key == 'T',
+        },
+        _ => return Err(ParseError::Invalid),
+    };
+    Ok(motion)
+}
+
+fn parse_object(keys: &mut Keys) -> Result<TextObject, ParseError> {
+    let object = match keys.next()? {
+        'w' => TextObject::Word(false),
+        'W' => TextObject::Word(true),
+        q @ ('"' | '\'' | '`') => TextObject::Quote(q),
+        '(' | ')' | 'b' => TextObject::Bracket('(', ')'),
+        '[' | ']' => TextObject::Bracket('[', ']'),
+        '{' | '}' | 'B' => TextObject::Bracket('{', '}'),
+        '<' | '>' => TextObject::Bracket('<', '>'),
+        _ => return Err(ParseError::Invalid),
+    };
+    Ok(object)
+}
+
+fn parse_operator(key: char) -> Option<Operator> {
+    match key {
+        'd' => Some(Operator::Delete),
+        'c' => Some(Operator::Change),
+        'y' => Some(Operator::Yank),
+        _ => None,
+    }
+}
+
+/// Parses `["x][count]command`, visual mode has its own set of commands
+fn parse(keys: &[char], visual: bool) -> Result<Command, ParseError> {
+    let mut keys = Keys { keys, pos: 0 };
+    let register = match keys.peek() {
+        Some('"') => {
+            keys.next()?;
+            match keys.next()? {
+                r if r.is_ascii_alphanumeric() || r == '"' || r == '_' => Some(r),
+                _ => return Err(ParseError::Invalid),
+            }
+        }
+        _ => None,
+    };
+    let mut count = keys.count();
+    let key = keys.next()?;
+
+    let operate = |op, motion| Action::Operate(op, Target::Motion(motion));
+    let action = match key {
+        ':' => Action::Ex,
+        'u' if !visual => Action::Undo,
+        REDO if !visual => Action::Redo,
+        'v' | 'V' => Action::Visual {
+            linewise: key == 'V',
+        },
+        'J' => Action::Join,
+        '~' => Action::ToggleCase,
+        'p' | 'P' => Action::Put { before: key == 'P' },
+        'o' if visual => Action::VisualSwap,
+        'd' | 'x' if visual => Action::VisualOperate(Operator::Delete),
+        'c' | 's' if visual => Action::VisualOperate(Operator::Change),
+        'y' if visual => Action::VisualOperate(Operator::Yank),
+        'i' | 'a' if visual => Action::VisualSelect {
+            object: parse_object(&mut keys)?,
+            inner: key == 'i',
+        },
+        'i' | 'a' | 'I' | 'A' | 'o' | 'O' => Action::Insert(key),
+        'x' => operate(Operator::Delete, Motion::Right),
+        'X' => operate(Operator::Delete, Motion::Left),
+        's' => operate(Operator::Change, Motion::Right),
+        'D' => operate(Operator::Delete, Motion::LineEnd),
+        'C' => operate(Operator::Change, Motion::LineEnd),
+        'S' => Action::Operate(Operator::Change, Target::Lines),
+        'Y' => Action::Operate(Operator::Yank, Target::Lines),
+        'r' => Action::Replace(keys.argument()?),
+        'Z' => match keys.next()? {
+            'Z' => Action::Request(VimRequest::Leave),
+            'Q' => Action::Request(VimRequest::Discard),
+            _ => return Err(ParseError::Invalid),
+        },
+        _ => match parse_operator(key) {
+            Some(op) => {
+                // counts before and after the operator are multiplied, `2d3w` deletes 6 words
+                if let Some(motion_count) = keys.count() {
+                    count = Some(count.unwrap_or(1).saturating_mul(motion_count));
+                }
+                let target = match keys.next()? {
+                    k if k == key => Target::Lines,
+                    k @ ('i' | 'a') => Target::Object {
+                        object: parse_object(&mut keys)?,
+                        inner: k == 'i',
+                    },
+                    k => Target::Motion(parse_motion(k, &mut keys)?),
+                };
+                Action::Operate(op, target)
+            }
+            None => Action::Move(parse_motion(key, &mut keys)?),
+        },
+    };
+    Ok(Command {
+        register,
+        count,
+        action,
+    })
+}
+
+#[derive(Debug, PartialEq)]
+enum CharClass {
+    Blank,
+    Word,
+    Punctuation,
+}
+
+/// With `big` words are separated by blanks only, like `W` in vim
+fn class(c: char, big: bool) -> CharClass {
+    if c.is_whitespace() {
+        CharClass::Blank
+    } else if big || c.is_alphanumeric() || c == '_' {
+        CharClass::Word
+    } else {
+        CharClass::Punctuation
+    }
+}
+
+fn line_len(lines: &[String], row: usize) -> usize {
+    lines.get(row).map_or(0, |l| l.chars().count())
+}
+
+/// Character at the position, the end of a line is a line break
+fn char_at(lines: &[String], (row, col): Pos) -> char {
+    lines
+        .get(row)
+        .and_then(|l| l.chars().nth(col))
+        .unwrap_or('\n')
+}
+
+fn next_pos(lines: &[String], (row, col): Pos) -> Option<Pos> {
+    if col < line_len(lines, row) {
+        Some((row, col + 1))
+    } else if row + 1 < lines.len() {
+        Some((row + 1, 0))
+    } else {
+        None
+    }
+}
+
+fn prev_pos(lines: &[String], (row, col): Pos) -> Option<Pos> {
+    if col > 0 {
+        Some((row, col - 1))
+    } else if row > 0 {
+        Some((row - 1, line_len(lines, row - 1)))
+    } else {
+        None
+    }
+}
+
+fn is_empty_line(lines: &[String], row: usize) -> bool {
+    lines.get(row).is_none_or(|l| l.trim().is_empty())
+}
+
+fn first_non_blank(lines: &[String], row: usize) -> usize {
+    lines
+        .get(row)
+        .and_then(|l| l.chars().position(|c| !c.is_whitespace()))
+        .unwrap_or_default()
+}
+
+fn text(lines: &[String], (start, end): (Pos, Pos)) -> String {
+    let mut text = String::new();
+    let mut pos = start;
+    while pos < end {
+        text.push(char_at(lines, pos));
+        match next_pos(lines, pos) {
+            Some(next) => pos = next,
+            None => break,
+        }
+    }
+    text
+}
+
+fn word_forward(lines: &[String], start: Pos, big: bool) -> Pos {
+    let mut pos = start;
+    let start_class = class(char_at(lines, pos), big);
+    if start_class != CharClass::Blank {
+        while class(char_at(lines, pos), big) == start_class {
+            match next_pos(lines, pos) {
+                Some(next) => pos = next,
+                None => return pos,
+            }
+        }
+    }
+    // an empty line counts as a word
+    while class(char_at(lines, pos), big) == CharClass::Blank {
+        if pos != start && pos.1 == 0 && line_len(lines, pos.0) == 0 {
+            break;
+        }
+        match next_pos(lines, pos) {
+            Some(next) => pos = next,
+            None => break,
+        }
+    }
+    pos
+}
+
+fn word_end(lines: &[String], start: Pos, big: bool) -> Pos {
+    let Some(mut pos) = next_pos(lines, start) else {
+        return start;
+    };
+    while class(char_at(lines, pos), big) == CharClass::Blank {
+        match next_pos(lines, pos) {
+            Some(next) => pos = next,
+            None => return pos,
+        }
+    }
+    let word_class = class(char_at(lines, pos), big);
+    while let Some(next) = next_pos(lines, pos) {
+        if class(char_at(lines, next), big) != word_class {
+            break;
+        }
+        pos = next;
+    }
+    pos
+}
+
+fn word_back(lines: &[String], start: Pos, big: bool) -> Pos {
+    let Some(mut pos) = prev_pos(lines, start) else {
+        return start;
+    };
+    while class(char_at(lines, pos), big) == CharClass::Blank {
+        if pos.1 == 0 && line_len(lines, pos.0) == 0 {
+            return pos;
+        }
+        match prev_pos(lines, pos) {
+            Some(prev) => pos = prev,
+            None => return pos,
+        }
+    }
+    let word_class = class(char_at(lines, pos), big);
+    while let Some(prev) = prev_pos(lines, pos) {
+        if class(char_at(lines, prev), big) != word_class {
+            break;
+        }
+        pos = prev;
+    }
+    pos
+}
+
+fn paragraph_forward(lines: &[String], mut row: usize, count: usize) -> Pos {
+    for _ in 0..count {
+        while row + 1 < lines.len() && is_empty_line(lines, row) {
+            row += 1;
+        }
+        while row + 1 < lines.len() && !is_empty_line(lines, row) {
+            row += 1;
+        }
+    }
+    if is_empty_line(lines, row) {
+        (row, 0)
+    } else {
+        (row, line_len(lines, row))
+    }
+}
+
+fn paragraph_back(lines: &[String], mut row: usize, count: usize) -> Pos {
+    for _ in 0..count {
+        while row > 0 && is_empty_line(lines, row) {
+            row -= 1;
+        }
+        while row > 0 && !is_empty_line(lines, row) {
+            row -= 1;
+        }
+    }
+    (row, 0)
+}
+
+const PAIRS: [(char, char); 3] = [('(', ')'), ('[', ']'), ('{', '}')];
+
+/// `%`: the bracket matching the first bracket at or after the cursor in the line
+fn match_pair(lines: &[String], (row, col): Pos) -> Option<Pos> {
+    let (col, c) = lines
+        .get(row)?
+        .chars()
+        .enumerate()
+        .skip(col)
+        .find(|(_, c)| PAIRS.iter().any(|(open, close)| c == open || c == close))?;
+    let (open, close) = *PAIRS.iter().find(|(o, cl)| c == *o || c == *cl)?;
+    let step = if c == open { next_pos } else { prev_pos };
+    let mut depth = 0;
+    let mut pos = (row, col);
+    loop {
+        pos = step(lines, pos)?;
+        match char_at(lines, pos) {
+            ch if ch == c => depth += 1,
+            ch if (ch == open || ch == close) && depth == 0 => return Some(pos),
+            ch if ch == open || ch == close => depth -= 1,
+            _ => {}
+        }
+    }
+}
+
+fn find_in_line(
+    lines: &[String],
+    (row, col): Pos,
+    ch: char,
+    forward: bool,
+    till: bool,
+    count: usize,
+) -> Option<Pos> {
+    let chars = lines.get(row)?.chars().collect::<Vec<_>>();
+    let mut found = col;
+    for _ in 0..count {
+        found = if forward {
+            (found + 1..chars.len()).find(|&i| chars[i] == ch)?
+        } else {
+            (0..found).rev().find(|&i| chars[i] == ch)?
+        };
+    }
+    match (till, forward) {
+        (true, true) => Some((row, found - 1)),
+        (true, false) => Some((row, found + 1)),
+        (false, _) => Some((row, found)),
+    }
+}
+
+fn repeat(count: usize, start: Pos, step: impl Fn(Pos) -> Pos) -> Pos {
+    (0..count).fold(start, |pos, _| step(pos))
+}
+
+/// Where the motion moves the cursor, `None` if it cannot be done
+fn motion_target(
+    lines: &[String],
+    cursor: Pos,
+    motion: Motion,
+    count: Option<usize>,
+) -> Option<(Pos, MotionKind)> {
+    let n = count.unwrap_or(1);
+    let (row, col) = cursor;
+    let last = lines.len().saturating_sub(1);
+    let target = match motion {
+        Motion::Left => ((row, col.saturating_sub(n)), MotionKind::Exclusive),
+        Motion::Right => (
+            (row, (col + n).min(line_len(lines, row))),
+            MotionKind::Exclusive,
+        ),
+        Motion::Up => {
+            let row = row.saturating_sub(n);
+            ((row, col.min(line_len(lines, row))), MotionKind::Linewise)
+        }
+        Motion::Down => {
+            let row = (row + n).min(last);
+            ((row, col.min(line_len(lines, row))), MotionKind::Linewise)
+        }
+        Motion::WordForward(big) => (
+            repeat(n, cursor, |p| word_forward(lines, p, big)),
+            MotionKind::Exclusive,
+        ),
+        Motion::WordEnd(big) => (
+            repeat(n, cursor, |p| word_end(lines, p, big)),
+            MotionKind::Inclusive,
+        ),
+        Motion::WordBack(big) => (
+            repeat(n, cursor, |p| word_back(lines, p, big)),
+            MotionKind::Exclusive,
+        ),
+        Motion::LineStart => ((row, 0), MotionKind::Exclusive),
+        Motion::FirstNonBlank => ((row, first_non_blank(lines, row)), MotionKind::Exclusive),
+        Motion::LineEnd => {
+            let row = (row + n - 1).min(last);
+            (
+                (row, line_len(lines, row).saturating_sub(1)),
+                MotionKind::Inclusive,
+            )
+        }
+        Motion::ParagraphForward => (paragraph_forward(lines, row, n), MotionKind::Exclusive),
+        Motion::ParagraphBack => (paragraph_back(lines, row, n), MotionKind::Exclusive),
+        Motion::MatchPair => (match_pair(lines, cursor)?, MotionKind::Inclusive),
+        Motion::FirstLine | Motion::LastLine => {
+            let default = if motion == Motion::FirstLine { 0 } else { last };
+            let row = count.map_or(default, |c| c.saturating_sub(1).min(last));
+            ((row, first_non_blank(lines, row)), MotionKind::Linewise)
+        }
+        Motion::Find { ch, forward, till } => {
+            let kind = if forward {
+                MotionKind::Inclusive
+            } else {
+                MotionKind::Exclusive
+            };
+            (find_in_line(lines, cursor, ch, forward, till, n)?, kind)
+        }
+    };
+    Some(target)
+}
+
+fn motion_range(lines: &[String], cursor: Pos, target: Pos, kind: MotionKind) -> Range {
+    let (start, end) = if target < cursor {
+        (target, cursor)
+    } else {
+        (cursor, target)
+    };
+    match kind {
+        MotionKind::Linewise => Range::Lines(start.0, end.0),
+        MotionKind::Inclusive => {
+            Range::Chars(start, (end.0, (end.1 + 1).min(line_len(lines, end.0))))
+        }
+        // an exclusive motion ending at the start of a line does not take the line break
+        MotionKind::Exclusive if end.1 == 0 && end.0 > start.0 => {
+            Range::Chars(start, (end.0 - 1, line_len(lines, end.0 - 1)))
+        }
+        MotionKind::Exclusive => Range::Chars(start, end),
+    }
+}
+
+fn object_range(lines: &[String], cursor: Pos, object: TextObject, inner: bool) -> Option<Range> {
+    let (row, col) = cursor;
+    let chars = lines.get(row)?.chars().collect::<Vec<_>>();
+    let range = match object {
+        TextObject::Word(big) => {
+            if chars.is_empty() {
+                return None;
+            }
+            let col = col.min(chars.len() - 1);
+            let word_class = class(chars[col], big);
+            let same = |i: usize| class(chars[i], big) == word_class;
+            let blank = |i: usize| class(chars[i], big) == CharClass::Blank;
+            let mut start = col;
+            while start > 0 && same(start - 1) {
+                start -= 1;
+            }
+            let mut end = col + 1;
+            while end < chars.len() && same(end) {
+                end += 1;
+            }
+            if !inner {
+                // a word with trailing blanks, or leading ones if there are none
+                if word_class == CharClass::Blank {
+                    let next_class = chars.get(end).map(|c| class(*c, big));
+                    while end < chars.len() && Some(class(chars[end], big)) == next_class {
+                        end += 1;
+                    }
+                } else if end < chars.len() && blank(end) {
+                    while end < chars.len() && blank(end) {
+                        end += 1;
+                    }
+                } else {
+                    while start > 0 && blank(start - 1) {
+                        start -= 1;
+                    }
+                }
+            }
+            Range::Chars((row, start), (row, end))
+        }
+        TextObject::Quote(quote) => {
+            let quotes = chars
+                .iter()
+                .enumerate()
+                .filter(|(_, c)| **c == quote)
+                .map(|(i, _)| i)
+                .collect::<Vec<_>>();
+            let (start, end) = quotes
+                .chunks_exact(2)
+                .map(|pair| (pair[0], pair[1]))
+                .find(|(_, end)| *end >= col)?;
+            if inner {
+                Range::Chars((row, start + 1), (row, end))
+            } else {
+                Range::Chars((row, start), (row, end + 1))
+            }
+        }
+        TextObject::Bracket(open, close) => {
+            let mut depth = 0;
+            let mut pos = cursor;
+            let start = loop {
+                match char_at(lines, pos) {
+                    c if c == open && depth == 0 => break pos,
+                    c if c == open => depth -= 1,
+                    c if c == close && pos != cursor => depth += 1,
+                    _ => {}
+                }
+                pos = prev_pos(lines, pos)?;
+            };
+            let mut pos = start;
+            let end = loop {
+                pos = next_pos(lines, pos)?;
+                match char_at(lines, pos) {
+                    c if c == close && depth == 0 => break pos,
+                    c if c == close => depth -= 1,
+                    c if c == open => depth += 1,
+                    _ => {}
+                }
+            };
+            if inner {
+                Range::Chars(next_pos(lines, start)?, end)
+            } else {
+                Range::Chars(start, (end.0, end.1 + 1))
+            }
+        }
+    };
+    Some(range)
+}
+
+fn jump(editor: &mut TextArea, (row, col): Pos) {
+    editor.move_cursor(CursorMove::Jump(
+        row.min(u16::MAX as usize) as u16,
+        col.min(u16::MAX as usize) as u16,
+    ));
+}
+
+fn select(editor: &mut TextArea, start: Pos, end: Pos) {
+    editor.cancel_selection();
+    jump(editor, start);
+    editor.start_selection();
+    jump(editor, end);
+}
+
+fn toggle_case(text: &str) -> String {
+    text.chars()
+        .map(|c| {
+            if c.is_uppercase() {
+                c.to_lowercase().collect::<String>()
+            } else {
+                c.to_uppercase().collect::<String>()
+            }
+        })
+        .collect()
+}
+
+impl Vim {
+    /// Keys of an incomplete command
+    pub fn pending(&self) -> &str {
+        &self.pending
+    }
+
+    /// Forgets the state of the previous cell, registers are kept
+    pub fn reset(&mut self) {
+        self.mode = VimMode::Normal;
+        self.pending.clear();
+        self.command.clear();
+        self.message = None;
+        self.undo.clear();
+        self.redo.clear();
+        self.insert_start = None;
+    }
+
+    /// Switches to normal mode, e.g. when the editor is focused again
+    pub fn normal(&mut self, editor: &mut TextArea) {
+        if self.mode == VimMode::Insert {
+            self.leave_insert(editor);
+        }
+        editor.cancel_selection();
+        self.mode = VimMode::Normal;
+        self.pending.clear();
+        self.command.clear();
+    }
+
+    /// Starts in insert mode, used for new cells
+    pub fn insert(&mut self, editor: &TextArea) {
+        self.mode = VimMode::Insert;
+        self.insert_start = Some(Snapshot::of(editor));
+    }
+
+    pub fn input(&mut self, editor: &mut TextArea, key: KeyEvent) -> Option<VimRequest> {
+        self.message = None;
+        match self.mode {
+            VimMode::Insert => {
+                if key.code == KeyCode::Esc {
+                    self.leave_insert(editor);
+                    editor.move_cursor(CursorMove::Back);
+                } else {
+                    editor.input(key);
+                }
+                None
+            }
+            VimMode::Command => self.command_input(editor, key),
+            VimMode::Normal | VimMode::Visual | VimMode::VisualLine => {
+                if key.code == KeyCode::Esc {
+                    if self.pending.is_empty() && self.mode != VimMode::Normal {
+                        self.leave_visual(editor);
+                    }
+                    self.pending.clear();
+                    return None;
+                }
+                self.pending.push(key_char(&key)?);
+                let keys = self.pending.chars().collect::<Vec<_>>();
+                let visual = self.mode != VimMode::Normal;
+                match parse(&keys, visual) {
+                    Ok(command) => {
+                        self.pending.clear();
+                        self.run(editor, command)
+                    }
+                    Err(ParseError::Incomplete) => None,
+                    Err(ParseError::Invalid) => {
+                        self.pending.clear();
+                        None
+                    }
+                }
+            }
+        }
+    }
+
+    fn command_input(&mut self, editor: &mut TextArea, key: KeyEvent) -> Option<VimRequest> {
+        match key.code {
+            KeyCode::Esc => {
+                self.command.clear();
+                self.mode = VimMode::Normal;
+            }
+            KeyCode::Backspace if self.command.pop().is_none() => self.mode = VimMode::Normal,
+            KeyCode::Char(c) => self.command.push(c),
+            KeyCode::Enter => {
+                self.mode = VimMode::Normal;
+                let command = mem::take(&mut self.command);
+                match command.trim() {
+                    "" => {}
+                    "w" => return Some(VimRequest::Save),
+                    "q" | "wq" | "x" => return Some(VimRequest::Leave),
+                    "q!" => return Some(VimRequest::Discard),
+                    line if line.parse::<usize>().is_ok() => {
+                        let lines = editor.lines();
+                        let row = line
+                            .parse::<usize>()
+                            .unwrap_or_default()
+                            .saturating_sub(1)
+                            .min(lines.len().saturating_sub(1));
+                        let col = first_non_blank(lines, row);
+                        jump(editor, (row, col));
+                    }
+                    other => self.message = Some(format!("Not an editor command: {}", other)),
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+
+    /// Runs a complete command, recording the change for undo
+    fn run(&mut self, editor: &mut TextArea, command: Command) -> Option<VimRequest> {
+        let before = Snapshot::of(editor);
+        let request = self.execute(editor, command);
+        if matches!(command.action, Action::Undo | Action::Redo) {
+            return request;
+        }
+        if self.mode == VimMode::Insert {
+            self.insert_start.get_or_insert(before);
+        } else {
+            self.record(editor, before);
+            // in normal mode the cursor is always on a character
+            if self.mode == VimMode::Normal {
+                let (row, col) = editor.cursor();
+                let len = line_len(editor.lines(), row);
+                if len > 0 && col >= len {
+                    jump(editor, (row, len - 1));
+                }
+            }
+        }
+        request
+    }
+
+    fn record(&mut self, editor: &TextArea, before: Snapshot) {
+        if editor.lines() != before.lines.as_slice() {
+            self.undo.push(before);
+            if self.undo.len() > UNDO_LIMIT {
+                self.undo.remove(0);
+            }
+            self.redo.clear();
+        }
+    }
+
+    fn leave_insert(&mut self, editor: &mut TextArea) {
+        self.mode = VimMode::Normal;
+        if let Some(before) = self.insert_start.take() {
+            self.record(editor, before);
+        }
+    }
+
+    fn leave_visual(&mut self, editor: &mut TextArea) {
+        editor.cancel_selection();
+        jump(editor, self.cursor);
+        self.mode = VimMode::Normal;
+    }
+
+    /// Shows the visual mode selection in the editor, it includes the character under the cursor
+    fn sync_selection(&mut self, editor: &mut TextArea) {
+        let lines = editor.lines();
+        let (anchor, cursor) = (self.anchor, self.cursor);
+        let (start, end) = match self.mode {
+            VimMode::VisualLine if cursor.0 >= anchor.0 => {
+                ((anchor.0, 0), (cursor.0, line_len(lines, cursor.0)))
+            }
+            VimMode::VisualLine => ((anchor.0, line_len(lines, anchor.0)), (cursor.0, 0)),
+            _ if cursor >= anchor => (anchor, (cursor.0, cursor.1 + 1)),
+            _ => ((anchor.0, anchor.1 + 1), cursor),
+        };
+        select(editor, start, end);
+    }
+
+    fn visual_range(&self, lines: &[String]) -> Range {
+        let (start, end) = if self.cursor < self.anchor {
+            (self.cursor, self.anchor)
+        } else {
+            (self.anchor, self.cursor)
+        };
+        match self.mode {
+            VimMode::VisualLine => Range::Lines(start.0, end.0),
+            _ => Range::Chars(start, (end.0, (end.1 + 1).min(line_len(lines, end.0)))),
+        }
+    }
+
+    fn register(&self, name: Option<char>) -> Option<&Register> {
+        self.registers
+            .get(&name.unwrap_or('"').to_ascii_lowercase())
+    }
+
+    /// Stores deleted or yanked text, uppercase registers append to the lowercase ones
+    fn store(&mut self, name: Option<char>, value: Register, yank: bool) {
+        let value = match name {
+            Some('_') => return,
+            Some(name) if name.is_ascii_uppercase() => {
+                let register = self.registers.entry(name.to_ascii_lowercase()).or_default();
+                if register.text.is_empty() {
+                    *register = value;
+                } else {
+                    if register.linewise || value.linewise {
+                        register.text.push('\n');
+                    }
+                    register.text.push_str(&value.text);
+                    register.linewise |= value.linewise;
+                }
+                register.clone()
+            }
+            Some(name) if name != '"' => {
+                self.registers.insert(name, value.clone());
+                value
+            }
+            _ => value,
+        };
+        if yank {
+            self.registers.insert('0', value.clone());
+        }
+        self.registers.insert('"', value);
+    }
+
+    fn apply(&mut self, editor: &mut TextArea, op: Operator, range: Range, register: Option<char>) {
+        let lines = editor.lines();
+        match range {
+            Range::Chars(start, end) => {
+                if start >= end {
+                    return;
+                }
+                let value = Register {
+                    text: text(lines, (start, end)),
+                    linewise: false,
+                };
+                self.store(register, value, op == Operator::Yank);
+                select(editor, start, end);
+                match op {
+                    Operator::Yank => {
+                        editor.cancel_selection();
+                        jump(editor, start);
+                    }
+                    Operator::Delete | Operator::Change => {
+                        editor.cut();
+                    }
+                }
+            }
+            Range::Lines(first, last) => {
+                let last = last.min(lines.len().saturating_sub(1));
+                let value = Register {
+                    text: lines[first..=last].join("\n"),
+                    linewise: true,
+                };
+                let end = (last, line_len(lines, last));
+                let (start, end) = if last + 1 < lines.len() {
+                    ((first, 0), (last + 1, 0))
+                } else if first > 0 {
+                    ((first - 1, line_len(lines, first - 1)), end)
+                } else {
+                    ((first, 0), end)
+                };
+                let col = editor.cursor().1;
+                self.store(register, value, op == Operator::Yank);
+                match op {
+                    Operator::Yank => jump(editor, (first, col)),
+                    Operator::Delete => {
+                        select(editor, start, end);
+                        editor.cut();
+                        let lines = editor.lines();
+                        let row = first.min(lines.len().saturating_sub(1));
+                        let col = first_non_blank(lines, row);
+                        jump(editor, (row, col));
+                    }
+                    Operator::Change => {
+                        // the lines are emptied but kept
+                        let (row, col) = (first, first_non_blank(lines, first));
+                        let indent = lines[first].chars().take(col).collect::<String>();
+                        select(editor, (row, 0), (last, line_len(lines, last)));
+                        editor.insert_str(indent);
+                    }
+                }
+            }
+        }
+        if op == Operator::Change {
+            self.mode = VimMode::Insert;
+        }
+    }
+
+    fn put(&self, editor: &mut TextArea, value: Register, before: bool, count: usize) {
+        editor.cancel_selection();
+        let (row, col) = editor.cursor();
+        if value.linewise {
+            let text = vec![value.text.as_str(); count].join("\n");
+            let row = if before {
+                jump(editor, (row, 0));
+                editor.insert_str(format!("{}\n", text));
+                row
+            } else {
+                jump(editor, (row, line_len(editor.lines(), row)));
+                editor.insert_str(format!("\n{}", text));
+                row + 1
+            };
+            let col = first_non_blank(editor.lines(), row);
+            jump(editor, (row, col));
+        } else {
+            if !before && line_len(editor.lines(), row) > 0 {
+                jump(editor, (row, col + 1));
+            }
+            editor.insert_str(value.text.repeat(count));
+            editor.move_cursor(CursorMove::Back);
+        }
+    }
+
+    fn join(&mut self, editor: &mut TextArea, count: usize) {
+        let row = editor.cursor().0;
+        for _ in 0..count {
+            let lines = editor.lines();
+            if row + 1 >= lines.len() {
+                break;
+            }
+            let len = line_len(lines, row);
+            let next = &lines[row + 1];
+            let indent = next.chars().take_while(|c| c.is_whitespace()).count();
+            let separator = if lines[row].trim().is_empty()
+                || next.trim().is_empty()
+                || next.trim_start().starts_with(')')
+            {
+                ""
+            } else {
+                " "
+            };
+            select(editor, (row, len), (row + 1, indent));
+            editor.insert_str(separator);
+            jump(editor, (row, len));
+        }
+    }
+
+    fn execute(&mut self, editor: &mut TextArea, command: Command) -> Option<VimRequest> {
+        let Command {
+            register,
+            count,
+            action,
+        } = command;
+        // every repetition covers at least a character, so a count beyond the size of the text
+        // does nothing more, and cannot overflow positions
+        let size = editor
+            .lines()
+            .iter()
+            .map(|l| l.chars().count() + 1)
+            .sum::<usize>();
+        let count = count.map(|c| match action {
+            Action::Put { .. } => c.min(PUT_LIMIT),
+            Action::Undo | Action::Redo => c.min(UNDO_LIMIT),
+            _ => c.min(size),
+        });
+        let n = count.unwrap_or(1);
+        let visual = self.mode != VimMode::Normal;
+        let cursor = if visual { self.cursor } else { editor.cursor() };
+        match action {
+            Action::Move(motion) => {
+                let (target, _) = motion_target(editor.lines(), cursor, motion, count)?;
+                if visual {
+                    let len = line_len(editor.lines(), target.0);
+                    self.cursor = (target.0, target.1.min(len.saturating_sub(1)));
+                    self.sync_selection(editor);
+                } else {
+                    jump(editor, target);
+                }
+            }
+            Action::Operate(op, target) => {
+                let lines = editor.lines();
+                let range = match target {
+                    Target::Lines => Some(Range::Lines(
+                        cursor.0,
+                        (cursor.0 + n - 1).min(lines.len().saturating_sub(1)),
+                    )),
+                    // `cw` on a word changes to the end of the word like `ce`
+                    Target::Motion(Motion::WordForward(big))
+                        if op == Operator::Change
+                            && class(char_at(lines, cursor), big) != CharClass::Blank =>
+                    {
+                        let word_class = |p| class(char_at(lines, p), big);
+                        let at_word_end = next_pos(lines, cursor)
+                            .is_none_or(|next| word_class(next) != word_class(cursor));
+                        let first = if at_word_end {
+                            cursor
+                        } else {
+                            word_end(lines, cursor, big)
+                        };
+                        let end = repeat(n - 1, first, |p| word_end(lines, p, big));
+                        Some(motion_range(lines, cursor, end, MotionKind::Inclusive))
+                    }
+                    Target::Motion(motion) => motion_target(lines, cursor, motion, count)
+                        .map(|(target, kind)| motion_range(lines, cursor, target, kind)),
+                    Target::Object { object, inner } => object_range(lines, cursor, object, inner),
+                };
+                if let Some(range) = range {
+                    self.apply(editor, op, range, register);
+                }
+            }
+            Action::Insert(key) => {
+                let (row, col) = cursor;
+                let lines = editor.lines();
+                match key {
+                    'a' if line_len(lines, row) > 0 => jump(editor, (row, col + 1)),
+                    'I' => jump(editor, (row, first_non_blank(lines, row))),
+                    'A' => jump(editor, (row, line_len(lines, row))),
+                    'o' => {
+                        jump(editor, (row, line_len(lines, row)));
+                        editor.insert_newline();
+                    }
+                    'O' => {
+                        jump(editor, (row, 0));
+                        editor.insert_newline();
+                        jump(editor, (row, 0));
+                    }
+                    _ => {}
+                }
+                self.mode = VimMode::Insert;
+            }
+            Action::Put { .. } if visual => {
+                let value = self.register(register).cloned();
+                let line_count = editor.lines().len();
+                let range = self.visual_range(editor.lines());
+                self.leave_visual(editor);
+                self.apply(editor, Operator::Delete, range, Some('_'));
+                if let Some(value) = value {
+                    // after deleting the last lines the cursor is on the line above them
+                    let after = matches!(range, Range::Lines(first, last) if first > 0 && last + 1 >= line_count);
+                    self.put(editor, value, !after, n);
+                }
+            }
+            Action::Put { before } => {
+                if let Some(value) = self.register(register).cloned() {
+                    self.put(editor, value, before, n);
+                }
+            }
+            Action::Replace(ch) => {
+                let (row, col) = cursor;
+                if col + n <= line_len(editor.lines(), row) {
+                    select(editor, (row, col), (row, col + n));
+                    editor.insert_str(ch.to_string().repeat(n));
+                    jump(editor, (row, col + n - 1));
+                }
+            }
+            Action::ToggleCase => {
+                let range = if visual {
+                    let range = self.visual_range(editor.lines());
+                    self.leave_visual(editor);
+                    range
+                } else {
+                    let (row, col) = cursor;
+                    Range::Chars(cursor, (row, (col + n).min(line_len(editor.lines(), row))))
+                };
+                let (start, end) = match range {
+                    Range::Chars(start, end) => (start, end),
+                    Range::Lines(first, last) => {
+                        ((first, 0), (last, line_len(editor.lines(), last)))
+                    }
+                };
+                let toggled = toggle_case(&text(editor.lines(), (start, end)));
+                select(editor, start, end);
+                editor.insert_str(toggled);
+                jump(editor, if visual { start } else { end });
+            }
+            Action::Join => {
+                if visual {
+                    let (first, last) = match self.visual_range(editor.lines()) {
+                        Range::Chars(start, end) => (start.0, end.0),
+                        Range::Lines(first, last) => (first, last),
+                    };
+                    self.leave_visual(editor);
+                    jump(editor, (first, 0));
+                    self.join(editor, (last - first).max(1));
+                } else {
+                    self.join(editor, n.max(2) - 1);
+                }
+            }
+            Action::Undo => {
+                for _ in 0..n {
+                    let Some(snapshot) = self.undo.pop() else {
+                        break;
+                    };
+                    self.redo.push(Snapshot::of(editor));
+                    Self::restore(editor, snapshot);
+                }
+            }
+            Action::Redo => {
+                for _ in 0..n {
+                    let Some(snapshot) = self.redo.pop() else {
+                        break;
+                    };
+                    self.undo.push(Snapshot::of(editor));
+                    Self::restore(editor, snapshot);
+                }
+            }
+            Action::Visual { linewise } => {
+                let mode = if linewise {
+                    VimMode::VisualLine
+                } else {
+                    VimMode::Visual
+                };
+                if self.mode == mode {
+                    self.leave_visual(editor);
+                } else {
+                    if !visual {
+                        self.anchor = cursor;
+                        self.cursor = cursor;
+                    }
+                    self.mode = mode;
+                    self.sync_selection(editor);
+                }
+            }
+            Action::VisualOperate(op) => {
+                let range = self.visual_range(editor.lines());
+                self.leave_visual(editor);
+                self.apply(editor, op, range, register);
+            }
+            Action::VisualSelect { object, inner } => {
+                if let Some(Range::Chars(start, end)) =
+                    object_range(editor.lines(), cursor, object, inner)
+                {
+                    self.anchor = start;
+                    self.cursor = prev_pos(editor.lines(), end).unwrap_or(end);
+                    self.sync_selection(editor);
+                }
+            }
+            Action::VisualSwap => {
+                mem::swap(&mut self.anchor, &mut self.cursor);
+                self.sync_selection(editor);
+            }
+            Action::Ex => {
+                if visual {
+                    self.leave_visual(editor);
+                }
+                self.mode = VimMode::Command;
+            }
+            Action::Request(request) => return Some(request),
+        }
+        None
+    }
+
+    fn restore(editor: &mut TextArea, snapshot: Snapshot) {
+        editor.cancel_selection();
+        editor.select_all();
+        editor.insert_str(snapshot.lines.join("\n"));
+        jump(editor, snapshot.cursor);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ESC: char = '\u{1b}';
+
+    fn editor(text: &str, cursor: Pos) -> TextArea<'static> {
+        let mut editor = TextArea::from(text.lines());
+        jump(&mut editor, cursor);
+        editor
+    }
+
+    fn type_keys(vim: &mut Vim, editor: &mut TextArea, keys: &str) -> Option<VimRequest> {
+        let mut request = None;
+        for c in keys.chars() {
+            let code = match c {
+                ESC => KeyCode::Esc,
+                ENTER => KeyCode::Enter,
+                c => KeyCode::Char(c),
+            };
+            request = vim
+                .input(editor, KeyEvent::new(code, KeyModifiers::NONE))
+                .or(request);
+        }
+        request
+    }
+
+    /// Text and cursor after typing the keys in normal mode
+    fn run(text: &str, cursor: Pos, keys: &str) -> (String, Pos) {
+        let mut editor = editor(text, cursor);
+        type_keys(&mut Vim::default(), &mut editor, keys);
+        (editor.lines().join("\n"), editor.cursor())
+    }
+
+    fn cursor(text: &str, cursor: Pos, keys: &str) -> Pos {
+        run(text, cursor, keys).1
+    }
+
+    #[test]
+    fn parse_counts() {
+        let keys = |s: &str| s.chars().collect::<Vec<_>>();
+        let command = parse(&keys("2d3w"), false).unwrap();
+        assert_eq!(command.count, Some(6));
+        assert_eq!(
+            command.action,
+            Action::Operate(Operator::Delete, Target::Motion(Motion::WordForward(false)))
+        );
+        assert_eq!(parse(&keys("0"), false).unwrap().count, None);
+        assert_eq!(parse(&keys("10j"), false).unwrap().count, Some(10));
+        assert_eq!(parse(&keys("\"a"), false), Err(ParseError::Incomplete));
+        assert_eq!(parse(&keys("d"), false), Err(ParseError::Incomplete));
+        assert_eq!(parse(&keys("gx"), false), Err(ParseError::Invalid));
+        let huge = parse(&keys("99999999999999999999999j"), false).unwrap();
+        assert_eq!(huge.count, Some(usize::MAX));
+    }
+
+    #[test]
+    fn motions() {
+        let text = "select a, b\nfrom t\n\nwhere (a = 1)";
+        assert_eq!(cursor(text, (0, 0), "w"), (0, 7));
+        assert_eq!(cursor(text, (0, 0), "3w"), (0, 10));
+        assert_eq!(cursor(text, (0, 0), "e"), (0, 5));
+        assert_eq!(cursor(text, (0, 10), "b"), (0, 8));
+        assert_eq!(cursor(text, (0, 0), "$"), (0, 10));
+        assert_eq!(cursor(text, (1, 3), "0"), (1, 0));
+        assert_eq!(cursor(text, (0, 0), "}"), (2, 0));
+        assert_eq!(cursor(text, (3, 0), "{"), (2, 0));
+        assert_eq!(cursor(text, (0, 0), "G"), (3, 0));
+        assert_eq!(cursor(text, (3, 4), "gg"), (0, 0));
+        assert_eq!(cursor(text, (0, 0), "2G"), (1, 0));
+        assert_eq!(cursor(text, (3, 0), "%"), (3, 12));
+        assert_eq!(cursor(text, (0, 0), "fa"), (0, 7));
+        assert_eq!(cursor(text, (0, 0), "ta"), (0, 6));
+        assert_eq!(cursor(text, (0, 10), "F,"), (0, 8));
+        // the column is kept where the line is long enough
+        assert_eq!(cursor(text, (0, 4), "j"), (1, 4));
+        assert_eq!(cursor(text, (0, 9), "j"), (1, 5));
+    }
+
+    #[test]
+    fn huge_counts() {
+        let text = "one two\nthree";
+        let count = "99999999999999999999999";
+        assert_eq!(cursor(text, (0, 0), &format!("{count}j")), (1, 0));
+        assert_eq!(cursor(text, (0, 0), &format!("{count}w")), (1, 4));
+        assert_eq!(cursor(text, (0, 0), &format!("{count}$")), (1, 4));
+        assert_eq!(run(text, (0, 0), &format!("{count}dd")).0, "");
+        assert_eq!(run(text, (0, 0), &format!("{count}rx")).0, text);
+        assert_eq!(run(text, (0, 0), &format!("{count}x")).0, "\nthree");
+        assert_eq!(run(text, (0, 0), &format!("{count}~")).0, "ONE TWO\nthree");
+        let (pasted, _) = run("a", (0, 0), &format!("yl{count}p"));
+        assert_eq!(pasted.len(), PUT_LIMIT + 1);
+        let (pasted, _) = run("a", (0, 0), &format!("yy{count}p"));
+        assert_eq!(pasted.lines().count(), PUT_LIMIT + 1);
+    }
+
+    #[test]
+    fn operators() {
+        let text = "select a, b\nfrom t";
+        assert_eq!(run(text, (0, 0), "dw").0, "a, b\nfrom t");
+        assert_eq!(run(text, (0, 0), "d2w").0, ", b\nfrom t");
+        assert_eq!(run(text, (0, 7), "d$").0, "select \nfrom t");
+        assert_eq!(run(text, (0, 7), "D").0, "select \nfrom t");
+        assert_eq!(run(text, (0, 0), "dd"), ("from t".into(), (0, 0)));
+        assert_eq!(run(text, (1, 0), "dd"), ("select a, b".into(), (0, 0)));
+        assert_eq!(run(text, (0, 0), "dj").0, "");
+        assert_eq!(run(text, (0, 0), "cwinsert\u{1b}").0, "insert a, b\nfrom t");
+        assert_eq!(run(text, (0, 3), "ciwx\u{1b}").0, "x a, b\nfrom t");
+        assert_eq!(run(text, (0, 3), "daw").0, "a, b\nfrom t");
+        assert_eq!(run(text, (1, 0), "ccwhere\u{1b}").0, "select a, b\nwhere");
+        assert_eq!(run(text, (0, 0), "xx").0, "lect a, b\nfrom t");
+        assert_eq!(run(text, (0, 0), "3rx").0, "xxxect a, b\nfrom t");
+        assert_eq!(run(text, (0, 0), "J").0, "select a, b from t");
+        assert_eq!(run(text, (0, 0), "~").0, "Select a, b\nfrom t");
+    }
+
+    #[test]
+    fn objects() {
+        let text = "where name = 'a b' and (x + (y))";
+        assert_eq!(run(text, (0, 15), "di'").0, "where name = '' and (x + (y))");
+        assert_eq!(run(text, (0, 15), "da'").0, "where name =  and (x + (y))");
+        assert_eq!(run(text, (0, 25), "di(").0, "where name = 'a b' and ()");
+        assert_eq!(run(text, (0, 29), "da(").0, "where name = 'a b' and (x + )");
+    }
+
+    #[test]
+    fn registers_and_put() {
+        let text = "a\nb";
+        assert_eq!(run(text, (0, 0), "yyp").0, "a\na\nb");
+        assert_eq!(run(text, (0, 0), "yyP").0, "a\na\nb");
+        assert_eq!(run(text, (0, 0), "ddp").0, "b\na");
+        assert_eq!(run(text, (0, 0), "yy2p").0, "a\na\na\nb");
+        // deleting into the black hole register keeps the yanked text
+        assert_eq!(run(text, (0, 0), "yyj\"_ddp").0, "a\na");
+        assert_eq!(run(text, (0, 0), "\"ayyj\"Ayy\"ap").0, "a\nb\na\nb");
+        assert_eq!(run("abc", (0, 0), "ylp").0, "aabc");
+        assert_eq!(run("abc", (0, 0), "yl3P").0, "aaaabc");
+    }
+
+    #[test]
+    fn visual() {
+        let text = "select a, b\nfrom t";
+        assert_eq!(run(text, (0, 0), "ved").0, " a, b\nfrom t");
+        assert_eq!(run(text, (0, 0), "Vd").0, "from t");
+        assert_eq!(
+            run(text, (0, 7), "vjy$p").0,
+            "select a, ba, b\nfrom t\nfrom t"
+        );
+        assert_eq!(run(text, (0, 0), "viwU").0, text);
+        assert_eq!(run(text, (0, 0), "viw~").0, "SELECT a, b\nfrom t");
+        assert_eq!(run(text, (0, 0), "Vjd").0, "");
+    }
+
+    #[test]
+    fn undo_and_redo() {
+        let text = "a b c";
+        assert_eq!(run(text, (0, 0), "xxu").0, " b c");
+        assert_eq!(run(text, (0, 0), "xx2u").0, text);
+        assert_eq!(run(text, (0, 0), "xx2u\u{12}").0, " b c");
+        // an insertion is undone at once
+        assert_eq!(run(text, (0, 0), "ixyz\u{1b}u").0, text);
+        assert_eq!(run(text, (0, 0), "99999999999999999999ux").0, " b c");
+    }
+
+    #[test]
+    fn requests() {
+        let mut vim = Vim::default();
+        let mut editor = editor("a", (0, 0));
+        assert_eq!(
+            type_keys(&mut vim, &mut editor, "ZZ"),
+            Some(VimRequest::Leave)
+        );
+        assert_eq!(
+            type_keys(&mut vim, &mut editor, ":w\r"),
+            Some(VimRequest::Save)
+        );
+        assert_eq!(
+            type_keys(&mut vim, &mut editor, ":q!\r"),
+            Some(VimRequest::Discard)
+        );
+        assert_eq!(type_keys(&mut vim, &mut editor, ":nope\r"), None);
+        assert!(vim.message.is_some());
+    }
+}